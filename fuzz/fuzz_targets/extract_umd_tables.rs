@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use universal_markdown::extensions::table::umd::extract_umd_tables;
+
+fuzz_target!(|input: &str| {
+    let _ = extract_umd_tables(input);
+});
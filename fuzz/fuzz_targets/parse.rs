@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use universal_markdown::parse_with_options;
+use universal_markdown::parser::ParserOptions;
+
+fuzz_target!(|input: &str| {
+    let options = ParserOptions::default();
+    let result = parse_with_options(input, &options);
+
+    let size = result.html.len() + result.footnotes.as_ref().map_or(0, String::len);
+    assert!(size <= options.limits.max_output_bytes);
+});
@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use universal_markdown::sanitizer::sanitize;
+
+fuzz_target!(|input: &str| {
+    let output = sanitize(input);
+
+    // Raw HTML never survives sanitization
+    assert!(!output.contains('<'));
+});
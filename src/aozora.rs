@@ -0,0 +1,421 @@
+//! Aozora Bunko notation import
+//!
+//! Converts texts in Aozora Bunko format (青空文庫形式) to Universal
+//! Markdown so they can be rendered with the regular pipeline:
+//!
+//! - Ruby: `｜漢字《かんじ》` and `漢字《かんじ》` → `&ruby(かんじ){漢字};`
+//! - Emphasis marks: `［＃傍点］…［＃傍点終わり］` and `…［＃「…」に傍点］`
+//!   → `&em(sesame){…};` (丸傍点, 二重丸傍点, 黒三角傍点 etc. select the shape)
+//! - Indentation: `［＃２字下げ］` and `［＃ここから２字下げ］`…`［＃ここで字下げ終わり］`
+//!   → `INDENT(2):`; `［＃地付き］` → `RIGHT:`
+//! - Headings: `［＃「…」は大見出し］` and `［＃大見出し］…［＃大見出し終わり］`
+//!   (中見出し, 小見出し) → `#`, `##`, `###`
+//! - Page breaks: `［＃改ページ］`, `［＃改丁］` etc. → `---`
+//!
+//! Each line of the text becomes its own paragraph. The explanation of
+//! symbols enclosed in dashed lines at the top of the file is dropped.
+//! Other annotations are removed and reported as `aozora-unsupported`
+//! diagnostics.
+//!
+//! The text itself is never read as markup: ASCII characters with a
+//! meaning in Markdown or UMD (`|`, `#`, `>`, `//`, `&x;`, `*` etc.) are
+//! written as character references, so they render as they appear in the
+//! source.
+
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+
+use crate::diagnostics::Diagnostic;
+
+// Any annotation, optionally preceded by the gaiji mark ※
+static ANNOTATION: Lazy<Regex> = Lazy::new(|| Regex::new(r"※?［＃([^［］]*)］").unwrap());
+
+// ［＃傍点］…［＃傍点終わり］ (the names are compared by the caller)
+static EMPHASIS_RANGE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"［＃([^［］「」]*傍点)］(.*?)［＃([^［］「」]*傍点)終わり］").unwrap()
+});
+
+// ［＃「target」に傍点］ following its target
+static EMPHASIS_REFERENCE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"［＃「([^」]+)」に([^［］]*傍点)］").unwrap());
+
+// ［＃「target」は大見出し］ following its target
+static HEADING_REFERENCE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"［＃「[^」]+」は([大中小])見出し］").unwrap());
+
+// ［＃大見出し］…［＃大見出し終わり］
+static HEADING_RANGE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"［＃([大中小])見出し(?:終わり)?］").unwrap());
+
+// Explicit ruby base: ｜base《reading》
+static RUBY_EXPLICIT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"｜([^｜《》]+)《([^》]+)》").unwrap());
+
+// Implicit ruby base: the run of kanji before 《reading》
+static RUBY_IMPLICIT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"([\p{Han}々〆〇ヵヶ]+)《([^》]+)》").unwrap());
+
+// Either form, used to keep ruby out of emphasis marks
+static RUBY: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"｜[^｜《》]+《[^》]+》|[\p{Han}々〆〇ヵヶ]+《[^》]+》").unwrap());
+
+static INDENT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^［＃([０-９0-9一二三四五六七八九十]+)字下げ］").unwrap());
+
+static INDENT_BLOCK_START: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^［＃ここから([０-９0-9一二三四五六七八九十]+)字下げ］$").unwrap());
+
+static INDENT_BLOCK_END: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^［＃ここで字下げ終わり］$").unwrap());
+
+static PAGE_BREAK: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^［＃改(?:ページ|丁|段|見開き)］$").unwrap());
+
+/// Convert Aozora Bunko text to Universal Markdown
+///
+/// # Arguments
+///
+/// * `input` - Text in Aozora Bunko format
+///
+/// # Returns
+///
+/// A tuple of (Universal Markdown text, diagnostics for unsupported
+/// annotations)
+///
+/// # Examples
+///
+/// ```
+/// use universal_markdown::aozora::convert;
+///
+/// let (umd, diagnostics) = convert("［＃２字下げ］｜青空《あおぞら》文庫の［＃傍点］本［＃傍点終わり］");
+/// assert_eq!(umd, "INDENT(2): &ruby(あおぞら){青空};文庫の&em(sesame){本};");
+/// assert!(diagnostics.is_empty());
+/// ```
+pub fn convert(input: &str) -> (String, Vec<Diagnostic>) {
+    let mut diagnostics = Vec::new();
+    let mut paragraphs = Vec::new();
+    let mut block_indent: Option<usize> = None;
+    let mut in_explanation = false;
+
+    for line in input.lines() {
+        let line = line.trim_end();
+
+        // Explanation of symbols between dashed lines
+        if line.len() >= 10 && line.chars().all(|c| c == '-') {
+            in_explanation = !in_explanation;
+            continue;
+        }
+        if in_explanation || line.is_empty() {
+            continue;
+        }
+
+        if let Some(caps) = INDENT_BLOCK_START.captures(line) {
+            block_indent = parse_number(&caps[1]);
+            continue;
+        }
+        if INDENT_BLOCK_END.is_match(line) {
+            block_indent = None;
+            continue;
+        }
+        if PAGE_BREAK.is_match(line) {
+            paragraphs.push("---".to_string());
+            continue;
+        }
+
+        paragraphs.push(convert_line(line, block_indent, &mut diagnostics));
+    }
+
+    (paragraphs.join("\n\n"), diagnostics)
+}
+
+/// Convert a single line to a paragraph
+fn convert_line(
+    line: &str,
+    block_indent: Option<usize>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> String {
+    let mut line = escape_text(line.trim_start_matches([' ', '\t']));
+
+    // Line prefixes
+    let mut indent = block_indent;
+    if let Some(caps) = INDENT.captures(&line) {
+        indent = parse_number(&caps[1]);
+        line = line[caps.get(0).unwrap().end()..].to_string();
+    }
+    let mut right = false;
+    if let Some(rest) = line.strip_prefix("［＃地付き］") {
+        right = true;
+        line = rest.to_string();
+    }
+
+    // Headings apply to the whole line
+    let heading = HEADING_REFERENCE
+        .captures(&line)
+        .or_else(|| HEADING_RANGE.captures(&line))
+        .map(|caps| match &caps[1] {
+            "大" => "#",
+            "中" => "##",
+            _ => "###",
+        });
+    if heading.is_some() {
+        line = HEADING_REFERENCE.replace_all(&line, "").into_owned();
+        line = HEADING_RANGE.replace_all(&line, "").into_owned();
+    }
+
+    let line = convert_emphasis(&line, diagnostics);
+    let line = convert_ruby(&line);
+    let line = ANNOTATION
+        .replace_all(&line, |caps: &Captures| {
+            diagnostics.push(Diagnostic::new(
+                "aozora-unsupported",
+                format!("Unsupported annotation removed: {}", &caps[0]),
+            ));
+            if caps[0].starts_with('※') {
+                "※"
+            } else {
+                ""
+            }
+        })
+        .into_owned();
+
+    if let Some(heading) = heading {
+        return format!("{} {}", heading, line);
+    }
+
+    let mut prefix = String::new();
+    if right {
+        prefix.push_str("RIGHT: ");
+    }
+    if let Some(indent) = indent.filter(|&n| n > 0) {
+        prefix.push_str(&format!("INDENT({}): ", indent.min(10)));
+    }
+    prefix + &line
+}
+
+/// Convert emphasis mark ranges and references
+fn convert_emphasis(line: &str, diagnostics: &mut Vec<Diagnostic>) -> String {
+    let line = EMPHASIS_RANGE.replace_all(line, |caps: &Captures| match emphasis_shape(&caps[1]) {
+        Some(shape) if caps[1] == caps[3] => emphasize(shape, &caps[2]),
+        _ => caps[0].to_string(),
+    });
+
+    let mut result = String::with_capacity(line.len());
+    let mut last = 0;
+    for caps in EMPHASIS_REFERENCE.captures_iter(&line) {
+        let found = caps.get(0).unwrap();
+        result.push_str(&line[last..found.start()]);
+        last = found.end();
+
+        let target = &caps[1];
+        match emphasis_shape(&caps[2]) {
+            Some(shape) if result.ends_with(target) => {
+                result.truncate(result.len() - target.len());
+                result.push_str(&emphasize(shape, target));
+            }
+            _ => {
+                diagnostics.push(Diagnostic::new(
+                    "aozora-unsupported",
+                    format!("Unsupported annotation removed: {}", found.as_str()),
+                ));
+            }
+        }
+    }
+    result.push_str(&line[last..]);
+    result
+}
+
+/// Wrap text in `&em()`, leaving ruby outside
+///
+/// Ruby and emphasis marks share the same side of the text, and
+/// decorations cannot be nested, so ruby bases are not emphasized.
+fn emphasize(shape: &str, text: &str) -> String {
+    let mut result = String::new();
+    let mut last = 0;
+    let push_emphasized = |result: &mut String, part: &str| {
+        if !part.is_empty() {
+            result.push_str(&format!("&em({}){{{}}};", shape, part));
+        }
+    };
+    for ruby in RUBY.find_iter(text) {
+        push_emphasized(&mut result, &text[last..ruby.start()]);
+        result.push_str(ruby.as_str());
+        last = ruby.end();
+    }
+    push_emphasized(&mut result, &text[last..]);
+    result
+}
+
+/// Convert ruby, explicit bases first
+fn convert_ruby(line: &str) -> String {
+    let ruby = |caps: &Captures| format!("&ruby({}){{{}}};", escape_reading(&caps[2]), &caps[1]);
+    let line = RUBY_EXPLICIT.replace_all(line, ruby);
+    RUBY_IMPLICIT.replace_all(&line, ruby).into_owned()
+}
+
+/// Write the characters that are markup in Markdown or UMD as character
+/// references
+///
+/// A number followed by `.` or `)` at the start of the line would be an
+/// ordered list item, so its delimiter is escaped too.
+fn escape_text(text: &str) -> String {
+    let digits = text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let mut result = String::with_capacity(text.len());
+    for (index, c) in text.char_indices() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '.' | ')' if digits > 0 && index == digits => {
+                result.push_str(&format!("&#{};", c as u32))
+            }
+            '|' | '#' | '>' | '<' | '-' | '+' | '*' | '_' | '`' | '~' | '[' | ']' | '{' | '}'
+            | '\\' | '/' | ':' | '\'' | '%' | '@' | '=' | '^' => {
+                result.push_str(&format!("&#{};", c as u32))
+            }
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// Escape the separators of `&ruby()` arguments in a reading
+fn escape_reading(reading: &str) -> String {
+    reading
+        .replace('.', "&#46;")
+        .replace(',', "&#44;")
+        .replace('(', "&#40;")
+        .replace(')', "&#41;")
+}
+
+/// Map an Aozora emphasis mark to an `&em()` shape
+fn emphasis_shape(name: &str) -> Option<&'static str> {
+    match name {
+        "傍点" | "白ゴマ傍点" => Some("sesame"),
+        "丸傍点" | "白丸傍点" => Some("circle"),
+        "二重丸傍点" => Some("double-circle"),
+        "黒三角傍点" | "白三角傍点" => Some("triangle"),
+        "黒丸傍点" | "小さい丸傍点" => Some("dot"),
+        _ => None,
+    }
+}
+
+/// Parse Arabic (half or fullwidth) or kanji numerals up to 99
+fn parse_number(text: &str) -> Option<usize> {
+    if let Some(n) = text
+        .chars()
+        .map(|c| match c {
+            '０'..='９' => Some(c as u32 - '０' as u32),
+            _ => c.to_digit(10),
+        })
+        .try_fold(0usize, |acc, d| d.map(|d| acc * 10 + d as usize))
+    {
+        return Some(n);
+    }
+
+    let digit = |c: char| {
+        "一二三四五六七八九"
+            .chars()
+            .position(|d| d == c)
+            .map(|i| i + 1)
+    };
+    match text.split_once('十') {
+        Some((tens, ones)) => {
+            let tens = if tens.is_empty() {
+                1
+            } else {
+                digit(tens.chars().next()?)?
+            };
+            let ones = if ones.is_empty() {
+                0
+            } else {
+                digit(ones.chars().next()?)?
+            };
+            Some(tens * 10 + ones)
+        }
+        None => digit(text.chars().next()?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn umd(input: &str) -> String {
+        convert(input).0
+    }
+
+    #[test]
+    fn test_ruby() {
+        assert_eq!(
+            umd("｜青空文庫《あおぞらぶんこ》"),
+            "&ruby(あおぞらぶんこ){青空文庫};"
+        );
+        assert_eq!(
+            umd("吾輩《わがはい》は猫である"),
+            "&ruby(わがはい){吾輩};は猫である"
+        );
+        assert_eq!(umd("その人々《ひとびと》"), "その&ruby(ひとびと){人々};");
+    }
+
+    #[test]
+    fn test_emphasis() {
+        assert_eq!(
+            umd("［＃丸傍点］重要［＃丸傍点終わり］"),
+            "&em(circle){重要};"
+        );
+        assert_eq!(
+            umd("これは大事［＃「大事」に傍点］だ"),
+            "これは&em(sesame){大事};だ"
+        );
+        assert_eq!(
+            umd("［＃傍点］その漢字《かんじ》を［＃傍点終わり］"),
+            "&em(sesame){その};&ruby(かんじ){漢字};&em(sesame){を};"
+        );
+
+        let (text, diagnostics) = convert("文字［＃「別」に傍点］");
+        assert_eq!(text, "文字");
+        assert_eq!(diagnostics[0].code, "aozora-unsupported");
+    }
+
+    #[test]
+    fn test_indentation_and_headings() {
+        let input = "［＃ここから２字下げ］\n一行目\n二行目\n［＃ここで字下げ終わり］\n［＃地付き］署名\n［＃３字下げ］第一章［＃「第一章」は大見出し］\n［＃中見出し］一［＃中見出し終わり］\n［＃改ページ］";
+        assert_eq!(
+            umd(input),
+            "INDENT(2): 一行目\n\nINDENT(2): 二行目\n\nRIGHT: 署名\n\n# 第一章\n\n## 一\n\n---"
+        );
+    }
+
+    #[test]
+    fn test_explanation_and_unsupported() {
+        let input = "題名\n-------------------------------------------------------\n【テキスト中に現れる記号について】\n《》：ルビ\n-------------------------------------------------------\n※［＃「さんずい＋旦」、第3水準1-86-57］を［＃割り注］x［＃割り注終わり］";
+        let (text, diagnostics) = convert(input);
+        assert_eq!(text, "題名\n\n※をx");
+        assert_eq!(diagnostics.len(), 3);
+    }
+
+    #[test]
+    fn test_markup_is_escaped() {
+        assert_eq!(
+            umd("# a|b - c > d // e &amp; x;"),
+            "&#35; a&#124;b &#45; c &#62; d &#47;&#47; e &amp;amp; x;"
+        );
+        assert_eq!(umd("1. 項目"), "1&#46; 項目");
+        assert_eq!(umd("漢字《a.b,c)》"), "&ruby(a&#46;b&#44;c&#41;){漢字};");
+
+        // The converted text renders as written
+        let html = crate::parse(&umd("- |a| **b** //c &x; 1) 漢字《a.b》"));
+        assert_eq!(
+            html,
+            "<p>- |a| **b** //c &amp;x; 1) <ruby>漢字<rp>(</rp><rt>a&#46;b</rt><rp>)</rp></ruby></p>"
+        );
+    }
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(parse_number("２"), Some(2));
+        assert_eq!(parse_number("12"), Some(12));
+        assert_eq!(parse_number("三"), Some(3));
+        assert_eq!(parse_number("十"), Some(10));
+        assert_eq!(parse_number("二十一"), Some(21));
+        assert_eq!(parse_number("x"), None);
+    }
+}
@@ -0,0 +1,29 @@
+//! Diagnostics reported while parsing
+//!
+//! Problems in the input that the parser recovers from (for example an
+//! invalid `COLOR()` value that is dropped) are not errors; they are
+//! collected as diagnostics and returned in [`crate::ParseResult`].
+
+/// A problem found in the input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Stable identifier of the rule that produced the diagnostic
+    pub code: &'static str,
+    /// Human-readable description
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Create a diagnostic
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - Stable identifier of the rule, e.g. `"invalid-style-value"`
+    /// * `message` - Human-readable description
+    pub fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
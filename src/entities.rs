@@ -0,0 +1,112 @@
+//! HTML character references
+//!
+//! The single table of named character references used by the sanitizer
+//! and the plugin detector, so `&name;` is classified the same way
+//! everywhere: a known reference is an entity, anything else may be a
+//! plugin call (or is escaped as literal text).
+//!
+//! The data is the complete WHATWG list of 2,231 named references; only the
+//! semicolon-terminated forms are recognised, since UMD always requires the
+//! trailing `;`.
+
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+
+/// Length of the longest reference name (`CounterClockwiseContourIntegral`)
+pub const MAX_NAME_LEN: usize = 31;
+
+// Reference names (without `&` and `;`) mapped to their characters
+static NAMED_REFERENCES: Lazy<HashMap<&'static str, &'static str>> = Lazy::new(|| {
+    ::entities::ENTITIES
+        .iter()
+        .filter_map(|e| {
+            let name = e.entity.strip_prefix('&')?.strip_suffix(';')?;
+            Some((name, e.characters))
+        })
+        .collect()
+});
+
+/// Look up the characters of a named reference
+///
+/// # Arguments
+///
+/// * `name` - Reference name without `&` and `;` (case-sensitive)
+///
+/// # Returns
+///
+/// The characters the reference stands for, or `None` if it is not a
+/// named reference
+///
+/// # Examples
+///
+/// ```
+/// use universal_markdown::entities::lookup;
+///
+/// assert_eq!(lookup("rarr"), Some("→"));
+/// assert_eq!(lookup("hearts"), Some("♥"));
+/// assert_eq!(lookup("counter"), None);
+/// ```
+pub fn lookup(name: &str) -> Option<&'static str> {
+    NAMED_REFERENCES.get(name).copied()
+}
+
+/// Check whether `name` is a named character reference
+pub fn is_named_reference(name: &str) -> bool {
+    NAMED_REFERENCES.contains_key(name)
+}
+
+/// Check whether `reference` is a valid character reference
+///
+/// Accepts named references and decimal (`#123`) or hexadecimal (`#x7B`)
+/// numeric references, all without `&` and `;`.
+pub fn is_character_reference(reference: &str) -> bool {
+    let Some(numeric) = reference.strip_prefix('#') else {
+        return is_named_reference(reference);
+    };
+
+    match numeric
+        .strip_prefix('x')
+        .or_else(|| numeric.strip_prefix('X'))
+    {
+        // Hexadecimal: &#xHH;
+        Some(hex) => !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()),
+        // Decimal: &#123;
+        None => !numeric.is_empty() && numeric.chars().all(|c| c.is_ascii_digit()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complete_table() {
+        assert_eq!(::entities::ENTITIES.len(), 2231);
+        // Every name has a semicolon form; legacy forms without it are skipped
+        assert_eq!(NAMED_REFERENCES.len(), 2125);
+        assert_eq!(
+            NAMED_REFERENCES.keys().map(|k| k.len()).max(),
+            Some(MAX_NAME_LEN)
+        );
+    }
+
+    #[test]
+    fn test_named_references_are_case_sensitive() {
+        assert!(is_named_reference("AMP"));
+        assert!(is_named_reference("Prime"));
+        assert!(!is_named_reference("Hearts"));
+        assert_eq!(lookup("acE"), Some("\u{223E}\u{0333}"));
+    }
+
+    #[test]
+    fn test_character_references() {
+        assert!(is_character_reference("nbsp"));
+        assert!(is_character_reference("#123"));
+        assert!(is_character_reference("#x7B"));
+        assert!(!is_character_reference("#"));
+        assert!(!is_character_reference("#x"));
+        assert!(!is_character_reference("#12a"));
+        assert!(!is_character_reference("invalid"));
+        assert!(!is_character_reference(""));
+    }
+}
@@ -5,6 +5,8 @@
 //! - SIZE(value): Bootstrap fs-* classes or inline rem
 //! - TRUNCATE: Bootstrap text-truncate class
 //! - JUSTIFY/RIGHT/CENTER/LEFT: Bootstrap text alignment classes
+//! - VERTICAL: vertical writing mode (縦書き)
+//! - INDENT(n): block indentation of n characters (字下げ, 1 to 10)
 //!
 //! Multiple prefixes can be combined:
 //! - SIZE(1.5): COLOR(primary): CENTER: Text
@@ -13,6 +15,8 @@
 use once_cell::sync::Lazy;
 use regex::Regex;
 
+use crate::extensions::styles::Styles;
+
 /// Block decoration attributes
 #[derive(Default, Debug)]
 struct BlockDecoration {
@@ -27,11 +31,15 @@ struct BlockDecoration {
     truncate: bool,
     // Vertical alignment (for table cells)
     vertical_align: Option<String>,
+    // Vertical writing mode
+    vertical_writing: bool,
+    // Indentation in characters
+    indent: Option<usize>,
 }
 
 impl BlockDecoration {
     /// Convert to HTML class and style attributes
-    fn to_html_attrs(&self) -> (Option<String>, Option<String>) {
+    fn to_html_attrs(&self, inline_styles: &mut Styles) -> (Option<String>, Option<String>) {
         let mut classes = Vec::new();
         let mut styles = Vec::new();
 
@@ -45,6 +53,16 @@ impl BlockDecoration {
             classes.push("text-truncate".to_string());
         }
 
+        // Writing mode
+        if self.vertical_writing {
+            classes.push("writing-mode-vertical-rl".to_string());
+        }
+
+        // Indentation
+        if let Some(indent) = self.indent {
+            classes.push(format!("indent-{}", indent));
+        }
+
         // Vertical alignment
         if let Some(ref valign) = self.vertical_align {
             classes.push(valign.clone());
@@ -55,7 +73,7 @@ impl BlockDecoration {
             if size.starts_with("fs-") {
                 classes.push(size.clone());
             } else {
-                styles.push(format!("font-size: {}", size));
+                inline_styles.apply("font-size", size, &mut classes, &mut styles);
             }
         }
 
@@ -64,7 +82,7 @@ impl BlockDecoration {
             if fg.starts_with("text-") {
                 classes.push(fg.clone());
             } else {
-                styles.push(format!("color: {}", fg));
+                inline_styles.apply("color", fg, &mut classes, &mut styles);
             }
         }
 
@@ -73,7 +91,7 @@ impl BlockDecoration {
             if bg.starts_with("bg-") {
                 classes.push(bg.clone());
             } else {
-                styles.push(format!("background-color: {}", bg));
+                inline_styles.apply("background-color", bg, &mut classes, &mut styles);
             }
         }
 
@@ -111,6 +129,14 @@ static VALIGN_EXTRACT: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"(TOP|MIDDLE|BOTTOM|BASELINE):").unwrap());
 static ALIGN_EXTRACT: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"(JUSTIFY|RIGHT|CENTER|LEFT):").unwrap());
+static VERTICAL_EXTRACT: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*VERTICAL:").unwrap());
+// INDENT may follow other prefixes, but only inside the leading prefix run
+static INDENT_EXTRACT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"^(\s*(?:(?:SIZE\([^)]*\)|COLOR\([^)]*\)|TRUNCATE|TOP|MIDDLE|BOTTOM|BASELINE|JUSTIFY|RIGHT|CENTER|LEFT|VERTICAL):\s*)*)INDENT\((\d+)\):\s*",
+    )
+    .unwrap()
+});
 
 // Block placement pattern for tables and plugins (must start on new line)
 static BLOCK_PLACEMENT: Lazy<Regex> = Lazy::new(|| {
@@ -213,7 +239,19 @@ fn map_vertical_align(value: &str) -> String {
 /// Parse all prefixes from a line and extract decoration attributes
 fn parse_prefixes(line: &str) -> (BlockDecoration, String) {
     let mut decoration = BlockDecoration::default();
+
+    // Extract INDENT, which may appear among the other prefixes
+    let without_indent;
     let mut remaining = line;
+    if let Some(caps) = INDENT_EXTRACT.captures(line) {
+        let indent: usize = caps[2].parse().unwrap_or(0);
+        if (1..=10).contains(&indent) {
+            decoration.indent = Some(indent);
+        }
+        let found = caps.get(0).unwrap();
+        without_indent = format!("{}{}", &caps[1], &line[found.end()..]);
+        remaining = &without_indent;
+    }
 
     // Extract SIZE
     if let Some(caps) = SIZE_EXTRACT.captures(remaining) {
@@ -244,6 +282,12 @@ fn parse_prefixes(line: &str) -> (BlockDecoration, String) {
         remaining = &remaining[caps.get(0).unwrap().end()..];
     }
 
+    // Extract vertical writing mode (before or after the text alignment)
+    if let Some(m) = VERTICAL_EXTRACT.find(remaining) {
+        decoration.vertical_writing = true;
+        remaining = &remaining[m.end()..];
+    }
+
     // Extract text alignment (must be last as it contains the content)
     if let Some(caps) = ALIGN_EXTRACT.captures(remaining) {
         let value = caps.get(1).map_or("", |m| m.as_str());
@@ -251,6 +295,11 @@ fn parse_prefixes(line: &str) -> (BlockDecoration, String) {
         remaining = &remaining[caps.get(0).unwrap().end()..];
     }
 
+    if let Some(m) = VERTICAL_EXTRACT.find(remaining) {
+        decoration.vertical_writing = true;
+        remaining = &remaining[m.end()..];
+    }
+
     (decoration, remaining.trim().to_string())
 }
 
@@ -264,6 +313,20 @@ fn parse_prefixes(line: &str) -> (BlockDecoration, String) {
 ///
 /// HTML with block decorations applied
 pub fn apply_block_decorations(html: &str) -> String {
+    apply_block_decorations_with_styles(html, &mut Styles::default())
+}
+
+/// Apply block decoration prefixes, validating custom style values
+///
+/// # Arguments
+///
+/// * `html` - The HTML content to process
+/// * `styles` - Validator for custom `COLOR()`/`SIZE()` values
+///
+/// # Returns
+///
+/// HTML with block decorations applied
+pub fn apply_block_decorations_with_styles(html: &str, styles: &mut Styles) -> String {
     let mut result = String::new();
 
     for line in html.lines() {
@@ -279,9 +342,11 @@ pub fn apply_block_decorations(html: &str) -> String {
             || line.starts_with("RIGHT:")
             || line.starts_with("CENTER:")
             || line.starts_with("LEFT:")
+            || line.starts_with("VERTICAL:")
+            || line.starts_with("INDENT(")
         {
             let (decoration, content) = parse_prefixes(line);
-            let (class_attr, style_attr) = decoration.to_html_attrs();
+            let (class_attr, style_attr) = decoration.to_html_attrs(styles);
 
             let mut attrs = Vec::new();
             if let Some(class) = class_attr {
@@ -305,6 +370,25 @@ pub fn apply_block_decorations(html: &str) -> String {
     result.trim_end().to_string()
 }
 
+/// Map a block placement prefix to its wrapper classes
+///
+/// # Arguments
+///
+/// * `placement` - `LEFT`, `CENTER`, `RIGHT` or `JUSTIFY`
+///
+/// # Returns
+///
+/// Bootstrap utility classes for the wrapper `<div>`
+pub(crate) fn placement_class(placement: &str) -> &'static str {
+    match placement {
+        "LEFT" => "w-auto",               // Content width, left aligned
+        "CENTER" => "w-auto mx-auto",     // Content width, centered
+        "RIGHT" => "w-auto ms-auto me-0", // Content width, right aligned
+        "JUSTIFY" => "w-100",             // Full width
+        _ => "",
+    }
+}
+
 /// Apply block placement prefixes to tables and block plugins
 ///
 /// Handles LEFT:/CENTER:/RIGHT:/JUSTIFY: prefixes followed by newline
@@ -323,13 +407,7 @@ pub fn apply_block_placement(html: &str) -> String {
             let placement = &caps[1];
             let content = &caps[2];
 
-            let wrapper_class = match placement {
-                "LEFT" => "w-auto", // Content width, left aligned
-                "CENTER" => "w-auto mx-auto", // Content width, centered
-                "RIGHT" => "w-auto ms-auto me-0", // Content width, right aligned
-                "JUSTIFY" => "w-100", // Full width
-                _ => "",
-            };
+            let wrapper_class = placement_class(placement);
 
             // Wrap table or plugin in div with appropriate class
             if content.starts_with('|') {
@@ -385,6 +463,39 @@ mod tests {
         assert!(output.contains("class=\"text-center\""));
     }
 
+    #[test]
+    fn test_vertical_writing() {
+        let output = apply_block_decorations("VERTICAL: 縦書きの文章");
+        assert_eq!(
+            output,
+            "<p class=\"writing-mode-vertical-rl\">縦書きの文章</p>"
+        );
+
+        let output = apply_block_decorations("VERTICAL: CENTER: 中央");
+        assert!(output.contains("class=\"text-center writing-mode-vertical-rl\""));
+        let output = apply_block_decorations("CENTER: VERTICAL: 中央");
+        assert!(output.contains("class=\"text-center writing-mode-vertical-rl\""));
+        assert!(output.contains(">中央</p>"));
+    }
+
+    #[test]
+    fn test_indent() {
+        assert_eq!(
+            apply_block_decorations("INDENT(2): 字下げ"),
+            "<p class=\"indent-2\">字下げ</p>"
+        );
+        let output = apply_block_decorations("RIGHT: INDENT(3): 署名");
+        assert!(output.contains("class=\"text-end indent-3\""));
+        assert!(output.contains(">署名</p>"));
+        // Out of range levels are ignored
+        assert_eq!(apply_block_decorations("INDENT(20): x"), "<p>x</p>");
+        // INDENT only counts as a prefix at the start of the line
+        assert_eq!(
+            apply_block_decorations("Use INDENT(2): for indents"),
+            "Use INDENT(2): for indents"
+        );
+    }
+
     #[test]
     fn test_truncate() {
         let input = "TRUNCATE: Long text that will be truncated";
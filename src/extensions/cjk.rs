@@ -0,0 +1,152 @@
+//! CJK text handling
+//!
+//! Enabled with `ParserOptions::cjk`:
+//!
+//! - Markdown emphasis uses comrak's CJK-friendly delimiter rules, so
+//!   `**「強調」**です` becomes bold
+//! - UMD `''text''` also accepts a single character (`''強''`)
+//! - Soft line breaks between two East Asian wide characters are removed
+//!   instead of rendering as a space
+//!
+//! UMD `''`/`'''` and `__underline__` have no flanking restrictions, so
+//! they already work next to CJK letters and punctuation.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// Check whether a character is East Asian wide or fullwidth
+///
+/// Covers Hangul Jamo, CJK punctuation and symbols, kana, CJK ideographs,
+/// Hangul syllables, and fullwidth forms.
+///
+/// # Examples
+///
+/// ```
+/// use universal_markdown::extensions::cjk::is_wide;
+///
+/// assert!(is_wide('漢'));
+/// assert!(is_wide('。'));
+/// assert!(is_wide('Ａ'));
+/// assert!(!is_wide('A'));
+/// ```
+pub fn is_wide(c: char) -> bool {
+    matches!(
+        c,
+        '\u{1100}'..='\u{115F}'
+            | '\u{2E80}'..='\u{303E}'
+            | '\u{3041}'..='\u{33FF}'
+            | '\u{3400}'..='\u{4DBF}'
+            | '\u{4E00}'..='\u{9FFF}'
+            | '\u{A000}'..='\u{A4CF}'
+            | '\u{AC00}'..='\u{D7A3}'
+            | '\u{F900}'..='\u{FAFF}'
+            | '\u{FE30}'..='\u{FE4F}'
+            | '\u{FF00}'..='\u{FF60}'
+            | '\u{FFE0}'..='\u{FFE6}'
+            | '\u{20000}'..='\u{2FFFD}'
+            | '\u{30000}'..='\u{3FFFD}'
+    )
+}
+
+// Inline elements that may surround a soft break
+const INLINE_TAGS: &str = "a|abbr|b|bdi|bdo|cite|code|del|dfn|em|i|ins|kbd|mark|q|rp|rt|rtc|ruby|s|samp|small|span|strong|sub|sup|u|var";
+
+// A line break with the inline closing tags before it and opening tags after
+static SOFT_BREAK: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(&format!(
+        r"(?:</(?:{tags})>)*\n(?:<(?:{tags})(?:\s[^<>]*)?>)*",
+        tags = INLINE_TAGS
+    ))
+    .unwrap()
+});
+
+// Elements whose line breaks are content
+static PRESERVED: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?s)<(pre|template)[\s>].*?</(?:pre|template)>").unwrap());
+
+/// Remove soft line breaks between East Asian wide characters
+///
+/// A break is removed only when the text on both sides ends and starts
+/// with a wide character, ignoring inline tags in between. Line breaks
+/// inside `<pre>` and `<template>` are kept.
+///
+/// # Arguments
+///
+/// * `html` - Rendered HTML
+///
+/// # Returns
+///
+/// HTML without the soft breaks
+///
+/// # Examples
+///
+/// ```
+/// use universal_markdown::extensions::cjk::remove_soft_breaks;
+///
+/// assert_eq!(
+///     remove_soft_breaks("<p>一行目\n<strong>二行目</strong></p>"),
+///     "<p>一行目<strong>二行目</strong></p>"
+/// );
+/// assert_eq!(remove_soft_breaks("<p>日本語\nEnglish</p>"), "<p>日本語\nEnglish</p>");
+/// ```
+pub fn remove_soft_breaks(html: &str) -> String {
+    let mut result = String::with_capacity(html.len());
+    let mut last = 0;
+    for preserved in PRESERVED.find_iter(html) {
+        result.push_str(&remove_breaks_in_text(&html[last..preserved.start()]));
+        result.push_str(preserved.as_str());
+        last = preserved.end();
+    }
+    result.push_str(&remove_breaks_in_text(&html[last..]));
+    result
+}
+
+fn remove_breaks_in_text(html: &str) -> String {
+    let mut result = String::with_capacity(html.len());
+    let mut last = 0;
+    for found in SOFT_BREAK.find_iter(html) {
+        let before = html[..found.start()].chars().next_back();
+        let after = html[found.end()..].chars().next();
+        result.push_str(&html[last..found.start()]);
+        if before.is_some_and(is_wide) && after.is_some_and(is_wide) {
+            result.push_str(&found.as_str().replace('\n', ""));
+        } else {
+            result.push_str(found.as_str());
+        }
+        last = found.end();
+    }
+    result.push_str(&html[last..]);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_soft_breaks_between_wide_characters() {
+        assert_eq!(remove_soft_breaks("<p>あ\nい\nう</p>"), "<p>あいう</p>");
+        assert_eq!(
+            remove_soft_breaks("<p>한국어\n문장</p>"),
+            "<p>한국어문장</p>"
+        );
+        assert_eq!(
+            remove_soft_breaks("<p><em>強調</em>\n続き。</p>"),
+            "<p><em>強調</em>続き。</p>"
+        );
+    }
+
+    #[test]
+    fn test_other_breaks_kept() {
+        assert_eq!(remove_soft_breaks("<p>a\nb</p>"), "<p>a\nb</p>");
+        assert_eq!(
+            remove_soft_breaks("<p>あ</p>\n<p>い</p>"),
+            "<p>あ</p>\n<p>い</p>"
+        );
+        assert_eq!(
+            remove_soft_breaks("<pre><code>あ\nい</code></pre>"),
+            "<pre><code>あ\nい</code></pre>"
+        );
+        assert_eq!(remove_soft_breaks("あ<br />\nい"), "あ<br />\nい");
+    }
+}
@@ -7,8 +7,16 @@ use once_cell::sync::Lazy;
 use regex::{Captures, Regex};
 use std::collections::HashMap;
 
-use super::plugin_markers;
+use crate::diagnostics::Diagnostic;
+
+use super::deferred::DeferredPlugins;
+use super::definition_list::{self, DefinitionList};
+use super::markers;
+use super::plugin_markers::{self, ProtectedPlugin};
+use super::plugin_syntax::{PluginKind, parse_args};
 use super::preprocessor;
+use super::styles::Styles;
+use super::table::{self, export::TableRecorder, model::Table, plugin::TablePlugin};
 
 /// Escape HTML special characters
 ///
@@ -26,20 +34,15 @@ fn escape_html_text(input: &str) -> String {
         .replace('>', "&gt;")
 }
 
-/// Parse comma-separated args into a vector
-///
-/// # Arguments
-///
-/// * `args` - Comma-separated argument string
-///
-/// # Returns
+/// Escape markup in plugin text interpolated into HTML
 ///
-/// Vector of trimmed argument strings
-fn parse_args(args: &str) -> Vec<String> {
-    if args.trim().is_empty() {
-        return vec![];
-    }
-    args.split(',').map(|s| s.trim().to_string()).collect()
+/// Entities are kept as written, matching the sanitizer, so nested inline
+/// decorations in the text still resolve in post-processing.
+fn escape_markup(input: &str) -> String {
+    input
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
 }
 
 /// Render args as <data> elements
@@ -60,6 +63,34 @@ fn render_args_as_data(args: &str) -> String {
         .join("")
 }
 
+/// Render a plugin call as a `<template>` element or a deferred placeholder
+fn render_plugin(
+    kind: PluginKind,
+    function: &str,
+    args: &str,
+    content: Option<&str>,
+    deferred: &mut Option<&mut DeferredPlugins>,
+) -> String {
+    if let Some(deferred) = deferred.as_deref_mut() {
+        return deferred.defer(kind, function, args, content);
+    }
+
+    let args_html = render_args_as_data(args);
+    let escaped_content = escape_html_text(content.unwrap_or(""));
+
+    if escaped_content.is_empty() {
+        format!(
+            "<template class=\"umd-plugin umd-plugin-{}\">{}</template>",
+            function, args_html
+        )
+    } else {
+        format!(
+            "<template class=\"umd-plugin umd-plugin-{}\">{}{}</template>",
+            function, args_html, escaped_content
+        )
+    }
+}
+
 /// Map font size value to Bootstrap class or inline style
 fn map_font_size_value(value: &str) -> (bool, String) {
     // Check if value has unit (rem, em, px, etc.)
@@ -146,13 +177,27 @@ static TRIPLE_STAR_EMPHASIS: Lazy<Regex> =
 static CUSTOM_HEADER_ID: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"(?m)^(#{1,6})\s+(.+?)\s+\{#([a-zA-Z0-9_-]+)\}\s*$").unwrap());
 
-/// Store custom header IDs and UMD tables during preprocessing
+/// Store custom header IDs, UMD tables and protected syntax during preprocessing
 #[derive(Debug, Clone)]
 pub struct HeaderIdMap {
     /// Maps heading number (1-based) to custom ID
     pub ids: HashMap<usize, String>,
-    /// Maps table markers to HTML content
-    pub tables: Vec<(String, String)>,
+    /// UMD and CSV tables, indexed by their tokens
+    pub tables: Vec<Table>,
+    /// Plugin calls, indexed by their tokens
+    pub plugins: Vec<ProtectedPlugin>,
+    /// Definition lists, indexed by their tokens
+    pub definition_lists: Vec<DefinitionList>,
+    /// `@table(...)` options, indexed by their tokens
+    pub table_plugins: Vec<TablePlugin>,
+    /// Problems found while extracting the protected syntax
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Default for HeaderIdMap {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl HeaderIdMap {
@@ -160,6 +205,10 @@ impl HeaderIdMap {
         Self {
             ids: HashMap::new(),
             tables: Vec::new(),
+            plugins: Vec::new(),
+            definition_lists: Vec::new(),
+            table_plugins: Vec::new(),
+            diagnostics: Vec::new(),
         }
     }
 }
@@ -169,6 +218,9 @@ impl HeaderIdMap {
 /// This function escapes or transforms syntax that would otherwise create
 /// ambiguous parsing situations. It also extracts custom header IDs.
 ///
+/// Protected syntax is replaced by [`markers`] tokens; the input should
+/// already have been passed through [`markers::scrub`].
+///
 /// # Arguments
 ///
 /// * `input` - The raw wiki markup input
@@ -187,6 +239,20 @@ impl HeaderIdMap {
 /// // UMD blockquote is preserved
 /// ```
 pub fn preprocess_conflicts(input: &str) -> (String, HeaderIdMap) {
+    preprocess_conflicts_with_styles(input, &mut Styles::default())
+}
+
+/// Pre-process input, validating custom style values in UMD tables
+///
+/// # Arguments
+///
+/// * `input` - The raw wiki markup input
+/// * `styles` - Validator for custom cell `COLOR()`/`SIZE()` values
+///
+/// # Returns
+///
+/// A tuple of (pre-processed markup, header ID map)
+pub fn preprocess_conflicts_with_styles(input: &str, styles: &mut Styles) -> (String, HeaderIdMap) {
     // Step 1: Remove comments before any other processing
     let mut result = preprocessor::remove_comments(input);
 
@@ -212,54 +278,79 @@ pub fn preprocess_conflicts(input: &str) -> (String, HeaderIdMap) {
         .to_string();
 
     // Handle UMD blockquotes: > ... <
-    // Use a safe marker that won't be affected by HTML escaping
+    // Wrap the content in a token pair that won't be affected by HTML escaping
     result = UMD_BLOCKQUOTE
         .replace_all(&result, |caps: &Captures| {
-            let content = &caps[1];
-            format!("{{{{UMD_BLOCKQUOTE:{}:UMD_BLOCKQUOTE}}}}", content)
+            wrap_pair(markers::BLOCKQUOTE, &caps[1])
         })
         .to_string();
 
+    // Take out @table(...) wrappers and placed tables before the placement
+    // lines are read as block decorations
+    result = table::plugin::protect_table_plugins(&result, &mut header_map.table_plugins);
+
+    // Turn CSV/TSV blocks into table placeholders before they are read as
+    // code blocks or plugin calls
+    result = table::csv::protect_csv_blocks(&result, &mut header_map.tables);
+
     // Protect UMD block decorations (COLOR, SIZE, alignment)
     // These will be applied in post-processing
     let color_prefix = Regex::new(r"(?m)^(COLOR\([^)]*\):\s*.+)$").unwrap();
     result = color_prefix
         .replace_all(&result, |caps: &Captures| {
-            format!("{{{{BLOCK_DECORATION:{}:BLOCK_DECORATION}}}}", &caps[1])
+            wrap_pair(markers::BLOCK_DECORATION, &caps[1])
         })
         .to_string();
 
     let size_prefix = Regex::new(r"(?m)^(SIZE\([^)]+\):\s*.+)$").unwrap();
     result = size_prefix
         .replace_all(&result, |caps: &Captures| {
-            format!("{{{{BLOCK_DECORATION:{}:BLOCK_DECORATION}}}}", &caps[1])
+            wrap_pair(markers::BLOCK_DECORATION, &caps[1])
         })
         .to_string();
 
-    let align_prefix = Regex::new(r"(?m)^((RIGHT|CENTER|LEFT):\s*.+)$").unwrap();
+    let align_prefix =
+        Regex::new(r"(?m)^((RIGHT|CENTER|LEFT|VERTICAL|INDENT\(\d+\)):\s*.+)$").unwrap();
     result = align_prefix
         .replace_all(&result, |caps: &regex::Captures| {
-            format!("{{{{BLOCK_DECORATION:{}:BLOCK_DECORATION}}}}", &caps[1])
+            wrap_pair(markers::BLOCK_DECORATION, &caps[1])
         })
         .to_string();
 
     // Protect inline and block plugin syntax
-    result = plugin_markers::protect_inline_plugins(&result);
-    result = plugin_markers::protect_block_plugins(&result);
+    result = plugin_markers::protect_inline_plugins(&result, &mut header_map.plugins);
+    result = plugin_markers::protect_block_plugins(&result, &mut header_map.plugins);
 
     // Extract and protect UMD tables (before definition lists)
-    let (result, table_map) = crate::extensions::table::umd::extract_umd_tables(&result);
-    header_map.tables = table_map;
+    let result = table::umd::extract_umd_tables_into(
+        &result,
+        &mut header_map.tables,
+        styles,
+        &mut header_map.diagnostics,
+    );
 
     // Process definition lists: :term|definition
-    let result = preprocessor::process_definition_lists(&result);
+    let result =
+        definition_list::protect_definition_lists(&result, &mut header_map.definition_lists);
 
     (result, header_map)
 }
 
+/// Wrap `content` in an open/close token pair of `kind`
+fn wrap_pair(kind: char, content: &str) -> String {
+    format!("{}{}{}", markers::open(kind), content, markers::close(kind))
+}
+
 /// Convert inline decoration function to HTML
 /// Returns None if not a decoration function
-fn convert_inline_decoration_to_html(function: &str, args: &str, content: &str) -> Option<String> {
+fn convert_inline_decoration_to_html(
+    function: &str,
+    args: &str,
+    content: &str,
+    styles: &mut Styles,
+) -> Option<String> {
+    let args = &escape_markup(args);
+    let content = &escape_markup(content);
     match function {
         // Simple wrapper tags without content
         "dfn" => Some(format!("<dfn>{}</dfn>", content)),
@@ -275,10 +366,7 @@ fn convert_inline_decoration_to_html(function: &str, args: &str, content: &str)
         // Tags with attributes
         "ruby" => {
             // &ruby(reading){text}; → <ruby>text<rp>(</rp><rt>reading</rt><rp>)</rp></ruby>
-            Some(format!(
-                "<ruby>{}<rp>(</rp><rt>{}</rt><rp>)</rp></ruby>",
-                content, args
-            ))
+            Some(super::ruby::render_ruby(args, content))
         }
         "time" => {
             // &time(datetime){text}; → <time datetime="datetime">text</time>
@@ -292,6 +380,17 @@ fn convert_inline_decoration_to_html(function: &str, args: &str, content: &str)
             // &bdo(dir){text}; → <bdo dir="dir">text</bdo>
             Some(format!("<bdo dir=\"{}\">{}</bdo>", args, content))
         }
+        "em" => {
+            // &em(sesame){text}; → <span class="text-emphasis-sesame">text</span>
+            let class = emphasis_mark_class(args).unwrap_or_else(|| {
+                styles.report(Diagnostic::new(
+                    "unknown-emphasis-shape",
+                    format!("Unknown emphasis mark shape, using sesame: {}", args.trim()),
+                ));
+                emphasis_mark_class("").unwrap()
+            });
+            Some(format!("<span class=\"{}\">{}</span>", class, content))
+        }
         "lang" => {
             // &lang(locale){text}; → <span lang="locale">text</span>
             Some(format!("<span lang=\"{}\">{}</span>", args, content))
@@ -322,7 +421,8 @@ fn convert_inline_decoration_to_html(function: &str, args: &str, content: &str)
             let link_regex = Regex::new(r"\[([^\]]+)\]\(([^)]+)\)").unwrap();
             if let Some(link_caps) = link_regex.captures(content) {
                 let text = link_caps.get(1).map_or("", |m| m.as_str());
-                let url = link_caps.get(2).map_or("", |m| m.as_str());
+                let url =
+                    crate::sanitizer::sanitize_url(link_caps.get(2).map_or("", |m| m.as_str()));
                 Some(format!(
                     "<a href=\"{}\" class=\"{}\">{}</a>",
                     url, badge_class, text
@@ -337,18 +437,18 @@ fn convert_inline_decoration_to_html(function: &str, args: &str, content: &str)
         "color" => {
             // &color(fg,bg){text}; with Bootstrap support
             let parts: Vec<&str> = args.split(',').collect();
-            let fg = parts.get(0).map_or("", |m| m.trim());
+            let fg = parts.first().map_or("", |m| m.trim());
             let bg = parts.get(1).map_or("", |m| m.trim());
 
             let mut classes = Vec::new();
-            let mut styles = Vec::new();
+            let mut declarations = Vec::new();
 
             if !fg.is_empty() && fg != "inherit" {
                 let (is_class, value) = map_color_value(fg, false);
                 if is_class {
                     classes.push(value);
                 } else {
-                    styles.push(format!("color: {}", value));
+                    styles.apply("color", &value, &mut classes, &mut declarations);
                 }
             }
 
@@ -357,33 +457,48 @@ fn convert_inline_decoration_to_html(function: &str, args: &str, content: &str)
                 if is_class {
                     classes.push(value);
                 } else {
-                    styles.push(format!("background-color: {}", value));
+                    styles.apply("background-color", &value, &mut classes, &mut declarations);
                 }
             }
 
-            if classes.is_empty() && styles.is_empty() {
+            if classes.is_empty() && declarations.is_empty() {
                 Some(content.to_string())
             } else {
                 let mut attrs = Vec::new();
                 if !classes.is_empty() {
                     attrs.push(format!("class=\"{}\"", classes.join(" ")));
                 }
-                if !styles.is_empty() {
-                    attrs.push(format!("style=\"{}\"", styles.join("; ")));
+                if !declarations.is_empty() {
+                    attrs.push(format!("style=\"{}\"", declarations.join("; ")));
                 }
                 Some(format!("<span {}>{}</span>", attrs.join(" "), content))
             }
         }
         "size" => {
             // &size(value){text}; with Bootstrap support
+            let mut classes = Vec::new();
+            let mut declarations = Vec::new();
             let (is_class, value) = map_font_size_value(args);
             if is_class {
-                Some(format!("<span class=\"{}\">{}</span>", value, content))
+                classes.push(value);
             } else {
+                styles.apply("font-size", &value, &mut classes, &mut declarations);
+            }
+
+            if !classes.is_empty() {
                 Some(format!(
-                    "<span style=\"font-size: {}\">{}</span>",
-                    value, content
+                    "<span class=\"{}\">{}</span>",
+                    classes.join(" "),
+                    content
                 ))
+            } else if !declarations.is_empty() {
+                Some(format!(
+                    "<span style=\"{}\">{}</span>",
+                    declarations.join("; "),
+                    content
+                ))
+            } else {
+                Some(content.to_string())
             }
         }
         _ => None,
@@ -392,13 +507,35 @@ fn convert_inline_decoration_to_html(function: &str, args: &str, content: &str)
 
 /// Convert args-only inline decoration function to HTML
 fn convert_inline_decoration_argsonly_to_html(function: &str, args: &str) -> Option<String> {
+    let args = &escape_markup(args);
     match function {
         "sup" => Some(format!("<sup>{}</sup>", args)),
         "sub" => Some(format!("<sub>{}</sub>", args)),
+        // &tcy(12); → <span class="text-combine-upright">12</span>
+        "tcy" => Some(format!(
+            "<span class=\"text-combine-upright\">{}</span>",
+            args
+        )),
         _ => None,
     }
 }
 
+/// Class for an emphasis mark (傍点) shape
+///
+/// A missing shape means `sesame`, the usual mark in Japanese text.
+///
+/// # Returns
+///
+/// The class, or `None` if the shape is unknown
+pub(crate) fn emphasis_mark_class(shape: &str) -> Option<String> {
+    let shape = match shape.trim() {
+        "" => "sesame",
+        shape @ ("dot" | "circle" | "double-circle" | "triangle" | "sesame") => shape,
+        _ => return None,
+    };
+    Some(format!("text-emphasis-{}", shape))
+}
+
 /// Convert no-args inline decoration function to HTML
 fn convert_inline_decoration_noargs_to_html(function: &str) -> Option<String> {
     match function {
@@ -408,57 +545,51 @@ fn convert_inline_decoration_noargs_to_html(function: &str) -> Option<String> {
     }
 }
 
+/// Post-process HTML to restore protected UMD syntax
+///
+/// Plugins are rendered as `<template>` elements.
+///
+/// # Arguments
+///
+/// * `html` - The HTML output from the Markdown parser
+/// * `header_map` - Custom header IDs and UMD tables from pre-processing
+///
+/// # Returns
+///
+/// HTML with markers restored
 pub fn postprocess_conflicts(html: &str, header_map: &HeaderIdMap) -> String {
-    use crate::extensions::block_decorations;
+    postprocess_conflicts_deferred(html, header_map, None, &mut Styles::default(), None)
+}
 
-    // First, unescape quotes within markers to allow proper JSON parsing
-    // comrak escapes quotes in JSON within markers, so we need to restore them
-    // but ONLY within marker boundaries to avoid XSS
-    let result = html.to_string();
-
-    // Helper function to unescape quotes only within markers
-    let unescape_marker_quotes = |input: &str| -> String {
-        let marker_patterns = vec![
-            (
-                r"\{\{DEFINITION_LIST:([^\}]+):DEFINITION_LIST\}\}",
-                "{{DEFINITION_LIST:",
-            ),
-            (
-                r"\{\{INLINE_PLUGIN:([^\}]+):INLINE_PLUGIN\}\}",
-                "{{INLINE_PLUGIN:",
-            ),
-            (
-                r"\{\{BLOCK_PLUGIN:([^\}]+):BLOCK_PLUGIN\}\}",
-                "{{BLOCK_PLUGIN:",
-            ),
-            (
-                r"\{\{BLOCK_PLUGIN_ARGSONLY:([^\}]+):BLOCK_PLUGIN_ARGSONLY\}\}",
-                "{{BLOCK_PLUGIN_ARGSONLY:",
-            ),
-            (
-                r"\{\{INLINE_PLUGIN_ARGSONLY:([^\}]+):INLINE_PLUGIN_ARGSONLY\}\}",
-                "{{INLINE_PLUGIN_ARGSONLY:",
-            ),
-            (
-                r"\{\{INLINE_PLUGIN_NOARGS:([^\}]+):INLINE_PLUGIN_NOARGS\}\}",
-                "{{INLINE_PLUGIN_NOARGS:",
-            ),
-        ];
-
-        let mut result = input.to_string();
-        for (pattern, marker_start) in marker_patterns {
-            let re = Regex::new(pattern).unwrap();
-            result = re
-                .replace_all(&result, |caps: &Captures| {
-                    let content = &caps[0];
-                    content.replace("&quot;", "\"")
-                })
-                .to_string();
-        }
-        result
-    };
+/// Post-process HTML, optionally deferring plugin calls
+///
+/// When `deferred` is given, plugin calls are recorded there and replaced by
+/// placeholders instead of `<template>` elements.
+///
+/// # Arguments
+///
+/// * `html` - The HTML output from the Markdown parser
+/// * `header_map` - Custom header IDs and UMD tables from pre-processing
+/// * `deferred` - Collector for deferred plugin calls
+/// * `styles` - Validator for custom decoration style values
+/// * `recorder` - Recorder of the rendered tables, if they are exported
+///
+/// # Returns
+///
+/// HTML with markers restored
+pub fn postprocess_conflicts_deferred(
+    html: &str,
+    header_map: &HeaderIdMap,
+    mut deferred: Option<&mut DeferredPlugins>,
+    styles: &mut Styles,
+    mut recorder: Option<&mut TableRecorder>,
+) -> String {
+    use crate::extensions::block_decorations;
 
-    let mut result = unescape_marker_quotes(&result);
+    // Restore UMD tables first so plugins in their cells are resolved too
+    let mut result =
+        table::umd::restore_umd_tables(html, &header_map.tables, recorder.as_deref_mut());
+    result = table::plugin::restore_table_plugins(&result, &header_map.table_plugins, recorder);
 
     // Add header IDs: <h1>Title</h1> -> <h1><a href="#id" id="id"></a>Title</h1>
     let mut heading_counter = 0;
@@ -486,7 +617,7 @@ pub fn postprocess_conflicts(html: &str, header_map: &HeaderIdMap) -> String {
         .to_string();
 
     // Restore UMD blockquotes
-    let umd_blockquote_marker = Regex::new(r"\{\{UMD_BLOCKQUOTE:(.+?):UMD_BLOCKQUOTE\}\}").unwrap();
+    let umd_blockquote_marker = Regex::new(&markers::pair_pattern(markers::BLOCKQUOTE)).unwrap();
 
     result = umd_blockquote_marker
         .replace_all(&result, |caps: &Captures| {
@@ -499,156 +630,31 @@ pub fn postprocess_conflicts(html: &str, header_map: &HeaderIdMap) -> String {
         .to_string();
 
     // Restore and apply block decorations
-    let block_decoration_marker =
-        Regex::new(r"<p>\{\{BLOCK_DECORATION:(.+?):BLOCK_DECORATION\}\}</p>").unwrap();
+    let block_decoration_marker = Regex::new(&format!(
+        "<p>{}</p>",
+        markers::pair_pattern(markers::BLOCK_DECORATION)
+    ))
+    .unwrap();
 
     result = block_decoration_marker
         .replace_all(&result, |caps: &Captures| {
             let decoration = &caps[1];
             // Apply block decoration logic
-            block_decorations::apply_block_decorations(decoration)
+            block_decorations::apply_block_decorations_with_styles(decoration, styles)
         })
         .to_string();
 
-    // Restore inline plugins
-    let inline_plugin_marker =
-        Regex::new(r"\{\{INLINE_PLUGIN:(\w+):([\s\S]*?):([\s\S]*?):INLINE_PLUGIN\}\}").unwrap();
-    result = inline_plugin_marker
-        .replace_all(&result, |caps: &Captures| {
-            use base64::{Engine as _, engine::general_purpose};
-            let function = &caps[1];
-            let args = &caps[2];
-            let encoded_content = &caps[3];
-
-            // Decode base64 to get original content
-            let content = general_purpose::STANDARD
-                .decode(encoded_content.as_bytes())
-                .ok()
-                .and_then(|bytes| String::from_utf8(bytes).ok())
-                .unwrap_or_else(|| encoded_content.to_string());
-
-            // Try to convert as inline decoration function
-            if let Some(html) = convert_inline_decoration_to_html(function, args, &content) {
-                return html;
-            }
-
-            // Otherwise, convert to plugin <template>
-            let args_html = render_args_as_data(args);
-            let escaped_content = escape_html_text(&content);
-
-            if escaped_content.is_empty() {
-                format!(
-                    "<template class=\"umd-plugin umd-plugin-{}\">{}</template>",
-                    function, args_html
-                )
-            } else {
-                format!(
-                    "<template class=\"umd-plugin umd-plugin-{}\">{}{}</template>",
-                    function, args_html, escaped_content
-                )
-            }
-        })
-        .to_string();
+    // Restore definition lists (before plugins, which may appear in their items)
+    result = definition_list::restore_definition_lists(&result, &header_map.definition_lists);
 
-    // Restore inline plugins (args only)
-    let inline_plugin_argsonly_marker =
-        Regex::new(r"\{\{INLINE_PLUGIN_ARGSONLY:(\w+):([\s\S]*?):INLINE_PLUGIN_ARGSONLY\}\}")
-            .unwrap();
-    result = inline_plugin_argsonly_marker
+    // Restore plugins
+    let plugin_marker = Regex::new(&markers::token_pattern(markers::PLUGIN)).unwrap();
+    result = plugin_marker
         .replace_all(&result, |caps: &Captures| {
-            let function = &caps[1];
-            let args = &caps[2];
-
-            // Try to convert as inline decoration function
-            if let Some(html) = convert_inline_decoration_argsonly_to_html(function, args) {
-                return html;
+            match lookup(&header_map.plugins, &caps[1]) {
+                Some(plugin) => restore_plugin(plugin, &mut deferred, styles),
+                None => String::new(),
             }
-
-            // Otherwise, convert to plugin <template>
-            let args_html = render_args_as_data(args);
-            format!(
-                "<template class=\"umd-plugin umd-plugin-{}\">{}</template>",
-                function, args_html
-            )
-        })
-        .to_string();
-
-    // Restore inline plugins (no args)
-    let inline_plugin_noargs_marker =
-        Regex::new(r"\{\{INLINE_PLUGIN_NOARGS:(\w+):INLINE_PLUGIN_NOARGS\}\}").unwrap();
-    result = inline_plugin_noargs_marker
-        .replace_all(&result, |caps: &Captures| {
-            let function = &caps[1];
-
-            // Try to convert as inline decoration function
-            if let Some(html) = convert_inline_decoration_noargs_to_html(function) {
-                return html;
-            }
-
-            // Otherwise, convert to plugin <template>
-            format!(
-                "<template class=\"umd-plugin umd-plugin-{}\"></template>",
-                function
-            )
-        })
-        .to_string();
-
-    // Restore block plugins
-    let block_plugin_marker =
-        Regex::new(r"\{\{BLOCK_PLUGIN:(\w+):([\s\S]*?):([\s\S]*?):BLOCK_PLUGIN\}\}").unwrap();
-    result = block_plugin_marker
-        .replace_all(&result, |caps: &Captures| {
-            use base64::{Engine as _, engine::general_purpose};
-            let function = &caps[1];
-            let args = &caps[2];
-            let encoded_content = &caps[3];
-
-            // Decode base64 to get original content
-            let content = general_purpose::STANDARD
-                .decode(encoded_content.as_bytes())
-                .ok()
-                .and_then(|bytes| String::from_utf8(bytes).ok())
-                .unwrap_or_else(|| encoded_content.to_string());
-
-            let args_html = render_args_as_data(args);
-            let escaped_content = escape_html_text(&content);
-
-            if escaped_content.is_empty() {
-                format!(
-                    "<template class=\"umd-plugin umd-plugin-{}\">{}</template>",
-                    function, args_html
-                )
-            } else {
-                format!(
-                    "<template class=\"umd-plugin umd-plugin-{}\">{}{}</template>",
-                    function, args_html, escaped_content
-                )
-            }
-        })
-        .to_string();
-
-    // Restore block plugins (args only, no content)
-    let block_plugin_argsonly_marker =
-        Regex::new(r"\{\{BLOCK_PLUGIN_ARGSONLY:(\w+):([\s\S]*?):BLOCK_PLUGIN_ARGSONLY\}\}")
-            .unwrap();
-    result = block_plugin_argsonly_marker
-        .replace_all(&result, |caps: &Captures| {
-            use base64::{Engine as _, engine::general_purpose};
-            let function = &caps[1];
-            let encoded_args = &caps[2];
-
-            // Decode base64 to get original args
-            let args = general_purpose::STANDARD
-                .decode(encoded_args.as_bytes())
-                .ok()
-                .and_then(|bytes| String::from_utf8(bytes).ok())
-                .unwrap_or_else(|| encoded_args.to_string());
-
-            let args_html = render_args_as_data(&args);
-            format!(
-                "<template class=\"umd-plugin umd-plugin-{}\">{}</template>",
-                function, args_html
-            )
         })
         .to_string();
 
@@ -658,37 +664,85 @@ pub fn postprocess_conflicts(html: &str, header_map: &HeaderIdMap) -> String {
             .unwrap();
     result = wrapped_plugin.replace_all(&result, "$1").to_string();
 
-    // Restore definition lists
-    let definition_list_marker =
-        Regex::new(r"\{\{DEFINITION_LIST:([\s\S]*?):DEFINITION_LIST\}\}").unwrap();
-    result = definition_list_marker
-        .replace_all(&result, |caps: &Captures| {
-            let items_json = &caps[1];
+    // Remove wrapping <p> tags around deferred plugin placeholders
+    let wrapped_placeholder =
+        Regex::new(r"<p>\s*(<!--umd-plugin:[0-9a-f]+:[\w-]+-->)\s*</p>").unwrap();
+    result = wrapped_placeholder.replace_all(&result, "$1").to_string();
 
-            // Parse JSON to get items
-            let items: Vec<(String, String)> = serde_json::from_str(items_json).unwrap_or_default();
+    // Apply Bootstrap default classes and GFM alerts
+    result = apply_bootstrap_enhancements(&result);
 
-            if items.is_empty() {
-                return String::new();
-            }
+    result
+}
 
-            let mut dl_html = String::from("<dl>");
-            for (term, definition) in items {
-                dl_html.push_str(&format!("<dt>{}</dt><dd>{}</dd>", term, definition));
-            }
-            dl_html.push_str("</dl>");
-            dl_html
-        })
-        .to_string();
+/// Look up a side table entry by the index captured from a token
+fn lookup<'a, T>(entries: &'a [T], index: &str) -> Option<&'a T> {
+    index.parse::<usize>().ok().and_then(|i| entries.get(i))
+}
 
-    // Remove wrapping <p> tags around definition lists
-    let wrapped_dl = Regex::new(r"<p>\s*(<dl>.*?</dl>)\s*</p>").unwrap();
-    result = wrapped_dl.replace_all(&result, "$1").to_string();
+/// Render a protected plugin call as a decoration, `<template>` or placeholder
+fn restore_plugin(
+    plugin: &ProtectedPlugin,
+    deferred: &mut Option<&mut DeferredPlugins>,
+    styles: &mut Styles,
+) -> String {
+    let args = plugin.args.as_deref().unwrap_or("");
+
+    // Try to convert as inline decoration function
+    if plugin.kind == PluginKind::Inline {
+        let decoration = match (&plugin.args, &plugin.content) {
+            (_, Some(content)) => {
+                convert_inline_decoration_to_html(&plugin.function, args, content, styles)
+            }
+            (Some(args), None) => {
+                convert_inline_decoration_argsonly_to_html(&plugin.function, args)
+            }
+            (None, None) => convert_inline_decoration_noargs_to_html(&plugin.function),
+        };
+        if let Some(html) = decoration {
+            return html;
+        }
+    }
 
-    // Apply Bootstrap default classes, GFM alerts, and table cell alignment
-    result = apply_bootstrap_enhancements(&result, &header_map);
+    // Otherwise, convert to plugin <template> (or a deferred placeholder)
+    render_plugin(
+        plugin.kind,
+        &plugin.function,
+        args,
+        plugin.content.as_deref(),
+        deferred,
+    )
+}
 
-    result
+/// Replace tokens left over after post-processing
+///
+/// Tokens inside code sections are not transformed; they are turned back
+/// into the escaped syntax they replaced.
+///
+/// # Arguments
+///
+/// * `html` - Post-processed HTML
+/// * `header_map` - Side tables from pre-processing
+///
+/// # Returns
+///
+/// HTML without any tokens
+pub fn restore_leftover_tokens(html: &str, header_map: &HeaderIdMap) -> String {
+    markers::restore_leftovers(html, |kind, index| match kind {
+        markers::PLUGIN => header_map
+            .plugins
+            .get(index)
+            .map(|p| escape_markup(&escape_html_text(&p.source))),
+        markers::DEFINITION_LIST => header_map
+            .definition_lists
+            .get(index)
+            .map(|l| escape_markup(&escape_html_text(&l.source))),
+        markers::TABLE => header_map
+            .tables
+            .get(index)
+            .map(table::umd::render_source_table),
+        _ => None,
+    })
 }
 
 /// Apply Bootstrap 5 enhancements to HTML
@@ -697,7 +751,7 @@ pub fn postprocess_conflicts(html: &str, header_map: &HeaderIdMap) -> String {
 /// - Add default `blockquote` class to all <blockquote> elements (except UMD-style)
 /// - Convert GFM alerts ([!NOTE], etc.) to Bootstrap alert components
 /// - Add JUSTIFY support for tables (w-100 class)
-fn apply_bootstrap_enhancements(html: &str, header_map: &HeaderIdMap) -> String {
+fn apply_bootstrap_enhancements(html: &str) -> String {
     let mut result = html.to_string();
 
     // Add default class to tables
@@ -741,87 +795,9 @@ fn apply_bootstrap_enhancements(html: &str, header_map: &HeaderIdMap) -> String
         })
         .to_string();
 
-    // Restore UMD tables
-    // comrak wraps markers in <p> tags and strips newlines
-    for (marker, html) in &header_map.tables {
-        let marker_text = marker.trim();
-        let comrak_marker = format!("<p>{}</p>", marker_text);
-        result = result.replace(&comrak_marker, html);
-    }
-
-    // Process table cell vertical alignment prefixes (for GFM tables only)
-    result = process_table_cell_alignment(&result, header_map);
-
-    result
-}
-
-/// Process table cell alignment prefixes (TOP:, MIDDLE:, BOTTOM:, BASELINE:)
-///
-/// Detects alignment prefixes in table cells and adds Bootstrap alignment classes.
-/// Note: GFM tables are handled by comrak without extensions.
-/// UMD tables have their own cell spanning and decoration support.
-fn process_table_cell_alignment(html: &str, _header_map: &HeaderIdMap) -> String {
-    let mut result = html.to_string();
-
-    // Process <td> tags
-    let td_pattern = Regex::new(r"<td([^>]*)>(.*?)</td>").unwrap();
-    result = td_pattern
-        .replace_all(&result, |caps: &Captures| {
-            let existing_attrs = &caps[1];
-            let content = &caps[2];
-            process_cell_content("td", existing_attrs, content)
-        })
-        .to_string();
-
-    // Process <th> tags
-    let th_pattern = Regex::new(r"<th([^>]*)>(.*?)</th>").unwrap();
-    result = th_pattern
-        .replace_all(&result, |caps: &Captures| {
-            let existing_attrs = &caps[1];
-            let content = &caps[2];
-            process_cell_content("th", existing_attrs, content)
-        })
-        .to_string();
-
     result
 }
 
-/// Process individual cell content for alignment
-fn process_cell_content(tag: &str, existing_attrs: &str, content: &str) -> String {
-    // Check for vertical alignment prefixes
-    let (align_class, remaining_content) =
-        if let Some(stripped) = content.trim_start().strip_prefix("TOP:") {
-            ("align-top", stripped.trim_start())
-        } else if let Some(stripped) = content.trim_start().strip_prefix("MIDDLE:") {
-            ("align-middle", stripped.trim_start())
-        } else if let Some(stripped) = content.trim_start().strip_prefix("BOTTOM:") {
-            ("align-bottom", stripped.trim_start())
-        } else if let Some(stripped) = content.trim_start().strip_prefix("BASELINE:") {
-            ("align-baseline", stripped.trim_start())
-        } else {
-            ("", content)
-        };
-
-    if align_class.is_empty() {
-        // No alignment prefix, return original
-        format!("<{}{}>{}</{}>", tag, existing_attrs, content, tag)
-    } else {
-        // Add alignment class
-        if existing_attrs.contains("class=") {
-            // Append to existing class attribute
-            let new_attrs =
-                existing_attrs.replace("class=\"", &format!("class=\"{} ", align_class));
-            format!("<{}{}>{}</{}>", tag, new_attrs, remaining_content, tag)
-        } else {
-            // Add new class attribute
-            format!(
-                r#"<{} class="{}"{}>{}</{}>"#,
-                tag, align_class, existing_attrs, remaining_content, tag
-            )
-        }
-    }
-}
-
 /// Check if input contains potentially ambiguous syntax
 ///
 /// Used for diagnostics and warnings. Returns descriptions of
@@ -866,15 +842,15 @@ mod tests {
     fn test_umd_blockquote_preprocessing() {
         let input = "> This is a UMD quote <";
         let (output, _) = preprocess_conflicts(input);
-        assert!(output.contains("{{UMD_BLOCKQUOTE:"));
+        assert!(output.starts_with(&markers::open(markers::BLOCKQUOTE)));
         assert!(!output.starts_with(">"));
     }
 
     #[test]
     fn test_umd_blockquote_postprocessing() {
         let header_map = HeaderIdMap::new();
-        let input = "{{UMD_BLOCKQUOTE:Test content:UMD_BLOCKQUOTE}}";
-        let output = postprocess_conflicts(input, &header_map);
+        let input = wrap_pair(markers::BLOCKQUOTE, "Test content");
+        let output = postprocess_conflicts(&input, &header_map);
         assert!(output.contains("<blockquote class=\"umd-blockquote\">Test content</blockquote>"));
     }
 
@@ -999,8 +975,8 @@ mod tests {
     #[test]
     fn test_umd_blockquote_no_bootstrap_class() {
         let header_map = HeaderIdMap::new();
-        let input = "{{UMD_BLOCKQUOTE:Test content:UMD_BLOCKQUOTE}}";
-        let output = postprocess_conflicts(input, &header_map);
+        let input = wrap_pair(markers::BLOCKQUOTE, "Test content");
+        let output = postprocess_conflicts(&input, &header_map);
         assert!(output.contains(r#"<blockquote class="umd-blockquote">"#));
         assert!(!output.contains(r#"class="blockquote""#));
     }
@@ -1008,16 +984,18 @@ mod tests {
     #[test]
     fn test_definition_list() {
         let input = ":Term 1|Definition 1\n:Term 2|Definition 2";
-        let (preprocessed, _) = preprocess_conflicts(input);
-        assert!(preprocessed.contains("{{DEFINITION_LIST:"));
+        let (preprocessed, header_map) = preprocess_conflicts(input);
+        assert!(preprocessed.starts_with(&format!(
+            "\n{}\n",
+            markers::token(markers::DEFINITION_LIST, 0)
+        )));
+        assert_eq!(header_map.definition_lists[0].items.len(), 2);
     }
 
     #[test]
     fn test_definition_list_html_output() {
-        let header_map = HeaderIdMap::new();
         let input = ":HTML|HyperText Markup Language\n:CSS|Cascading Style Sheets";
-        let (preprocessed, _) = preprocess_conflicts(input);
-        let output = postprocess_conflicts(&preprocessed, &header_map);
+        let output = crate::parse(input);
         assert!(output.contains("<dl>"));
         assert!(output.contains("<dt>HTML</dt>"));
         assert!(output.contains("<dd>HyperText Markup Language</dd>"));
@@ -1027,23 +1005,40 @@ mod tests {
     }
 
     #[test]
-    fn test_table_cell_vertical_alignment() {
-        let header_map = HeaderIdMap::new();
-        let input =
-            r#"<table class="table"><tr><td>TOP: Cell1</td><td>MIDDLE: Cell2</td></tr></table>"#;
-        let output = postprocess_conflicts(input, &header_map);
-        assert!(output.contains(r#"class="align-top""#));
-        assert!(output.contains("Cell1"));
-        assert!(output.contains(r#"class="align-middle""#));
-        assert!(output.contains("Cell2"));
+    fn test_definition_list_escapes_items() {
+        let input = ":<img src=x onerror=alert(1)>|<b>x</b>";
+        let output = crate::parse(input);
+        assert!(output.contains("<dt>&lt;img src=x onerror=alert(1)&gt;</dt>"));
+        assert!(output.contains("<dd>&lt;b&gt;x&lt;/b&gt;</dd>"));
     }
 
     #[test]
-    fn test_table_cell_multiple_alignments() {
-        let header_map = HeaderIdMap::new();
-        let input = r#"<table><tr><th>BASELINE: Header</th><td>BOTTOM: Data</td></tr></table>"#;
-        let output = postprocess_conflicts(input, &header_map);
-        assert!(output.contains(r#"class="align-baseline""#));
-        assert!(output.contains(r#"class="align-bottom""#));
+    fn test_typed_markers_are_text() {
+        let input = "{{DEFINITION_LIST:[[\"<img onerror=x>\",\"x\"]]:DEFINITION_LIST}}\n\n\
+                     {{UMD_BLOCKQUOTE:x:UMD_BLOCKQUOTE}} <!--CODE_BLOCK_0-->";
+        let (preprocessed, header_map) = preprocess_conflicts(input);
+        assert_eq!(preprocessed, input);
+        let output = postprocess_conflicts(&preprocessed, &header_map);
+        assert!(!output.contains("<dl>"));
+        assert!(!output.contains("<blockquote"));
+    }
+
+    #[test]
+    fn test_inline_decoration_escapes_args_and_content() {
+        let input = "&time(x\" onmouseover=\"a){<img src=x>};";
+        let (preprocessed, header_map) = preprocess_conflicts(input);
+        let output = postprocess_conflicts(&preprocessed, &header_map);
+        assert_eq!(
+            output,
+            "<time datetime=\"x&quot; onmouseover=&quot;a\">&lt;img src=x&gt;</time>"
+        );
+    }
+
+    #[test]
+    fn test_leftover_plugin_token_restores_source() {
+        let (preprocessed, header_map) = preprocess_conflicts("&f(<a>);");
+        let html = format!("<code>{}</code>", preprocessed);
+        let output = restore_leftover_tokens(&html, &header_map);
+        assert_eq!(output, "<code>&amp;f(&lt;a&gt;);</code>");
     }
 }
@@ -0,0 +1,286 @@
+//! Deferred plugin resolution
+//!
+//! In deferred mode the parser does not emit `<template>` elements for
+//! plugins. Each call is replaced by an HTML comment placeholder that
+//! carries a per-parse nonce and a stable id, and is recorded as a
+//! [`PendingPlugin`]. The host resolves the pending calls (possibly
+//! asynchronously) and splices the trusted results in with [`hydrate_html`],
+//! without parsing the document again.
+//!
+//! Placeholders cannot be forged from user input: raw `<` is always escaped
+//! by the sanitizer, and hydration only replaces placeholders that carry the
+//! nonce of the parse that produced them.
+
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::plugin_syntax::{PluginKind, parse_args};
+
+static NONCE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// Placeholder emitted for a deferred plugin: <!--umd-plugin:nonce:id-->
+static PLACEHOLDER: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"<!--umd-plugin:([0-9a-f]+):([\w-]+)-->").unwrap());
+
+/// A plugin call waiting to be resolved by the host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingPlugin {
+    /// Stable id derived from the call itself; identical calls share an id
+    pub id: String,
+    /// Inline (`&`) or block (`@`) plugin
+    pub kind: PluginKind,
+    /// Plugin function name
+    pub name: String,
+    /// Trimmed, comma-separated arguments
+    pub args: Vec<String>,
+    /// Raw body text, if the call has one
+    pub body: Option<String>,
+}
+
+/// Collects deferred plugin calls during a single parse
+#[derive(Debug, Clone)]
+pub struct DeferredPlugins {
+    nonce: String,
+    pending: Vec<PendingPlugin>,
+}
+
+impl Default for DeferredPlugins {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeferredPlugins {
+    /// Create a collector with a fresh random nonce
+    pub fn new() -> Self {
+        Self {
+            nonce: generate_nonce(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// The nonce embedded in every placeholder of this parse
+    pub fn nonce(&self) -> &str {
+        &self.nonce
+    }
+
+    /// Pending calls in order of first appearance
+    pub fn pending(&self) -> &[PendingPlugin] {
+        &self.pending
+    }
+
+    /// Consume the collector and return the pending calls
+    pub fn into_pending(self) -> Vec<PendingPlugin> {
+        self.pending
+    }
+
+    /// Record a plugin call and return its placeholder
+    pub(crate) fn defer(
+        &mut self,
+        kind: PluginKind,
+        name: &str,
+        args: &str,
+        body: Option<&str>,
+    ) -> String {
+        let args = parse_args(args);
+        let body = body.filter(|b| !b.is_empty()).map(str::to_string);
+        let id = stable_id(kind, name, &args, body.as_deref());
+
+        if !self.pending.iter().any(|p| p.id == id) {
+            self.pending.push(PendingPlugin {
+                id: id.clone(),
+                kind,
+                name: name.to_string(),
+                args,
+                body,
+            });
+        }
+
+        format!("<!--umd-plugin:{}:{}-->", self.nonce, id)
+    }
+}
+
+/// Replace deferred plugin placeholders with resolved HTML
+///
+/// Only placeholders carrying `nonce` are considered. Placeholders whose id
+/// has no entry in `results` are left in place, so hydration can be done
+/// incrementally as results arrive.
+///
+/// # Arguments
+///
+/// * `html` - HTML rendered in deferred mode
+/// * `nonce` - The nonce of the parse that produced `html`
+/// * `results` - Trusted plugin output keyed by [`PendingPlugin::id`]
+///
+/// # Returns
+///
+/// HTML with resolved plugin output spliced in
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+/// use universal_markdown::extensions::deferred::hydrate_html;
+/// use universal_markdown::parse_with_options;
+/// use universal_markdown::parser::ParserOptions;
+///
+/// let options = ParserOptions {
+///     deferred_plugins: true,
+///     ..Default::default()
+/// };
+/// let result = parse_with_options("Visits: &counter;", &options);
+/// let nonce = result.plugin_nonce.as_deref().unwrap();
+/// let id = result.pending_plugins[0].id.clone();
+///
+/// let results = HashMap::from([(id, "<b>42</b>".to_string())]);
+/// let html = hydrate_html(&result.html, nonce, &results);
+/// assert!(html.contains("Visits: <b>42</b>"));
+/// ```
+pub fn hydrate_html(html: &str, nonce: &str, results: &HashMap<String, String>) -> String {
+    PLACEHOLDER
+        .replace_all(html, |caps: &Captures| {
+            if &caps[1] != nonce {
+                return caps[0].to_string();
+            }
+            match results.get(&caps[2]) {
+                Some(output) => output.clone(),
+                None => caps[0].to_string(),
+            }
+        })
+        .to_string()
+}
+
+/// Generate a per-parse random nonce
+///
+/// The nonce is 128 bits from the OS random source (Web Crypto on
+/// `wasm32-unknown-unknown`, where the keys of the std hasher are fixed).
+/// If no random source is available, the std hasher and a process-wide
+/// counter at least keep nonces distinct.
+fn generate_nonce() -> String {
+    let mut bytes = [0u8; 16];
+    if getrandom::getrandom(&mut bytes).is_ok() {
+        return bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    }
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(NONCE_COUNTER.fetch_add(1, Ordering::Relaxed));
+    format!("{:016x}", hasher.finish())
+}
+
+/// Derive a stable id from the call contents (SHA-256)
+///
+/// The id only depends on the call, so hosts can cache results by id
+/// across parses and documents. Each field is length-prefixed before
+/// hashing, so distinct calls cannot share an id without a SHA-256
+/// collision.
+fn stable_id(kind: PluginKind, name: &str, args: &[String], body: Option<&str>) -> String {
+    let mut hasher = Sha256::new();
+    let mut feed = |bytes: &[u8]| {
+        hasher.update((bytes.len() as u64).to_le_bytes());
+        hasher.update(bytes);
+    };
+
+    feed(match kind {
+        PluginKind::Inline => b"&",
+        PluginKind::Block => b"@",
+    });
+    feed(name.as_bytes());
+    feed(&(args.len() as u64).to_le_bytes());
+    for arg in args {
+        feed(arg.as_bytes());
+    }
+    match body {
+        Some(body) => {
+            feed(b"body");
+            feed(body.as_bytes());
+        }
+        None => feed(b""),
+    }
+
+    let digest: String = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("{}-{}", name, digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defer_records_call() {
+        let mut deferred = DeferredPlugins::new();
+        let placeholder = deferred.defer(PluginKind::Block, "rss", "https://e.com/feed, 5", None);
+
+        assert!(placeholder.starts_with("<!--umd-plugin:"));
+        assert!(placeholder.contains(deferred.nonce()));
+        assert_eq!(deferred.pending().len(), 1);
+
+        let pending = &deferred.pending()[0];
+        assert_eq!(pending.name, "rss");
+        assert_eq!(pending.args, vec!["https://e.com/feed", "5"]);
+        assert!(placeholder.contains(&pending.id));
+    }
+
+    #[test]
+    fn test_nonce_is_random() {
+        let nonce = DeferredPlugins::new().nonce().to_string();
+        assert_eq!(nonce.len(), 32);
+        assert!(nonce.bytes().all(|b| b.is_ascii_hexdigit()));
+        assert_ne!(nonce, DeferredPlugins::new().nonce());
+    }
+
+    #[test]
+    fn test_ids_are_stable_and_shared() {
+        let mut first = DeferredPlugins::new();
+        let mut second = DeferredPlugins::new();
+        first.defer(PluginKind::Inline, "counter", "", None);
+        first.defer(PluginKind::Inline, "counter", "", None);
+        second.defer(PluginKind::Inline, "counter", "", None);
+
+        assert_eq!(first.pending().len(), 1);
+        assert_eq!(first.pending()[0].id, second.pending()[0].id);
+        assert_ne!(first.nonce(), second.nonce());
+    }
+
+    #[test]
+    fn test_ids_distinguish_field_boundaries() {
+        let id = |args: &[&str], body| {
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            stable_id(PluginKind::Inline, "x", &args, body)
+        };
+
+        assert_ne!(id(&["a\x1fb"], None), id(&["a", "b"], None));
+        assert_ne!(id(&["a"], Some("b")), id(&["a", "b"], None));
+        assert_ne!(id(&[], Some("")), id(&[], None));
+        assert_eq!(id(&[], None).len(), "x-".len() + 64);
+    }
+
+    #[test]
+    fn test_hydrate_requires_matching_nonce() {
+        let mut deferred = DeferredPlugins::new();
+        let placeholder = deferred.defer(PluginKind::Inline, "counter", "", None);
+        let id = deferred.pending()[0].id.clone();
+        let forged = format!("<!--umd-plugin:0000000000000000:{}-->", id);
+        let html = format!("{}{}", placeholder, forged);
+
+        let results = HashMap::from([(id, "42".to_string())]);
+        let output = hydrate_html(&html, deferred.nonce(), &results);
+        assert_eq!(output, format!("42{}", forged));
+    }
+
+    #[test]
+    fn test_hydrate_leaves_unresolved() {
+        let mut deferred = DeferredPlugins::new();
+        let placeholder = deferred.defer(PluginKind::Inline, "counter", "", None);
+        let output = hydrate_html(&placeholder, deferred.nonce(), &HashMap::new());
+        assert_eq!(output, placeholder);
+    }
+}
@@ -0,0 +1,501 @@
+//! Definition lists
+//!
+//! Consecutive `:term|definition` lines form a `<dl>`:
+//!
+//! ```text
+//! HORIZONTAL:
+//! :HTML|HyperText Markup Language
+//! :|The markup language of the web
+//! :CSS|Cascading Style Sheets
+//!
+//!   - Selectors
+//!   - Properties
+//! ```
+//!
+//! - `:|definition` adds another definition to the previous term, as does
+//!   repeating the term on the next line
+//! - Indented lines continue the last definition; after dedenting they are
+//!   Markdown blocks (paragraphs, lists, code), and blank lines between them
+//!   are kept as long as indented lines follow
+//! - Terms are inline Markdown and may use UMD syntax; a leading block
+//!   marker (`# `, `- `, `> `, `1. `, a fence) is kept as text
+//! - Code fences opened in a term or definition are closed at its end
+//! - Lines indented by four or more columns are indented code, not items,
+//!   unless they are inside a list
+//! - A `HORIZONTAL:` line (or `HORIZONTAL(4):` for the grid columns of the
+//!   terms) before the list lays it out with Bootstrap `row`/`col-sm-*`
+//!
+//! The terms and definitions stay in the document between tokens, so they
+//! are rendered like any other Markdown; the `<dl>` is assembled from the
+//! rendered parts in post-processing.
+
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+
+use super::markers;
+
+/// Layout line on its own: `HORIZONTAL:` or `HORIZONTAL(4):`
+static LAYOUT_LINE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^HORIZONTAL(?:\(([1-9]|1[01])\))?:$").unwrap());
+
+/// Rendered list: opening token, parts, closing token
+static RENDERED_LIST: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(&format!(
+        r"(?s)<p>{}</p>\s*(.*?)\s*<p>{}</p>",
+        markers::token_pattern(markers::DEFINITION_LIST),
+        markers::close(markers::DEFINITION_LIST)
+    ))
+    .unwrap()
+});
+
+/// Boundary before each rendered term or definition
+static RENDERED_PART: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(&format!(
+        r"\s*<p>{}</p>\s*",
+        markers::open(markers::DEFINITION)
+    ))
+    .unwrap()
+});
+
+/// Start of a term that Markdown would read as a block: heading, bullet,
+/// blockquote, thematic break or code fence
+static TERM_BLOCK_START: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?:#{1,6}(?:\s|$)|[-+*](?:\s|$)|>|(?:[-*_]\s*){3,}$|```|~~~)").unwrap()
+});
+
+/// Start of a term that Markdown would read as an ordered list item
+static TERM_ORDERED_START: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\d{1,9}[.)](?:\s|$)").unwrap());
+
+/// List item line
+static LIST_ITEM: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\s*(?:[-+*]|\d{1,9}[.)])(?:\s|$)").unwrap());
+
+/// Grid columns of the terms in the horizontal layout by default
+const DEFAULT_TERM_COLUMNS: usize = 3;
+
+/// A term and its definitions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefinitionItem {
+    /// Term source (inline Markdown)
+    pub term: String,
+    /// Definition sources (Markdown blocks)
+    pub definitions: Vec<String>,
+}
+
+/// A definition list taken out of the text during pre-processing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefinitionList {
+    /// Terms with their definitions, in source order
+    pub items: Vec<DefinitionItem>,
+    /// Grid columns of the terms when laid out horizontally
+    pub term_columns: Option<usize>,
+    /// The lines as written in the source
+    pub source: String,
+}
+
+impl DefinitionList {
+    /// Assemble the `<dl>` from the rendered terms and definitions
+    ///
+    /// # Arguments
+    ///
+    /// * `parts` - Rendered HTML of each term and definition, in order
+    ///
+    /// # Returns
+    ///
+    /// The `<dl>` element
+    pub fn apply<'a>(&self, parts: impl IntoIterator<Item = &'a str>) -> String {
+        let mut parts = parts.into_iter();
+        let (dl, dt, dd, next_dd) = match self.term_columns {
+            Some(columns) => (
+                r#"<dl class="row">"#.to_string(),
+                format!(r#"<dt class="col-sm-{}">"#, columns),
+                format!(r#"<dd class="col-sm-{}">"#, 12 - columns),
+                format!(
+                    r#"<dd class="col-sm-{} offset-sm-{}">"#,
+                    12 - columns,
+                    columns
+                ),
+            ),
+            None => (
+                "<dl>".to_string(),
+                "<dt>".to_string(),
+                "<dd>".to_string(),
+                "<dd>".to_string(),
+            ),
+        };
+
+        let mut html = dl;
+        for item in &self.items {
+            html.push_str(&dt);
+            html.push_str(unwrap_paragraph(parts.next().unwrap_or_default()));
+            html.push_str("</dt>");
+            for index in 0..item.definitions.len() {
+                html.push_str(if index == 0 { &dd } else { &next_dd });
+                html.push_str(unwrap_paragraph(parts.next().unwrap_or_default()));
+                html.push_str("</dd>");
+            }
+        }
+        html.push_str("</dl>");
+        html
+    }
+}
+
+/// Content of a part rendered as a single paragraph, or the part itself
+fn unwrap_paragraph(part: &str) -> &str {
+    let part = part.trim();
+    match part
+        .strip_prefix("<p>")
+        .and_then(|inner| inner.strip_suffix("</p>"))
+    {
+        Some(inner) if !inner.contains("<p>") => inner,
+        _ => part,
+    }
+}
+
+/// Replace definition lists with tokens
+///
+/// Each term and definition is kept in the text as its own block, after a
+/// boundary token; the list sits between an opening token (indexing
+/// `lists`) and a closing token. Lines inside fenced code blocks are left
+/// untouched.
+///
+/// # Arguments
+///
+/// * `input` - The wiki markup
+/// * `lists` - Side table receiving the structure of each list
+///
+/// # Returns
+///
+/// The markup with the lists replaced
+pub fn protect_definition_lists(input: &str, lists: &mut Vec<DefinitionList>) -> String {
+    let lines: Vec<&str> = input.split_inclusive('\n').collect();
+    let mut result = String::with_capacity(input.len());
+    let mut in_code_block = false;
+    let mut code_fence_marker = "";
+    let mut in_list = false;
+    let mut i = 0;
+
+    while i < lines.len() {
+        let trimmed = lines[i].trim();
+
+        // A list continues over blank and indented lines
+        if LIST_ITEM.is_match(lines[i]) {
+            in_list = true;
+        } else if !trimmed.is_empty() && indent_width(lines[i]) < 2 {
+            in_list = false;
+        }
+
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            if !in_code_block {
+                in_code_block = true;
+                code_fence_marker = if trimmed.starts_with("```") {
+                    "```"
+                } else {
+                    "~~~"
+                };
+            } else if trimmed.contains(code_fence_marker) {
+                in_code_block = false;
+            }
+        }
+
+        if !in_code_block && let Some((list, end)) = definition_list(&lines, i, in_list) {
+            result.push_str(&placeholder(lists.len(), &list));
+            lists.push(list);
+            i = end;
+            continue;
+        }
+
+        result.push_str(lines[i]);
+        i += 1;
+    }
+
+    result
+}
+
+/// Replace rendered definition lists with `<dl>` elements
+///
+/// # Arguments
+///
+/// * `html` - HTML rendered from the output of [`protect_definition_lists`]
+/// * `lists` - The lists taken out of the text
+///
+/// # Returns
+///
+/// The HTML with the lists restored
+pub fn restore_definition_lists(html: &str, lists: &[DefinitionList]) -> String {
+    RENDERED_LIST
+        .replace_all(html, |caps: &Captures| {
+            match caps[1].parse::<usize>().ok().and_then(|i| lists.get(i)) {
+                // The content starts with a boundary, so the first split is empty
+                Some(list) => list.apply(RENDERED_PART.split(&caps[2]).skip(1)),
+                None => caps[2].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// Whether `line` is a `:term|definition` item
+///
+/// Outside a list, a line indented by four or more columns is indented
+/// code.
+fn is_item(line: &str, in_list: bool) -> bool {
+    (in_list || indent_width(line) < 4) && line.trim_start().starts_with(':') && line.contains('|')
+}
+
+/// Indentation of `line` in columns, with tab stops every four columns
+fn indent_width(line: &str) -> usize {
+    let mut width = 0;
+    for c in line.chars() {
+        match c {
+            ' ' => width += 1,
+            '\t' => width += 4 - width % 4,
+            _ => break,
+        }
+    }
+    width
+}
+
+/// Whether `line` continues the definition above it
+fn is_continuation(line: &str) -> bool {
+    !line.trim().is_empty() && (line.starts_with("  ") || line.starts_with('\t'))
+}
+
+/// Match a definition list, with an optional layout line, starting at
+/// `start`
+///
+/// Returns the list and the index of the line after it.
+fn definition_list(lines: &[&str], start: usize, in_list: bool) -> Option<(DefinitionList, usize)> {
+    let mut first = start;
+    let layout = LAYOUT_LINE.captures(lines[first].trim()).map(|caps| {
+        caps.get(1)
+            .and_then(|columns| columns.as_str().parse().ok())
+            .unwrap_or(DEFAULT_TERM_COLUMNS)
+    });
+    if layout.is_some() {
+        first += 1;
+    }
+    if !lines.get(first).is_some_and(|line| is_item(line, in_list)) {
+        return None;
+    }
+
+    let mut items: Vec<DefinitionItem> = Vec::new();
+    let mut end = first;
+    while end < lines.len() {
+        let line = lines[end];
+        if is_item(line, in_list) && (end == first || !is_continuation(line)) {
+            let stripped = line.trim().trim_start_matches(':');
+            let (term, definition) = stripped.split_once('|').unwrap_or((stripped, ""));
+            let (term, definition) = (term.trim(), definition.trim().to_string());
+            match items.last_mut() {
+                Some(item) if term.is_empty() || item.term == term => {
+                    item.definitions.push(definition)
+                }
+                _ => items.push(DefinitionItem {
+                    term: term.to_string(),
+                    definitions: vec![definition],
+                }),
+            }
+            end += 1;
+            continue;
+        }
+
+        // Indented lines, and blank lines followed by indented lines,
+        // continue the last definition
+        let block_end = (end..lines.len())
+            .take_while(|&j| lines[j].trim().is_empty() || is_continuation(lines[j]))
+            .filter(|&j| is_continuation(lines[j]))
+            .last()
+            .map_or(end, |j| j + 1);
+        if block_end == end {
+            break;
+        }
+        if let Some(definition) = items
+            .last_mut()
+            .and_then(|item| item.definitions.last_mut())
+        {
+            let block = dedent(&lines[end..block_end]);
+            if definition.is_empty() {
+                *definition = block;
+            } else {
+                definition.push('\n');
+                definition.push_str(&block);
+            }
+        }
+        end = block_end;
+    }
+
+    for item in &mut items {
+        item.term = close_fences(&inline_term(&item.term));
+        for definition in &mut item.definitions {
+            *definition = close_fences(definition);
+        }
+    }
+
+    let list = DefinitionList {
+        items,
+        term_columns: layout,
+        source: lines[start..end].concat().trim_end().to_string(),
+    };
+    Some((list, end))
+}
+
+/// Remove the common indentation of continuation lines
+fn dedent(lines: &[&str]) -> String {
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|line| line.get(indent..).unwrap_or("").trim_end())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Escape a leading block marker so the term renders inline
+fn inline_term(term: &str) -> String {
+    if TERM_BLOCK_START.is_match(term) {
+        format!("\\{}", term)
+    } else if TERM_ORDERED_START.is_match(term) {
+        let digits = term.len() - term.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        format!("{}\\{}", &term[..digits], &term[digits..])
+    } else {
+        term.to_string()
+    }
+}
+
+/// Close a code fence left open in a term or definition, so it cannot
+/// swallow the rest of the list
+fn close_fences(part: &str) -> String {
+    let fences: Vec<&str> = part
+        .lines()
+        .map(str::trim_start)
+        .filter(|line| line.starts_with("```") || line.starts_with("~~~"))
+        .collect();
+    match fences.first() {
+        Some(fence) if fences.len() % 2 == 1 => format!("{}\n{}", part, &fence[..3]),
+        _ => part.to_string(),
+    }
+}
+
+/// Text standing in for a list: each term and definition as its own block
+/// after a boundary token, between the list tokens
+fn placeholder(index: usize, list: &DefinitionList) -> String {
+    let boundary = markers::open(markers::DEFINITION);
+    // Blank lines keep every part a block of its own
+    let mut source = format!("\n{}\n\n", markers::token(markers::DEFINITION_LIST, index));
+    for item in &list.items {
+        source.push_str(&format!("{}\n\n{}\n\n", boundary, item.term));
+        for definition in &item.definitions {
+            source.push_str(&format!("{}\n\n{}\n\n", boundary, definition));
+        }
+    }
+    source.push_str(&markers::close(markers::DEFINITION_LIST));
+    source.push_str("\n\n");
+    source
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(term: &str, definitions: &[&str]) -> DefinitionItem {
+        DefinitionItem {
+            term: term.to_string(),
+            definitions: definitions.iter().map(|d| d.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_definition_list() {
+        let mut lists = Vec::new();
+        let input = ":term1|definition1\n:term2|definition2\nregular text";
+        let output = protect_definition_lists(input, &mut lists);
+        assert!(output.contains(&markers::token(markers::DEFINITION_LIST, 0)));
+        assert!(output.ends_with("regular text"));
+        assert_eq!(
+            lists[0].items,
+            vec![
+                item("term1", &["definition1"]),
+                item("term2", &["definition2"])
+            ]
+        );
+        assert_eq!(lists[0].source, ":term1|definition1\n:term2|definition2");
+    }
+
+    #[test]
+    fn test_multiple_definitions_and_continuation() {
+        let mut lists = Vec::new();
+        let input = ":A|one\n:|two\n:A|three\n  more\n\n  - x\n  - y\n:B|\n    code\n\nafter";
+        let output = protect_definition_lists(input, &mut lists);
+        assert_eq!(
+            lists[0].items,
+            vec![
+                item("A", &["one", "two", "three\nmore\n\n- x\n- y"]),
+                item("B", &["code"])
+            ]
+        );
+        assert!(output.ends_with("\nafter"));
+    }
+
+    #[test]
+    fn test_layout_line_and_code_blocks() {
+        let mut lists = Vec::new();
+        let input = "HORIZONTAL(4):\n:A|a\n\n```\n:B|b\n```\n\n:C|\n  ```\n  x";
+        let output = protect_definition_lists(input, &mut lists);
+        assert_eq!(lists.len(), 2);
+        assert_eq!(lists[0].term_columns, Some(4));
+        assert!(output.contains("```\n:B|b\n```"));
+        assert_eq!(lists[1].items[0].definitions[0], "```\nx\n```");
+        assert!(!output.contains("HORIZONTAL"));
+    }
+
+    #[test]
+    fn test_parts_stay_in_the_list() {
+        let output = crate::parse(":a|```\ncode\n```\nafter");
+        assert!(
+            output.starts_with("<dl><dt>a</dt><dd><pre><code></code></pre></dd></dl>"),
+            "{}",
+            output
+        );
+
+        let output = crate::parse(":# H|def\n:- x|y\n:1. z|w\n:```|v");
+        assert!(output.contains("<dt># H</dt>"), "{}", output);
+        assert!(output.contains("<dt>- x</dt>"), "{}", output);
+        assert!(output.contains("<dt>1. z</dt>"), "{}", output);
+        assert!(output.contains("<dt>```</dt>"), "{}", output);
+    }
+
+    #[test]
+    fn test_indented_code_is_not_a_list() {
+        let output = crate::parse("text\n\n    :a|b");
+        assert!(
+            output.contains("<pre><code>:a|b\n</code></pre>"),
+            "{}",
+            output
+        );
+        assert!(!output.contains("<dl>"));
+
+        // Inside a list, indentation belongs to the list item
+        let mut lists = Vec::new();
+        protect_definition_lists("- item\n\n    :a|b", &mut lists);
+        assert_eq!(lists.len(), 1);
+    }
+
+    #[test]
+    fn test_apply_horizontal_layout() {
+        let list = DefinitionList {
+            items: vec![item("T", &["a", "b"])],
+            term_columns: Some(3),
+            source: String::new(),
+        };
+        assert_eq!(
+            list.apply(["<p>T</p>", "<p>a</p>", "<ul>\n<li>b</li>\n</ul>"]),
+            r#"<dl class="row"><dt class="col-sm-3">T</dt><dd class="col-sm-9">a</dd><dd class="col-sm-9 offset-sm-3"><ul>
+<li>b</li>
+</ul></dd></dl>"#
+        );
+    }
+}
@@ -4,15 +4,24 @@
 //! - ''text'' → <b>text</b> (visual bold)
 //! - '''text''' → <i>text</i> (visual italic)
 //! - __text__ → <u>text</u> (underline, Discord-style - handled in preprocessor)
+//!
+//! In CJK mode ''text'' also accepts a single wide character (''強'').
 
 use once_cell::sync::Lazy;
 use regex::Regex;
 
+use super::cjk::is_wide;
+
 static UMD_BOLD: Lazy<Regex> = Lazy::new(|| {
     // Match ''text'' but not '''text''' (at least 2 non-quote chars)
     Regex::new(r"''([^']{2,})''").unwrap()
 });
 
+static UMD_BOLD_CJK: Lazy<Regex> = Lazy::new(|| {
+    // Match ''text'' with any length; single characters are checked by the caller
+    Regex::new(r"''([^']+)''").unwrap()
+});
+
 static UMD_ITALIC: Lazy<Regex> = Lazy::new(|| {
     // Match '''text''' with at least one non-quote char
     Regex::new(r"'''([^']+)'''").unwrap()
@@ -42,10 +51,44 @@ static UMD_ITALIC: Lazy<Regex> = Lazy::new(|| {
 /// assert!(output.contains("<i>italic</i>"));
 /// ```
 pub fn apply_umd_emphasis(html: &str) -> String {
+    apply_umd_emphasis_with_cjk(html, false)
+}
+
+/// Apply UMD emphasis syntax to HTML, optionally in CJK mode
+///
+/// # Arguments
+///
+/// * `html` - The HTML content to process
+/// * `cjk` - Also accept a single wide character in ''text''
+///
+/// # Returns
+///
+/// HTML with UMD emphasis applied
+///
+/// # Examples
+///
+/// ```
+/// use universal_markdown::extensions::emphasis::apply_umd_emphasis_with_cjk;
+///
+/// assert_eq!(apply_umd_emphasis_with_cjk("''強''調", true), "<b>強</b>調");
+/// assert_eq!(apply_umd_emphasis_with_cjk("''a''", true), "''a''");
+/// ```
+pub fn apply_umd_emphasis_with_cjk(html: &str, cjk: bool) -> String {
     // Process '''text''' (italic) first to avoid conflicts with ''text''
     let result = UMD_ITALIC.replace_all(html, "<i>$1</i>");
 
     // Then process ''text'' (bold)
+    if cjk {
+        return UMD_BOLD_CJK
+            .replace_all(&result, |caps: &regex::Captures| {
+                let mut chars = caps[1].chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if !is_wide(c) => caps[0].to_string(),
+                    _ => format!("<b>{}</b>", &caps[1]),
+                }
+            })
+            .to_string();
+    }
     let result = UMD_BOLD.replace_all(&result, "<b>$1</b>");
 
     result.to_string()
@@ -81,9 +124,11 @@ mod tests {
     fn test_discord_underline() {
         // Note: __text__ is handled in preprocessor, not here
         // This test verifies that apply_umd_emphasis doesn't break placeholder markers
-        let input = "This is {{UNDERLINE:underlined:UNDERLINE}} text.";
-        let output = apply_umd_emphasis(input);
-        assert!(output.contains("{{UNDERLINE:underlined:UNDERLINE}}"));
+        let input = crate::extensions::preprocessor::preprocess_discord_underline(
+            "This is __underlined__ text.",
+        );
+        let output = apply_umd_emphasis(&input);
+        assert_eq!(output, input);
     }
 
     #[test]
@@ -102,6 +147,16 @@ mod tests {
         assert!(output.contains("<i>italic</i>"));
     }
 
+    #[test]
+    fn test_cjk_single_character_bold() {
+        let input = "''強''調と''ab''と'''斜'''";
+        assert_eq!(
+            apply_umd_emphasis_with_cjk(input, true),
+            "<b>強</b>調と<b>ab</b>と<i>斜</i>"
+        );
+        assert_eq!(apply_umd_emphasis("''強''"), "''強''");
+    }
+
     #[test]
     fn test_no_false_matches() {
         let input = "Don't match this: 'single' or four";
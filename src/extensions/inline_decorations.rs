@@ -20,6 +20,8 @@
 use once_cell::sync::Lazy;
 use regex::Regex;
 
+use crate::extensions::styles::Styles;
+
 // Badge pattern with optional link support
 static INLINE_BADGE: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"&badge\(([^)]+?)\)\{([^}]+?)\};").unwrap());
@@ -170,6 +172,20 @@ fn map_badge_type(badge_type: &str) -> String {
 ///
 /// HTML with inline decorations applied
 pub fn apply_inline_decorations(html: &str) -> String {
+    apply_inline_decorations_with_styles(html, &mut Styles::default())
+}
+
+/// Apply inline decoration functions, validating custom style values
+///
+/// # Arguments
+///
+/// * `html` - The HTML content to process
+/// * `styles` - Validator for custom `&color()`/`&size()` values
+///
+/// # Returns
+///
+/// HTML with inline decorations applied
+pub fn apply_inline_decorations_with_styles(html: &str, styles: &mut Styles) -> String {
     let mut result = html.to_string();
 
     // Apply %%text%% → <s>text</s> (LukiWiki strikethrough)
@@ -219,14 +235,14 @@ pub fn apply_inline_decorations(html: &str) -> String {
             let text = caps.get(3).map_or("", |m| m.as_str());
 
             let mut classes = Vec::new();
-            let mut styles = Vec::new();
+            let mut declarations = Vec::new();
 
             if !fg.is_empty() && fg != "inherit" {
                 let (is_class, value) = map_color(fg, false);
                 if is_class {
                     classes.push(value);
                 } else {
-                    styles.push(format!("color: {}", value));
+                    styles.apply("color", &value, &mut classes, &mut declarations);
                 }
             }
 
@@ -235,19 +251,19 @@ pub fn apply_inline_decorations(html: &str) -> String {
                 if is_class {
                     classes.push(value);
                 } else {
-                    styles.push(format!("background-color: {}", value));
+                    styles.apply("background-color", &value, &mut classes, &mut declarations);
                 }
             }
 
-            if classes.is_empty() && styles.is_empty() {
+            if classes.is_empty() && declarations.is_empty() {
                 text.to_string()
             } else {
                 let mut attrs = Vec::new();
                 if !classes.is_empty() {
                     attrs.push(format!("class=\"{}\"", classes.join(" ")));
                 }
-                if !styles.is_empty() {
-                    attrs.push(format!("style=\"{}\"", styles.join("; ")));
+                if !declarations.is_empty() {
+                    attrs.push(format!("style=\"{}\"", declarations.join("; ")));
                 }
                 format!("<span {}>{}</span>", attrs.join(" "), text)
             }
@@ -260,11 +276,25 @@ pub fn apply_inline_decorations(html: &str) -> String {
             let size = caps.get(1).map_or("", |m| m.as_str());
             let text = caps.get(2).map_or("", |m| m.as_str());
 
+            let mut classes = Vec::new();
+            let mut declarations = Vec::new();
             let (is_class, value) = map_font_size(size);
             if is_class {
-                format!("<span class=\"{}\">{}</span>", value, text)
+                classes.push(value);
             } else {
-                format!("<span style=\"font-size: {}\">{}</span>", value, text)
+                styles.apply("font-size", &value, &mut classes, &mut declarations);
+            }
+
+            if !classes.is_empty() {
+                format!("<span class=\"{}\">{}</span>", classes.join(" "), text)
+            } else if !declarations.is_empty() {
+                format!(
+                    "<span style=\"{}\">{}</span>",
+                    declarations.join("; "),
+                    text
+                )
+            } else {
+                text.to_string()
             }
         })
         .to_string();
@@ -291,7 +321,9 @@ pub fn apply_inline_decorations(html: &str) -> String {
 
     // Apply &ruby(reading){text};
     result = INLINE_RUBY
-        .replace_all(&result, "<ruby>$2<rp>(</rp><rt>$1</rt><rp>)</rp></ruby>;")
+        .replace_all(&result, |caps: &regex::Captures| {
+            format!("{};", super::ruby::render_ruby(&caps[1], &caps[2]))
+        })
         .to_string();
 
     // Semantic HTML elements - simple wrappers
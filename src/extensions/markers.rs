@@ -0,0 +1,184 @@
+//! Placeholder tokens that user input cannot forge
+//!
+//! Pre-processing replaces UMD syntax with tokens that survive sanitizing
+//! and Markdown parsing unchanged, and post-processing turns them back into
+//! HTML. A token is a kind letter and an optional index wrapped in the
+//! Unicode noncharacters U+FDD0 and U+FDD1:
+//!
+//! - `U+FDD0 P3 U+FDD1` - payload token, index into a side table
+//! - `U+FDD0 Q U+FDD1` … `U+FDD0 /Q U+FDD1` - open/close pair around content
+//!
+//! Noncharacters are reserved by Unicode for process-internal use and never
+//! appear in interchanged text, so [`scrub`] removes them from the input
+//! before any tokens are created. Payloads live in side tables instead of the
+//! token itself, so nothing carried by a token is ever reparsed as text.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::borrow::Cow;
+
+/// Opening sentinel of a token
+pub const TOKEN_START: char = '\u{FDD0}';
+/// Closing sentinel of a token
+pub const TOKEN_END: char = '\u{FDD1}';
+
+/// Any leftover token: kind letter, optional close slash and optional index
+static ANY_TOKEN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\x{FDD0}(/?)([A-Z])(\d*)\x{FDD1}").unwrap());
+
+/// Token kind: inline or block plugin call (payload)
+pub const PLUGIN: char = 'P';
+/// Token kind: definition list, opened by a payload token and closed by a
+/// closing token
+pub const DEFINITION_LIST: char = 'L';
+/// Token kind: boundary before each term and definition of a definition
+/// list (opening token only)
+pub const DEFINITION: char = 'I';
+/// Token kind: UMD table (payload)
+pub const TABLE: char = 'T';
+/// Token kind: rendered content of a UMD table cell (pair)
+pub const TABLE_CELL: char = 'E';
+/// Token kind: `@table(...)` wrapper, opened by a payload token and closed
+/// by a closing token
+pub const TABLE_PLUGIN: char = 'W';
+/// Token kind: protected code section (payload)
+pub const CODE: char = 'C';
+/// Token kind: UMD blockquote `> … <` (pair)
+pub const BLOCKQUOTE: char = 'Q';
+/// Token kind: block decoration line such as `COLOR(red): …` (pair)
+pub const BLOCK_DECORATION: char = 'D';
+/// Token kind: Discord-style underline `__…__` (pair)
+pub const UNDERLINE: char = 'U';
+/// Token kind: table recorded for export (payload, see
+/// [`crate::extensions::table::export::TableRecorder`])
+pub const EXPORTED_TABLE: char = 'X';
+/// Token kind: start of the content of a recorded table cell (payload)
+pub const EXPORTED_CELL: char = 'Y';
+/// Token kind: end of the content of a recorded table cell (payload)
+pub const EXPORTED_CELL_END: char = 'Z';
+
+/// Remove token sentinels (the U+FDD0..=U+FDEF noncharacters) from input
+///
+/// # Arguments
+///
+/// * `input` - Raw user input
+///
+/// # Returns
+///
+/// The input without any noncharacters that could form a token
+pub fn scrub(input: &str) -> Cow<'_, str> {
+    if input.contains(is_sentinel) {
+        Cow::Owned(input.chars().filter(|&c| !is_sentinel(c)).collect())
+    } else {
+        Cow::Borrowed(input)
+    }
+}
+
+fn is_sentinel(c: char) -> bool {
+    ('\u{FDD0}'..='\u{FDEF}').contains(&c)
+}
+
+/// Payload token pointing at `index` in the side table for `kind`
+pub fn token(kind: char, index: usize) -> String {
+    format!("{}{}{}{}", TOKEN_START, kind, index, TOKEN_END)
+}
+
+/// Opening token of a pair
+pub fn open(kind: char) -> String {
+    format!("{}{}{}", TOKEN_START, kind, TOKEN_END)
+}
+
+/// Closing token of a pair
+pub fn close(kind: char) -> String {
+    format!("{}/{}{}", TOKEN_START, kind, TOKEN_END)
+}
+
+/// Regex source matching payload tokens of `kind`, capturing the index
+pub fn token_pattern(kind: char) -> String {
+    format!(r"\x{{FDD0}}{}(\d+)\x{{FDD1}}", kind)
+}
+
+/// Regex source matching a pair of `kind`, capturing the enclosed content
+pub fn pair_pattern(kind: char) -> String {
+    format!(
+        r"\x{{FDD0}}{0}\x{{FDD1}}(.+?)\x{{FDD0}}/{0}\x{{FDD1}}",
+        kind
+    )
+}
+
+/// Replace tokens that survived post-processing
+///
+/// Tokens can be left over where a transformation did not apply, e.g. inside
+/// code spans. Payload tokens are replaced through `restore`, which returns
+/// the HTML to emit for a kind and index; pairs fall back to the syntax they
+/// replaced, and anything unknown is dropped.
+///
+/// # Arguments
+///
+/// * `html` - Post-processed HTML
+/// * `restore` - Callback producing HTML for a leftover payload token
+///
+/// # Returns
+///
+/// HTML without any token sentinels
+pub fn restore_leftovers<F>(html: &str, mut restore: F) -> String
+where
+    F: FnMut(char, usize) -> Option<String>,
+{
+    let result = ANY_TOKEN.replace_all(html, |caps: &regex::Captures| {
+        let closing = !caps[1].is_empty();
+        let kind = caps[2].chars().next().unwrap_or_default();
+
+        if let Ok(index) = caps[3].parse::<usize>() {
+            return restore(kind, index).unwrap_or_default();
+        }
+
+        match (kind, closing) {
+            (UNDERLINE, _) => "__".to_string(),
+            (BLOCKQUOTE, false) => "&gt; ".to_string(),
+            (BLOCKQUOTE, true) => " &lt;".to_string(),
+            _ => String::new(),
+        }
+    });
+
+    scrub(&result).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scrub_removes_sentinels() {
+        let forged = format!("a {} b", token(PLUGIN, 0));
+        assert_eq!(scrub(&forged), "a P0 b");
+        assert!(matches!(scrub("plain text"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_patterns_match_tokens() {
+        let payload = Regex::new(&token_pattern(TABLE)).unwrap();
+        let text = token(TABLE, 12);
+        let caps = payload.captures(&text).unwrap();
+        assert_eq!(&caps[1], "12");
+
+        let pair = Regex::new(&pair_pattern(BLOCKQUOTE)).unwrap();
+        let text = format!("{}quote{}", open(BLOCKQUOTE), close(BLOCKQUOTE));
+        assert_eq!(&pair.captures(&text).unwrap()[1], "quote");
+    }
+
+    #[test]
+    fn test_restore_leftovers() {
+        let html = format!(
+            "<code>{}x{} {}</code>{}",
+            open(UNDERLINE),
+            close(UNDERLINE),
+            token(PLUGIN, 0),
+            TOKEN_START
+        );
+        let output = restore_leftovers(&html, |kind, index| {
+            (kind == PLUGIN && index == 0).then(|| "&amp;f;".to_string())
+        });
+        assert_eq!(output, "<code>__x__ &amp;f;</code>");
+    }
+}
@@ -4,12 +4,19 @@
 //! semantic HTML elements, definition lists, and LukiWiki legacy compatibility.
 
 pub mod block_decorations;
+pub mod cjk;
 pub mod conflict_resolver;
+pub mod deferred;
+pub mod definition_list;
 pub mod emphasis;
 pub mod inline_decorations;
+pub mod markers;
 pub mod plugin_markers;
+pub mod plugin_syntax;
 pub mod plugins;
 pub mod preprocessor;
+pub mod ruby;
+pub mod styles;
 pub mod table;
 
 /// Apply extended syntax transformations to HTML output
@@ -42,6 +49,41 @@ pub fn apply_extensions(html: &str) -> String {
 pub fn apply_extensions_with_headers(
     html: &str,
     header_map: &conflict_resolver::HeaderIdMap,
+) -> String {
+    apply_extensions_deferred(
+        html,
+        header_map,
+        None,
+        &mut styles::Styles::default(),
+        None,
+        false,
+    )
+}
+
+/// Apply extended syntax transformations, optionally deferring plugin calls
+///
+/// # Arguments
+///
+/// * `html` - The HTML output from the Markdown parser
+/// * `header_map` - Map of custom header IDs
+/// * `deferred` - Collector for deferred plugin calls; plugins are rendered
+///   as `<template>` elements when `None`
+/// * `styles` - Validator for custom decoration style values
+/// * `recorder` - Recorder of the rendered tables, if they are exported;
+///   it takes the post-processed cell content before leftover tokens are
+///   removed
+/// * `cjk` - Apply CJK mode (see [`cjk`])
+///
+/// # Returns
+///
+/// Transformed HTML with extended syntax and custom header IDs applied
+pub fn apply_extensions_deferred(
+    html: &str,
+    header_map: &conflict_resolver::HeaderIdMap,
+    deferred: Option<&mut deferred::DeferredPlugins>,
+    styles: &mut styles::Styles,
+    mut recorder: Option<&mut table::export::TableRecorder>,
+    cjk: bool,
 ) -> String {
     let mut result = html.to_string();
 
@@ -51,19 +93,35 @@ pub fn apply_extensions_with_headers(
 
     // Apply transformations in order
     // Note: Plugins are handled in conflict_resolver::postprocess_conflicts
-    result = conflict_resolver::postprocess_conflicts(&result, header_map);
-    result = emphasis::apply_umd_emphasis(&result);
+    result = conflict_resolver::postprocess_conflicts_deferred(
+        &result,
+        header_map,
+        deferred,
+        styles,
+        recorder.as_deref_mut(),
+    );
+    result = emphasis::apply_umd_emphasis_with_cjk(&result, cjk);
     result = block_decorations::apply_block_placement(&result); // Apply block placement first
-    result = block_decorations::apply_block_decorations(&result);
-    result = inline_decorations::apply_inline_decorations(&result);
+    result = block_decorations::apply_block_decorations_with_styles(&result, styles);
+    result = inline_decorations::apply_inline_decorations_with_styles(&result, styles);
+    if cjk {
+        result = cjk::remove_soft_breaks(&result);
+    }
 
     // Restore protected code sections
-    restore_code_sections(&result, &placeholders)
+    result = restore_code_sections(&result, &placeholders);
+
+    if let Some(recorder) = recorder {
+        recorder.finish(&result);
+    }
+
+    // Turn tokens that were not transformed (e.g. inside code) back into text
+    conflict_resolver::restore_leftover_tokens(&result, header_map)
 }
 
 /// Protect code blocks and inline code from transformation
 ///
-/// Returns the HTML with code sections replaced by placeholders,
+/// Returns the HTML with code sections replaced by tokens,
 /// and a vector of the original code sections.
 fn protect_code_sections(html: &str) -> (String, Vec<String>) {
     use regex::Regex;
@@ -77,7 +135,7 @@ fn protect_code_sections(html: &str) -> (String, Vec<String>) {
         .replace_all(&result, |caps: &regex::Captures| {
             let index = placeholders.len();
             placeholders.push(caps[0].to_string());
-            format!("<!--CODE_BLOCK_{}-->", index)
+            markers::token(markers::CODE, index)
         })
         .to_string();
 
@@ -87,7 +145,7 @@ fn protect_code_sections(html: &str) -> (String, Vec<String>) {
         .replace_all(&result, |caps: &regex::Captures| {
             let index = placeholders.len();
             placeholders.push(caps[0].to_string());
-            format!("<!--INLINE_CODE_{}-->", index)
+            markers::token(markers::CODE, index)
         })
         .to_string();
 
@@ -101,10 +159,10 @@ fn restore_code_sections(html: &str, placeholders: &[String]) -> String {
     let mut result = html.to_string();
 
     // Restore code blocks
-    let placeholder_re = Regex::new(r"<!--(CODE_BLOCK|INLINE_CODE)_(\d+)-->").unwrap();
+    let placeholder_re = Regex::new(&markers::token_pattern(markers::CODE)).unwrap();
     result = placeholder_re
         .replace_all(&result, |caps: &regex::Captures| {
-            let index: usize = caps[2].parse().unwrap();
+            let index: usize = caps[1].parse().unwrap();
             placeholders.get(index).map(|s| s.as_str()).unwrap_or("")
         })
         .to_string();
@@ -1,215 +1,239 @@
 //! Plugin syntax marker processing
 //!
-//! This module handles the conversion of plugin syntax into safe markers
-//! that won't be affected by Markdown parsing.
-
-use base64::{Engine as _, engine::general_purpose};
-use regex::Regex;
-use std::collections::HashSet;
-
-/// HTML entities that should NOT be treated as plugins
-fn html_entities() -> HashSet<&'static str> {
-    [
-        "lt", "gt", "amp", "nbsp", "quot", "apos", "ndash", "mdash", "hellip", "copy", "reg",
-        "trade", "times", "divide", "plusmn", "le", "ge", "ne", "asymp", "equiv", "forall",
-        "exist", "empty", "nabla", "isin", "notin", "ni", "prod", "sum", "minus", "lowast",
-        "radic", "prop", "infin", "ang", "and", "or", "cap", "cup", "int", "there4", "sim", "cong",
-        "sub", "sup", "nsub", "sube", "supe", "oplus", "otimes", "perp", "sdot", "lceil", "rceil",
-        "lfloor", "rfloor", "lang", "rang", "loz", "spades", "clubs", "hearts", "diams", "alpha",
-        "beta", "gamma", "delta", "epsilon", "zeta", "eta", "theta", "iota", "kappa", "lambda",
-        "mu", "nu", "xi", "omicron", "pi", "rho", "sigma", "tau", "upsilon", "phi", "chi", "psi",
-        "omega", "Iuml", "iuml", "Uuml", "uuml", "Auml", "auml", "Ouml", "ouml", "Euml", "euml",
-        "Aring", "aring", "AElig", "aelig", "Ccedil", "ccedil", "Eth", "eth", "Ntilde", "ntilde",
-        "Oslash", "oslash", "Thorn", "thorn", "szlig", "yuml", "Agrave", "agrave", "Aacute",
-        "aacute", "Acirc", "acirc", "Atilde", "atilde", "Egrave", "egrave", "Eacute", "eacute",
-        "Ecirc", "ecirc", "Igrave", "igrave", "Iacute", "iacute", "Icirc", "icirc", "Ograve",
-        "ograve", "Oacute", "oacute", "Ocirc", "ocirc", "Otilde", "otilde", "Ugrave", "ugrave",
-        "Uacute", "uacute", "Ucirc", "ucirc", "Yacute", "yacute", "cent", "pound", "curren", "yen",
-        "brvbar", "sect", "uml", "ordf", "laquo", "not", "shy", "macr", "deg", "sup2", "sup3",
-        "acute", "micro", "para", "middot", "cedil", "sup1", "ordm", "raquo", "frac14", "frac12",
-        "frac34", "iquest", "ensp", "emsp", "thinsp", "zwnj", "zwj", "lrm", "rlm",
-    ]
-    .iter()
-    .copied()
-    .collect()
+//! This module replaces plugin syntax with placeholder tokens (see
+//! [`super::markers`]) that won't be affected by Markdown parsing. The calls
+//! themselves are kept in a side table and restored in post-processing.
+
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+
+use super::markers;
+use super::plugin_syntax::{
+    BLOCK_PLUGIN_ARGSONLY, BLOCK_PLUGIN_MULTILINE, BLOCK_PLUGIN_SINGLELINE, INLINE_PLUGIN,
+    INLINE_PLUGIN_ARGSONLY, INLINE_PLUGIN_CONTENT_ONLY, INLINE_PLUGIN_NOARGS, PluginKind,
+    is_html_entity_name,
+};
+
+static PLUGIN_TOKEN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(&markers::token_pattern(markers::PLUGIN)).unwrap());
+
+/// A plugin call taken out of the text during pre-processing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtectedPlugin {
+    /// Inline (`&`) or block (`@`) plugin
+    pub kind: PluginKind,
+    /// Plugin function name
+    pub function: String,
+    /// Raw argument string; `None` for `&function;` and `&function{content};`
+    pub args: Option<String>,
+    /// Raw body text, if the call has one
+    pub content: Option<String>,
+    /// The call as written in the source
+    pub source: String,
 }
 
-/// Protect inline plugin syntax by converting to markers
+/// Protect inline plugin syntax by converting to tokens
 ///
-/// Converts various inline plugin patterns into safe markers:
-/// - `&function{content};` → marker with content
-/// - `&function(args){content};` → marker with args and content
-/// - `&function(args);` → marker with args
-/// - `&function;` → marker (excluding HTML entities)
-pub fn protect_inline_plugins(input: &str) -> String {
+/// Converts various inline plugin patterns into tokens and records the calls
+/// in `plugins`:
+/// - `&function{content};` → call with content
+/// - `&function(args){content};` → call with args and content
+/// - `&function(args);` → call with args
+/// - `&function;` → call without args (excluding HTML entities)
+pub fn protect_inline_plugins(input: &str, plugins: &mut Vec<ProtectedPlugin>) -> String {
     let mut result = input.to_string();
 
     // Protect inline plugins with content but no args: &function{content};
-    let inline_plugin_noargs_content = Regex::new(r"&(\w+)\{((?:[^{}]|\{[^}]*\})*)\};").unwrap();
-    result = inline_plugin_noargs_content
-        .replace_all(&result, |caps: &regex::Captures| {
-            let function = &caps[1];
-            let content = &caps[2];
-            let encoded_content = general_purpose::STANDARD.encode(content.as_bytes());
-            format!(
-                "{{{{INLINE_PLUGIN:{}::{}:INLINE_PLUGIN}}}}",
-                function, encoded_content
-            )
+    result = INLINE_PLUGIN_CONTENT_ONLY
+        .replace_all(&result, |caps: &Captures| {
+            protect(plugins, PluginKind::Inline, caps, None, Some(&caps[2]))
         })
         .to_string();
 
     // Protect inline plugins: &function(args){content};
-    let inline_plugin = Regex::new(r"&(\w+)\(([^)]*)\)\{((?:[^{}]|\{[^}]*\})*)\};").unwrap();
-    result = inline_plugin
-        .replace_all(&result, |caps: &regex::Captures| {
-            let function = &caps[1];
-            let args = &caps[2];
-            let content = &caps[3];
-            let encoded_content = general_purpose::STANDARD.encode(content.as_bytes());
-            format!(
-                "{{{{INLINE_PLUGIN:{}:{}:{}:INLINE_PLUGIN}}}}",
-                function, args, encoded_content
+    result = INLINE_PLUGIN
+        .replace_all(&result, |caps: &Captures| {
+            protect(
+                plugins,
+                PluginKind::Inline,
+                caps,
+                Some(&caps[2]),
+                Some(&caps[3]),
             )
         })
         .to_string();
 
     // Protect inline plugins (args only): &function(args);
-    let inline_plugin_argsonly = Regex::new(r"&(\w+)\(([^)]*)\);").unwrap();
-    result = inline_plugin_argsonly
-        .replace_all(&result, |caps: &regex::Captures| {
-            let function = &caps[1];
-            let args = &caps[2];
-            format!(
-                "{{{{INLINE_PLUGIN_ARGSONLY:{}:{}:INLINE_PLUGIN_ARGSONLY}}}}",
-                function, args
-            )
+    result = INLINE_PLUGIN_ARGSONLY
+        .replace_all(&result, |caps: &Captures| {
+            protect(plugins, PluginKind::Inline, caps, Some(&caps[2]), None)
         })
         .to_string();
 
     // Protect inline plugins (no args): &function;
     // Function name must start with a letter to avoid conflicts with HTML entities
-    let inline_plugin_noargs = Regex::new(r"&([a-zA-Z]\w*);").unwrap();
-    let entities = html_entities();
-
-    result = inline_plugin_noargs
-        .replace_all(&result, |caps: &regex::Captures| {
-            let function = &caps[1];
-
+    result = INLINE_PLUGIN_NOARGS
+        .replace_all(&result, |caps: &Captures| {
             // Skip HTML entities
-            if entities.contains(function) {
+            if is_html_entity_name(&caps[1]) {
                 return caps[0].to_string();
             }
 
-            format!(
-                "{{{{INLINE_PLUGIN_NOARGS:{}:INLINE_PLUGIN_NOARGS}}}}",
-                function
-            )
+            protect(plugins, PluginKind::Inline, caps, None, None)
         })
         .to_string();
 
     result
 }
 
-/// Protect block plugin syntax by converting to markers
+/// Protect block plugin syntax by converting to tokens
 ///
-/// Converts various block plugin patterns into safe markers:
-/// - `@function(args){{ content }}` → marker with content
-/// - `@function(args){content}` → marker with content
-/// - `@function(args)` → marker with args
-pub fn protect_block_plugins(input: &str) -> String {
+/// Converts various block plugin patterns into tokens and records the calls
+/// in `plugins`:
+/// - `@function(args){{ content }}` → call with content
+/// - `@function(args){content}` → call with content
+/// - `@function(args)` → call with args
+pub fn protect_block_plugins(input: &str, plugins: &mut Vec<ProtectedPlugin>) -> String {
     let mut result = input.to_string();
 
     // Protect block plugins multiline: @function(args){{ content }}
-    let block_plugin_multi = Regex::new(r"@(\w+)\(([^)]*)\)\{\{([\s\S]*?)\}\}").unwrap();
-    result = block_plugin_multi
-        .replace_all(&result, |caps: &regex::Captures| {
-            let function = &caps[1];
-            let args = &caps[2];
-            let content = &caps[3];
-            let encoded_content = general_purpose::STANDARD.encode(content.as_bytes());
-            format!(
-                "{{{{BLOCK_PLUGIN:{}:{}:{}:BLOCK_PLUGIN}}}}",
-                function, args, encoded_content
+    result = BLOCK_PLUGIN_MULTILINE
+        .replace_all(&result, |caps: &Captures| {
+            protect(
+                plugins,
+                PluginKind::Block,
+                caps,
+                Some(&caps[2]),
+                Some(&caps[3]),
             )
         })
         .to_string();
 
     // Protect block plugins singleline: @function(args){content}
-    let block_plugin_single = Regex::new(r"@(\w+)\(([^)]*)\)\{([^}]*)\}").unwrap();
-    result = block_plugin_single
-        .replace_all(&result, |caps: &regex::Captures| {
-            let function = &caps[1];
-            let args = &caps[2];
-            let content = &caps[3];
-            let encoded_content = general_purpose::STANDARD.encode(content.as_bytes());
-            format!(
-                "{{{{BLOCK_PLUGIN:{}:{}:{}:BLOCK_PLUGIN}}}}",
-                function, args, encoded_content
+    result = BLOCK_PLUGIN_SINGLELINE
+        .replace_all(&result, |caps: &Captures| {
+            protect(
+                plugins,
+                PluginKind::Block,
+                caps,
+                Some(&caps[2]),
+                Some(&caps[3]),
             )
         })
         .to_string();
 
     // Protect block plugins (args only, no content): @function(args)
-    let block_plugin_argsonly = Regex::new(r"@(\w+)\(([^)]*)\)").unwrap();
-    result = block_plugin_argsonly
-        .replace_all(&result, |caps: &regex::Captures| {
-            let function = &caps[1];
-            let args = &caps[2];
-            let encoded_args = general_purpose::STANDARD.encode(args.as_bytes());
-            format!(
-                "{{{{BLOCK_PLUGIN_ARGSONLY:{}:{}:BLOCK_PLUGIN_ARGSONLY}}}}",
-                function, encoded_args
-            )
+    result = BLOCK_PLUGIN_ARGSONLY
+        .replace_all(&result, |caps: &Captures| {
+            protect(plugins, PluginKind::Block, caps, Some(&caps[2]), None)
         })
         .to_string();
 
     result
 }
 
+/// Replace plugin tokens in `text` with the source of the calls they stand for
+///
+/// Used for text that is kept verbatim, such as plugin bodies that contain
+/// calls protected by an earlier pattern.
+pub fn expand_plugin_sources(text: &str, plugins: &[ProtectedPlugin]) -> String {
+    if !text.contains(markers::TOKEN_START) {
+        return text.to_string();
+    }
+
+    PLUGIN_TOKEN
+        .replace_all(text, |caps: &Captures| {
+            caps[1]
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| plugins.get(index))
+                .map(|plugin| plugin.source.clone())
+                .unwrap_or_default()
+        })
+        .to_string()
+}
+
+/// Record a matched call and return its token
+fn protect(
+    plugins: &mut Vec<ProtectedPlugin>,
+    kind: PluginKind,
+    caps: &Captures,
+    args: Option<&str>,
+    content: Option<&str>,
+) -> String {
+    let plugin = ProtectedPlugin {
+        kind,
+        function: caps[1].to_string(),
+        args: args.map(|a| expand_plugin_sources(a, plugins)),
+        content: content.map(|c| expand_plugin_sources(c, plugins)),
+        source: expand_plugin_sources(&caps[0], plugins),
+    };
+    plugins.push(plugin);
+    markers::token(markers::PLUGIN, plugins.len() - 1)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_protect_inline_plugin_with_content() {
-        let input = "&test{content};";
-        let output = protect_inline_plugins(input);
-        assert!(output.contains("INLINE_PLUGIN:test::"));
-        assert!(!output.contains("&test"));
+        let mut plugins = Vec::new();
+        let output = protect_inline_plugins("&test{content};", &mut plugins);
+        assert_eq!(output, markers::token(markers::PLUGIN, 0));
+        assert_eq!(plugins[0].function, "test");
+        assert_eq!(plugins[0].args, None);
+        assert_eq!(plugins[0].content.as_deref(), Some("content"));
     }
 
     #[test]
     fn test_protect_inline_plugin_with_args_and_content() {
-        let input = "&test(arg1,arg2){content};";
-        let output = protect_inline_plugins(input);
-        assert!(output.contains("INLINE_PLUGIN:test:arg1,arg2:"));
+        let mut plugins = Vec::new();
+        protect_inline_plugins("&test(arg1,arg2){content};", &mut plugins);
+        assert_eq!(plugins[0].args.as_deref(), Some("arg1,arg2"));
+        assert_eq!(plugins[0].source, "&test(arg1,arg2){content};");
     }
 
     #[test]
     fn test_skip_html_entities() {
+        let mut plugins = Vec::new();
         let input = "&lt; &gt; &amp;";
-        let output = protect_inline_plugins(input);
+        let output = protect_inline_plugins(input, &mut plugins);
         assert_eq!(input, output); // Should remain unchanged
+        assert!(plugins.is_empty());
     }
 
     #[test]
     fn test_protect_block_plugin_multiline() {
-        let input = "@test(args){{ content }}";
-        let output = protect_block_plugins(input);
-        assert!(output.contains("BLOCK_PLUGIN:test:args:"));
+        let mut plugins = Vec::new();
+        protect_block_plugins("@test(args){{ content }}", &mut plugins);
+        assert_eq!(plugins[0].kind, PluginKind::Block);
+        assert_eq!(plugins[0].content.as_deref(), Some(" content "));
     }
 
     #[test]
     fn test_protect_block_plugin_single_line() {
-        let input = "@test(args){content}";
-        let output = protect_block_plugins(input);
-        assert!(output.contains("BLOCK_PLUGIN:test:args:"));
+        let mut plugins = Vec::new();
+        protect_block_plugins("@test(args){content}", &mut plugins);
+        assert_eq!(plugins[0].args.as_deref(), Some("args"));
+        assert_eq!(plugins[0].content.as_deref(), Some("content"));
     }
 
     #[test]
     fn test_protect_block_plugin_args_only() {
-        let input = "@test(args)";
-        let output = protect_block_plugins(input);
-        assert!(output.contains("BLOCK_PLUGIN_ARGSONLY:test:"));
+        let mut plugins = Vec::new();
+        protect_block_plugins("@test(args)", &mut plugins);
+        assert_eq!(plugins[0].content, None);
+    }
+
+    #[test]
+    fn test_nested_call_keeps_source() {
+        let mut plugins = Vec::new();
+        let input = "@box(){{ &color(red){x}; }}";
+        let output =
+            protect_block_plugins(&protect_inline_plugins(input, &mut plugins), &mut plugins);
+        assert_eq!(output, markers::token(markers::PLUGIN, 1));
+        assert_eq!(plugins[1].content.as_deref(), Some(" &color(red){x}; "));
+        assert_eq!(plugins[1].source, input);
     }
 }
@@ -0,0 +1,567 @@
+//! Shared plugin syntax detection
+//!
+//! This module owns the plugin patterns and the HTML entity check used by
+//! both the marker-based pipeline (`plugin_markers`) and the
+//! standalone `<template>` renderer (`plugins`). It also provides
+//! [`extract_plugin_calls`], which lists every plugin invocation in a
+//! document without rendering any HTML, so that hosts can prefetch plugin
+//! data before parsing.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+// Inline plugin with content but no args: &function{content};
+pub(crate) static INLINE_PLUGIN_CONTENT_ONLY: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"&(\w+)\{((?:[^{}]|\{[^}]*\})*)\};").unwrap());
+
+// Inline plugin with args and content: &function(args){content};
+// Content may contain nested braces for nested plugins
+pub(crate) static INLINE_PLUGIN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"&(\w+)\(([^)]*)\)\{((?:[^{}]|\{[^}]*\})*)\};").unwrap());
+
+// Inline plugin with args only: &function(args);
+pub(crate) static INLINE_PLUGIN_ARGSONLY: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"&(\w+)\(([^)]*)\);").unwrap());
+
+// Inline plugin without args: &function;
+// Function name must start with a letter to avoid conflicts with numeric entities
+pub(crate) static INLINE_PLUGIN_NOARGS: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"&([a-zA-Z]\w*);").unwrap());
+
+// Block plugin multiline: @function(args){{ content }} (non-greedy)
+pub(crate) static BLOCK_PLUGIN_MULTILINE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"@(\w+)\(([^)]*)\)\{\{([\s\S]*?)\}\}").unwrap());
+
+// Block plugin singleline: @function(args){content}
+pub(crate) static BLOCK_PLUGIN_SINGLELINE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"@(\w+)\(([^)]*)\)\{([^}]*)\}").unwrap());
+
+// Block plugin with args only (no content): @function(args)
+// Must be processed AFTER the patterns with { and {{
+pub(crate) static BLOCK_PLUGIN_ARGSONLY: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"@(\w+)\(([^)]*)\)").unwrap());
+
+// Block plugin without args: @function()
+// Parens are required to distinguish plugins from @mentions
+pub(crate) static BLOCK_PLUGIN_NOARGS: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"@(\w+)\(\)").unwrap());
+
+// Inline code span, used to skip plugin-like text inside code
+static INLINE_CODE: Lazy<Regex> = Lazy::new(|| Regex::new(r"`[^`\n]*`").unwrap());
+
+/// Check if a `&name;` reference is an HTML entity rather than a plugin
+///
+/// Uses the same table as the sanitizer ([`crate::entities`]).
+pub(crate) fn is_html_entity_name(name: &str) -> bool {
+    crate::entities::is_named_reference(name)
+}
+
+/// Check if an inline function is a built-in decoration rather than a plugin
+///
+/// Built-in decorations are rendered directly by the parser
+/// (see `conflict_resolver`), so they never reach the plugin host.
+fn is_builtin_decoration(name: &str, form: CallForm) -> bool {
+    match form {
+        CallForm::Content => matches!(
+            name,
+            "dfn"
+                | "kbd"
+                | "samp"
+                | "var"
+                | "cite"
+                | "q"
+                | "small"
+                | "u"
+                | "bdi"
+                | "ruby"
+                | "time"
+                | "data"
+                | "bdo"
+                | "em"
+                | "lang"
+                | "abbr"
+                | "sup"
+                | "sub"
+                | "badge"
+                | "color"
+                | "size"
+        ),
+        CallForm::ArgsOnly => matches!(name, "sup" | "sub" | "tcy"),
+        CallForm::NoArgs => matches!(name, "wbr" | "br"),
+    }
+}
+
+/// Check if a block function is built in rather than a plugin
+///
+/// `@table(...){{ … }}` and `@csv(...){{ … }}` are rendered by
+/// [`crate::extensions::table`].
+fn is_builtin_block(name: &str, form: CallForm) -> bool {
+    form == CallForm::Content && matches!(name, "table" | "csv")
+}
+
+/// Parse comma-separated plugin args into a vector of trimmed strings
+pub(crate) fn parse_args(args: &str) -> Vec<String> {
+    if args.trim().is_empty() {
+        return vec![];
+    }
+    args.split(',').map(|s| s.trim().to_string()).collect()
+}
+
+/// Plugin invocation style
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PluginKind {
+    /// `&function(args){content};` and its variants
+    Inline,
+    /// `@function(args){{ content }}` and its variants
+    Block,
+}
+
+/// Syntactic form of a call, used to tell decorations from plugins
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CallForm {
+    Content,
+    ArgsOnly,
+    NoArgs,
+}
+
+/// A plugin invocation found in the source text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PluginCall {
+    /// Inline (`&`) or block (`@`) plugin
+    pub kind: PluginKind,
+    /// Plugin function name
+    pub name: String,
+    /// Trimmed, comma-separated arguments
+    pub args: Vec<String>,
+    /// Raw body text, if the call has a `{...}` or `{{...}}` body
+    pub body: Option<String>,
+    /// Names of the enclosing calls, outermost first
+    pub path: Vec<String>,
+    /// Byte range of the whole invocation in the original input
+    pub span: Range<usize>,
+}
+
+/// A detection pattern with the capture groups holding args and body
+struct PluginPattern {
+    regex: &'static Regex,
+    kind: PluginKind,
+    form: CallForm,
+    args_group: Option<usize>,
+    body_group: Option<usize>,
+}
+
+impl PluginPattern {
+    fn new(regex: &'static Regex, kind: PluginKind, form: CallForm) -> Self {
+        Self {
+            regex,
+            kind,
+            form,
+            args_group: None,
+            body_group: None,
+        }
+    }
+
+    fn args(mut self, group: usize) -> Self {
+        self.args_group = Some(group);
+        self
+    }
+
+    fn body(mut self, group: usize) -> Self {
+        self.body_group = Some(group);
+        self
+    }
+}
+
+/// A raw pattern match before decorations are filtered out
+struct Candidate {
+    kind: PluginKind,
+    form: CallForm,
+    name: String,
+    args: String,
+    body: Option<Range<usize>>,
+    span: Range<usize>,
+}
+
+/// Extract every plugin invocation from Universal Markdown source
+///
+/// Detection uses the same patterns as the rendering pipeline; when two
+/// matches overlap, the enclosing one wins and the inner one is reported
+/// as nested. Built-in inline decorations such as `&color` are
+/// not reported, but plugins nested in their bodies are. Plugin-like text
+/// in frontmatter, comments and code is ignored. No HTML is produced.
+///
+/// # Arguments
+///
+/// * `input` - The Universal Markdown source text
+///
+/// # Returns
+///
+/// Plugin calls in document order; nested calls follow their parent
+///
+/// # Examples
+///
+/// ```
+/// use universal_markdown::extract_plugin_calls;
+///
+/// let calls = extract_plugin_calls("@include(Page)\n\nVisits: &counter;");
+/// assert_eq!(calls.len(), 2);
+/// assert_eq!(calls[0].name, "include");
+/// assert_eq!(calls[0].args, vec!["Page"]);
+/// assert_eq!(calls[1].name, "counter");
+/// ```
+pub fn extract_plugin_calls(input: &str) -> Vec<PluginCall> {
+    extract_plugin_calls_up_to(input, usize::MAX)
+}
+
+/// Extract plugin invocations, stopping once `limit` calls are found
+///
+/// # Arguments
+///
+/// * `input` - The Universal Markdown source text
+/// * `limit` - Maximum number of calls to return
+///
+/// # Returns
+///
+/// The first `limit` calls in the order of [`extract_plugin_calls`]
+pub(crate) fn extract_plugin_calls_up_to(input: &str, limit: usize) -> Vec<PluginCall> {
+    let (_, content) = crate::frontmatter::extract_frontmatter(input);
+    let base = input.len() - content.len();
+    let excluded = excluded_ranges(&content);
+
+    let mut calls = Vec::new();
+    scan(&content, 0, &[], &excluded, limit, &mut calls);
+
+    for call in &mut calls {
+        call.span = call.span.start + base..call.span.end + base;
+    }
+    calls
+}
+
+/// Scan `text` (located at `offset` in the content) for plugin calls
+///
+/// Stops adding calls to `out` once it holds `limit` of them.
+fn scan(
+    text: &str,
+    offset: usize,
+    path: &[String],
+    excluded: &[Range<usize>],
+    limit: usize,
+    out: &mut Vec<PluginCall>,
+) {
+    let patterns = [
+        PluginPattern::new(
+            &INLINE_PLUGIN_CONTENT_ONLY,
+            PluginKind::Inline,
+            CallForm::Content,
+        )
+        .body(2),
+        PluginPattern::new(&INLINE_PLUGIN, PluginKind::Inline, CallForm::Content)
+            .args(2)
+            .body(3),
+        PluginPattern::new(
+            &INLINE_PLUGIN_ARGSONLY,
+            PluginKind::Inline,
+            CallForm::ArgsOnly,
+        )
+        .args(2),
+        PluginPattern::new(&INLINE_PLUGIN_NOARGS, PluginKind::Inline, CallForm::NoArgs),
+        PluginPattern::new(
+            &BLOCK_PLUGIN_MULTILINE,
+            PluginKind::Block,
+            CallForm::Content,
+        )
+        .args(2)
+        .body(3),
+        PluginPattern::new(
+            &BLOCK_PLUGIN_SINGLELINE,
+            PluginKind::Block,
+            CallForm::Content,
+        )
+        .args(2)
+        .body(3),
+        PluginPattern::new(
+            &BLOCK_PLUGIN_ARGSONLY,
+            PluginKind::Block,
+            CallForm::ArgsOnly,
+        )
+        .args(2),
+    ];
+
+    // Accepted candidates never overlap, so keyed by start they are also
+    // ordered by end and only their neighbours need to be checked
+    let mut candidates: BTreeMap<usize, Candidate> = BTreeMap::new();
+    for pattern in patterns {
+        for caps in pattern.regex.captures_iter(text) {
+            let whole = caps.get(0).unwrap();
+            let span = whole.start()..whole.end();
+            let absolute = offset + span.start..offset + span.end;
+
+            // An enclosing call wins over calls inside its body, which are
+            // found again when the body is scanned. Partial overlaps lose.
+            let starts_inside_earlier = candidates
+                .range(..span.start)
+                .next_back()
+                .is_some_and(|(_, c)| c.span.end > span.start);
+            let ends_outside = candidates
+                .range(span.start..span.end)
+                .next_back()
+                .is_some_and(|(_, c)| c.span.end > span.end);
+            if starts_inside_earlier || ends_outside || is_excluded(excluded, &absolute) {
+                continue;
+            }
+
+            let name = caps[1].to_string();
+            if pattern.form == CallForm::NoArgs && is_html_entity_name(&name) {
+                continue;
+            }
+
+            let contained: Vec<usize> = candidates
+                .range(span.start..span.end)
+                .map(|(&start, _)| start)
+                .collect();
+            for start in contained {
+                candidates.remove(&start);
+            }
+            candidates.insert(
+                span.start,
+                Candidate {
+                    kind: pattern.kind,
+                    form: pattern.form,
+                    name,
+                    args: pattern
+                        .args_group
+                        .and_then(|g| caps.get(g))
+                        .map_or(String::new(), |m| m.as_str().to_string()),
+                    body: pattern
+                        .body_group
+                        .and_then(|g| caps.get(g))
+                        .map(|m| m.start()..m.end()),
+                    span,
+                },
+            );
+        }
+    }
+
+    for candidate in candidates.into_values() {
+        if out.len() >= limit {
+            return;
+        }
+
+        let is_decoration = match candidate.kind {
+            PluginKind::Inline => is_builtin_decoration(&candidate.name, candidate.form),
+            PluginKind::Block => is_builtin_block(&candidate.name, candidate.form),
+        };
+
+        if !is_decoration {
+            out.push(PluginCall {
+                kind: candidate.kind,
+                name: candidate.name.clone(),
+                args: parse_args(&candidate.args),
+                body: candidate.body.clone().map(|r| text[r].to_string()),
+                path: path.to_vec(),
+                span: offset + candidate.span.start..offset + candidate.span.end,
+            });
+        }
+
+        if let Some(body) = candidate.body {
+            let mut nested_path = path.to_vec();
+            nested_path.push(candidate.name);
+            scan(
+                &text[body.clone()],
+                offset + body.start,
+                &nested_path,
+                excluded,
+                limit,
+                out,
+            );
+        }
+    }
+}
+
+/// Compute byte ranges that the renderer never treats as plugin syntax
+///
+/// Mirrors the rules of `preprocessor::remove_comments`: fenced code blocks,
+/// inline code spans, `/* ... */` comments and `//` line comments (except
+/// after `:` as in URL schemes). The ranges are returned sorted and merged.
+fn excluded_ranges(input: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut in_code_block = false;
+    let mut code_fence_marker = "";
+    let mut comment_start: Option<usize> = None;
+    let mut line_start = 0;
+
+    for line in input.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            if !in_code_block {
+                in_code_block = true;
+                code_fence_marker = if trimmed.starts_with("```") {
+                    "```"
+                } else {
+                    "~~~"
+                };
+            } else if trimmed.contains(code_fence_marker) {
+                in_code_block = false;
+            }
+            ranges.push(line_start..line_start + line.len());
+            line_start += line.len();
+            continue;
+        }
+
+        if in_code_block {
+            ranges.push(line_start..line_start + line.len());
+            line_start += line.len();
+            continue;
+        }
+
+        let mut in_inline_code = false;
+        let mut prev_ch = '\0';
+        let mut chars = line.char_indices().peekable();
+        while let Some((i, ch)) = chars.next() {
+            let pos = line_start + i;
+            if comment_start.is_none() && ch == '`' {
+                in_inline_code = !in_inline_code;
+                prev_ch = ch;
+                continue;
+            }
+            if in_inline_code {
+                prev_ch = ch;
+                continue;
+            }
+
+            let next = chars.peek().map(|&(_, c)| c);
+            match comment_start {
+                None if ch == '/' && next == Some('*') => {
+                    comment_start = Some(pos);
+                    chars.next();
+                    prev_ch = '*';
+                    continue;
+                }
+                Some(start) if ch == '*' && next == Some('/') => {
+                    ranges.push(start..pos + 2);
+                    comment_start = None;
+                    chars.next();
+                    prev_ch = '/';
+                    continue;
+                }
+                None if ch == '/' && next == Some('/') && prev_ch != ':' => {
+                    ranges.push(pos..line_start + line.trim_end_matches('\n').len());
+                    break;
+                }
+                _ => {}
+            }
+            prev_ch = ch;
+        }
+
+        line_start += line.len();
+    }
+
+    if let Some(start) = comment_start {
+        ranges.push(start..input.len());
+    }
+
+    for m in INLINE_CODE.find_iter(input) {
+        ranges.push(m.start()..m.end());
+    }
+
+    // Merge into sorted, disjoint ranges for `is_excluded`
+    ranges.sort_by_key(|r| r.start);
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// Check whether `span` overlaps one of the sorted, disjoint `excluded` ranges
+fn is_excluded(excluded: &[Range<usize>], span: &Range<usize>) -> bool {
+    let i = excluded.partition_point(|r| r.end <= span.start);
+    excluded.get(i).is_some_and(|r| r.start < span.end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_inline_and_block() {
+        let input = "@include(Page)\n\n@rss(https://example.com/feed, 5)\n\nCount: &counter;";
+        let calls = extract_plugin_calls(input);
+        assert_eq!(calls.len(), 3);
+
+        assert_eq!(calls[0].kind, PluginKind::Block);
+        assert_eq!(calls[0].name, "include");
+        assert_eq!(calls[0].args, vec!["Page"]);
+        assert_eq!(&input[calls[0].span.clone()], "@include(Page)");
+
+        assert_eq!(calls[1].name, "rss");
+        assert_eq!(calls[1].args, vec!["https://example.com/feed", "5"]);
+
+        assert_eq!(calls[2].kind, PluginKind::Inline);
+        assert_eq!(calls[2].name, "counter");
+        assert!(calls[2].args.is_empty());
+        assert_eq!(calls[2].body, None);
+    }
+
+    #[test]
+    fn test_extract_body_and_nesting() {
+        let input = "@box(){{ &outer(a){text &inner(b){deep}; more}; }}";
+        let calls = extract_plugin_calls(input);
+        let names: Vec<&str> = calls.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["box", "outer", "inner"]);
+
+        assert_eq!(
+            calls[0].body.as_deref(),
+            Some(" &outer(a){text &inner(b){deep}; more}; ")
+        );
+        assert!(calls[0].path.is_empty());
+        assert_eq!(calls[1].path, vec!["box"]);
+        assert_eq!(calls[2].path, vec!["box", "outer"]);
+        assert_eq!(&input[calls[2].span.clone()], "&inner(b){deep};");
+    }
+
+    #[test]
+    fn test_extract_skips_entities_and_decorations() {
+        let input = "&lt; &hearts; &color(red){&counter;}; &br; &sup(2);";
+        let calls = extract_plugin_calls(input);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].name, "counter");
+        assert_eq!(calls[0].path, vec!["color"]);
+    }
+
+    #[test]
+    fn test_extract_skips_table_block() {
+        let calls = extract_plugin_calls("@table(striped){{\n| &counter; |\n}}");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].name, "counter");
+        assert_eq!(calls[0].path, vec!["table"]);
+    }
+
+    #[test]
+    fn test_extract_skips_code_and_comments() {
+        let input =
+            "`&inline;` text\n```\n@fenced(x)\n```\n// @comment(x)\n/* &block; */ &counter;";
+        let calls = extract_plugin_calls(input);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].name, "counter");
+    }
+
+    #[test]
+    fn test_extract_span_after_frontmatter() {
+        let input = "---\ntitle: Test\n---\n&counter;";
+        let calls = extract_plugin_calls(input);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(&input[calls[0].span.clone()], "&counter;");
+    }
+
+    #[test]
+    fn test_mentions_are_not_plugins() {
+        let calls = extract_plugin_calls("Ask @alice about it");
+        assert!(calls.is_empty());
+    }
+}
@@ -9,8 +9,10 @@
 //! Actual plugin execution is handled by backend (Nuxt/Laravel) or frontend.
 //! Content within plugins may contain nested plugins or other Wiki syntax.
 
-use once_cell::sync::Lazy;
-use regex::Regex;
+use super::plugin_syntax::{
+    BLOCK_PLUGIN_ARGSONLY, BLOCK_PLUGIN_MULTILINE, BLOCK_PLUGIN_NOARGS, BLOCK_PLUGIN_SINGLELINE,
+    INLINE_PLUGIN, INLINE_PLUGIN_ARGSONLY, INLINE_PLUGIN_NOARGS, is_html_entity_name, parse_args,
+};
 
 /// Escape HTML special characters
 ///
@@ -28,22 +30,6 @@ fn escape_html_text(input: &str) -> String {
         .replace('>', "&gt;")
 }
 
-/// Parse comma-separated args into a vector
-///
-/// # Arguments
-///
-/// * `args` - Comma-separated argument string
-///
-/// # Returns
-///
-/// Vector of trimmed argument strings
-fn parse_args(args: &str) -> Vec<String> {
-    if args.trim().is_empty() {
-        return vec![];
-    }
-    args.split(',').map(|s| s.trim().to_string()).collect()
-}
-
 /// Render args as <data> elements
 ///
 /// # Arguments
@@ -62,77 +48,6 @@ fn render_args_as_data(args: &str) -> String {
         .join("")
 }
 
-// Block plugin patterns
-static BLOCK_PLUGIN_MULTILINE: Lazy<Regex> = Lazy::new(|| {
-    // Match @function(args){{ content }} using non-greedy match
-    Regex::new(r"@(\w+)\(([^)]*)\)\{\{([\s\S]*?)\}\}").unwrap()
-});
-
-static BLOCK_PLUGIN_SINGLELINE: Lazy<Regex> = Lazy::new(|| {
-    // Match @function(args){content} (single braces)
-    Regex::new(r"@(\w+)\(([^)]*)\)\{([^}]*)\}").unwrap()
-});
-
-// Block plugin with args only (no content): @function(args)
-static BLOCK_PLUGIN_ARGSONLY: Lazy<Regex> = Lazy::new(|| {
-    // Match @function(args) - args only, no content
-    // This should be processed AFTER patterns with { and {{
-    Regex::new(r"@(\w+)\(([^)]*)\)").unwrap()
-});
-
-// Block plugin without args: @function()
-static BLOCK_PLUGIN_NOARGS: Lazy<Regex> = Lazy::new(|| {
-    // Match @function() - parens required to distinguish from @mentions
-    Regex::new(r"@(\w+)\(\)").unwrap()
-});
-
-// Inline plugin pattern
-static INLINE_PLUGIN: Lazy<Regex> = Lazy::new(|| {
-    // Match &function(args){content};
-    // Content may contain nested braces for nested plugins
-    Regex::new(r"&(\w+)\(([^)]*)\)\{((?:[^{}]|\{[^}]*\})*)\};").unwrap()
-});
-
-// Inline plugin with args only: &function(args);
-static INLINE_PLUGIN_ARGSONLY: Lazy<Regex> = Lazy::new(|| {
-    // Match &function(args); (no content)
-    Regex::new(r"&(\w+)\(([^)]*)\);").unwrap()
-});
-
-// Inline plugin without args: &function;
-static INLINE_PLUGIN_NOARGS: Lazy<Regex> = Lazy::new(|| {
-    // Match &function; (no args, no content)
-    // Function name must start with a letter to avoid conflicts with HTML entities
-    Regex::new(r"&([a-zA-Z]\w*);").unwrap()
-});
-
-// Common HTML entities that should NOT be treated as plugins
-static HTML_ENTITIES: Lazy<std::collections::HashSet<&'static str>> = Lazy::new(|| {
-    [
-        "lt", "gt", "amp", "nbsp", "quot", "apos", "ndash", "mdash", "hellip", "copy", "reg",
-        "trade", "times", "divide", "plusmn", "le", "ge", "ne", "asymp", "equiv", "forall",
-        "exist", "empty", "nabla", "isin", "notin", "ni", "prod", "sum", "minus", "lowast",
-        "radic", "prop", "infin", "ang", "and", "or", "cap", "cup", "int", "there4", "sim", "cong",
-        "sub", "sup", "nsub", "sube", "supe", "oplus", "otimes", "perp", "sdot", "lceil", "rceil",
-        "lfloor", "rfloor", "lang", "rang", "loz", "spades", "clubs", "hearts", "diams", "alpha",
-        "beta", "gamma", "delta", "epsilon", "zeta", "eta", "theta", "iota", "kappa", "lambda",
-        "mu", "nu", "xi", "omicron", "pi", "rho", "sigma", "tau", "upsilon", "phi", "chi", "psi",
-        "omega", "Iuml", "iuml", "Uuml", "uuml", "Auml", "auml", "Ouml", "ouml", "Euml", "euml",
-        "Aring", "aring", "AElig", "aelig", "Ccedil", "ccedil", "Eth", "eth", "Ntilde", "ntilde",
-        "Oslash", "oslash", "Thorn", "thorn", "szlig", "yuml", "Agrave", "agrave", "Aacute",
-        "aacute", "Acirc", "acirc", "Atilde", "atilde", "Egrave", "egrave", "Eacute", "eacute",
-        "Ecirc", "ecirc", "Igrave", "igrave", "Iacute", "iacute", "Icirc", "icirc", "Ograve",
-        "ograve", "Oacute", "oacute", "Ocirc", "ocirc", "Otilde", "otilde", "Ugrave", "ugrave",
-        "Uacute", "uacute", "Ucirc", "ucirc", "Yacute", "yacute", "cent", "pound", "curren", "yen",
-        "brvbar", "sect", "uml", "ordf", "laquo", "not", "shy", "macr", "deg", "sup2", "sup3",
-        "acute", "micro", "para", "middot", "cedil", "sup1", "ordm", "raquo", "frac14", "frac12",
-        "frac34", "iquest", "ensp", "emsp", "thinsp", "zwnj", "zwj", "lrm", "rlm",
-    ]
-    .iter()
-    .copied()
-    .collect()
-});
-
 /// Apply plugin syntax transformation
 ///
 /// Converts plugin syntax to <template> elements with <data> children.
@@ -295,7 +210,7 @@ pub fn apply_plugin_syntax(html: &str) -> String {
             let function = caps.get(1).map_or("", |m| m.as_str());
 
             // Skip HTML entities
-            if HTML_ENTITIES.contains(function) {
+            if is_html_entity_name(function) {
                 return caps[0].to_string(); // Return original match unchanged
             }
 
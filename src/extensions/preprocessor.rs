@@ -5,6 +5,8 @@
 use once_cell::sync::Lazy;
 use regex::Regex;
 
+use super::markers;
+
 // Discord-style underline pattern: __text__
 static DISCORD_UNDERLINE: Lazy<Regex> = Lazy::new(|| Regex::new(r"__([^_]+)__").unwrap());
 
@@ -123,61 +125,19 @@ pub fn remove_comments(input: &str) -> String {
     result
 }
 
-/// Process definition lists (:term|definition syntax)
-///
-/// Converts consecutive lines starting with `:term|definition` into
-/// marker placeholders that will be converted to HTML later.
-pub fn process_definition_lists(input: &str) -> String {
-    let mut result = Vec::new();
-    let mut lines = input.lines().peekable();
-
-    while let Some(line) = lines.next() {
-        // Check if this line starts a definition list
-        if line.trim_start().starts_with(':') && line.contains('|') {
-            let mut dl_items = Vec::new();
-
-            // Collect consecutive definition list items
-            let mut current_line = line;
-            loop {
-                if let Some(stripped) = current_line.trim_start().strip_prefix(':') {
-                    if let Some((term, definition)) = stripped.split_once('|') {
-                        dl_items.push((term.trim().to_string(), definition.trim().to_string()));
-                    }
-                }
-
-                // Check if next line is also a definition list item
-                match lines.peek() {
-                    Some(next_line)
-                        if next_line.trim_start().starts_with(':') && next_line.contains('|') =>
-                    {
-                        current_line = lines.next().unwrap();
-                    }
-                    _ => break,
-                }
-            }
-
-            // Create marker for the definition list
-            if !dl_items.is_empty() {
-                let items_json = serde_json::to_string(&dl_items).unwrap();
-                result.push(format!(
-                    "{{{{DEFINITION_LIST:{}:DEFINITION_LIST}}}}",
-                    items_json
-                ));
-            }
-        } else {
-            result.push(line.to_string());
-        }
-    }
-
-    result.join("\n")
-}
-
 /// Convert Discord-style underline (__text__) to placeholder before Markdown parsing
 ///
 /// This prevents CommonMark from converting __text__ to <strong>
 pub fn preprocess_discord_underline(input: &str) -> String {
     DISCORD_UNDERLINE
-        .replace_all(input, "{{UNDERLINE:$1:UNDERLINE}}")
+        .replace_all(input, |caps: &regex::Captures| {
+            format!(
+                "{}{}{}",
+                markers::open(markers::UNDERLINE),
+                &caps[1],
+                markers::close(markers::UNDERLINE)
+            )
+        })
         .to_string()
 }
 
@@ -185,8 +145,8 @@ pub fn preprocess_discord_underline(input: &str) -> String {
 ///
 /// This should be called after Markdown parsing
 pub fn postprocess_discord_underline(html: &str) -> String {
-    html.replace("{{UNDERLINE:", "<u>")
-        .replace(":UNDERLINE}}", "</u>")
+    html.replace(&markers::open(markers::UNDERLINE), "<u>")
+        .replace(&markers::close(markers::UNDERLINE), "</u>")
 }
 
 #[cfg(test)]
@@ -225,27 +185,27 @@ mod tests {
         assert!(output.contains("// code comment"));
     }
 
-    #[test]
-    fn test_definition_list() {
-        let input = ":term1|definition1\n:term2|definition2\nregular text";
-        let output = process_definition_lists(input);
-        assert!(output.contains("{{DEFINITION_LIST:"));
-        assert!(output.contains("DEFINITION_LIST}}"));
-        assert!(output.contains("regular text"));
-    }
-
     #[test]
     fn test_preprocess_discord_underline() {
         let input = "This is __underlined__ text.";
         let output = preprocess_discord_underline(input);
-        assert!(output.contains("{{UNDERLINE:underlined:UNDERLINE}}"));
+        let expected = format!(
+            "{}underlined{}",
+            markers::open(markers::UNDERLINE),
+            markers::close(markers::UNDERLINE)
+        );
+        assert!(output.contains(&expected));
         assert!(!output.contains("__underlined__"));
     }
 
     #[test]
     fn test_postprocess_discord_underline() {
-        let input = "<p>This is {{UNDERLINE:underlined:UNDERLINE}} text.</p>";
-        let output = postprocess_discord_underline(input);
+        let input = format!(
+            "<p>This is {}underlined{} text.</p>",
+            markers::open(markers::UNDERLINE),
+            markers::close(markers::UNDERLINE)
+        );
+        let output = postprocess_discord_underline(&input);
         assert_eq!(output, "<p>This is <u>underlined</u> text.</p>");
     }
 
@@ -253,10 +213,7 @@ mod tests {
     fn test_discord_underline_roundtrip() {
         let input = "Text with __underline__ here.";
         let preprocessed = preprocess_discord_underline(input);
-        let html = format!(
-            "<p>{}</p>",
-            preprocessed.replace("__underline__", "{{UNDERLINE:underline:UNDERLINE}}")
-        );
+        let html = format!("<p>{}</p>", preprocessed);
         let output = postprocess_discord_underline(&html);
         assert!(output.contains("<u>underline</u>"));
     }
@@ -0,0 +1,165 @@
+//! Ruby annotations
+//!
+//! `&ruby(reading){text};` supports three forms:
+//!
+//! - Group ruby: one reading for the whole text (`&ruby(あした){明日};`)
+//! - Mono ruby: readings separated by `.` are paired with the characters
+//!   (grapheme clusters) of the text (`&ruby(かん.じ){漢字};`)
+//! - Double-sided ruby: a second annotation after `,` annotates the whole
+//!   ruby in an outer `<ruby>`, e.g. a translation
+//!   (`&ruby(かん.じ,kanji){漢字};`)
+//!
+//! Every annotation is surrounded by `<rp>` parentheses for readers without
+//! ruby support.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use unicode_segmentation::UnicodeSegmentation;
+
+// HTML entity, counted as one character
+static ENTITY: Lazy<Regex> = Lazy::new(|| Regex::new(r"&#?[A-Za-z0-9]+;").unwrap());
+
+/// Render a ruby annotation
+///
+/// When the number of `.`-separated readings differs from the number of
+/// characters in `text`, or `text` contains markup, the whole reading is
+/// used as group ruby.
+/// Both arguments are expected to be HTML-escaped already; characters are
+/// grapheme clusters (a base with its combining marks or variation
+/// selector), and entities count as one character.
+///
+/// # Arguments
+///
+/// * `args` - The reading, optionally followed by `,` and a second annotation
+/// * `text` - The base text
+///
+/// # Returns
+///
+/// The `<ruby>` element
+///
+/// # Examples
+///
+/// ```
+/// use universal_markdown::extensions::ruby::render_ruby;
+///
+/// assert_eq!(
+///     render_ruby("かん.じ", "漢字"),
+///     "<ruby>漢<rp>(</rp><rt>かん</rt><rp>)</rp>字<rp>(</rp><rt>じ</rt><rp>)</rp></ruby>"
+/// );
+/// assert_eq!(
+///     render_ruby("あした,tomorrow", "明日"),
+///     "<ruby><ruby>明日<rp>(</rp><rt>あした</rt><rp>)</rp></ruby><rp>(</rp><rt>tomorrow</rt><rp>)</rp></ruby>"
+/// );
+/// ```
+pub fn render_ruby(args: &str, text: &str) -> String {
+    let (reading, annotation) = match args.split_once(',') {
+        Some((reading, annotation)) => (reading.trim(), Some(annotation.trim())),
+        None => (args.trim(), None),
+    };
+
+    let mut html = String::from("<ruby>");
+
+    let readings: Vec<&str> = reading.split('.').map(str::trim).collect();
+    let characters = if text.contains('<') {
+        Vec::new()
+    } else {
+        characters(text)
+    };
+    if readings.len() > 1 && readings.len() == characters.len() {
+        for (character, reading) in characters.iter().zip(&readings) {
+            html.push_str(character);
+            push_rt(&mut html, reading);
+        }
+    } else {
+        html.push_str(text);
+        push_rt(&mut html, reading);
+    }
+
+    html.push_str("</ruby>");
+
+    if let Some(annotation) = annotation.filter(|a| !a.is_empty()) {
+        html = format!("<ruby>{}", html);
+        push_rt(&mut html, annotation);
+        html.push_str("</ruby>");
+    }
+    html
+}
+
+/// Split escaped text into grapheme clusters, keeping entities whole
+fn characters(text: &str) -> Vec<&str> {
+    let mut characters = Vec::new();
+    let mut start = 0;
+    for entity in ENTITY.find_iter(text) {
+        characters.extend(text[start..entity.start()].graphemes(true));
+        characters.push(entity.as_str());
+        start = entity.end();
+    }
+    characters.extend(text[start..].graphemes(true));
+    characters
+}
+
+fn push_rt(html: &mut String, reading: &str) {
+    html.push_str(&format!("<rp>(</rp><rt>{}</rt><rp>)</rp>", reading));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_ruby() {
+        assert_eq!(
+            render_ruby("あした", "明日"),
+            "<ruby>明日<rp>(</rp><rt>あした</rt><rp>)</rp></ruby>"
+        );
+        // Reading count does not match the text
+        assert_eq!(
+            render_ruby("U.S.", "米国"),
+            "<ruby>米国<rp>(</rp><rt>U.S.</rt><rp>)</rp></ruby>"
+        );
+    }
+
+    #[test]
+    fn test_mono_ruby() {
+        assert_eq!(
+            render_ruby("とう.きょう.と", "東京都"),
+            "<ruby>東<rp>(</rp><rt>とう</rt><rp>)</rp>京<rp>(</rp><rt>きょう</rt><rp>)</rp>都<rp>(</rp><rt>と</rt><rp>)</rp></ruby>"
+        );
+        // Entities are a single character
+        assert_eq!(
+            render_ruby("and.a", "&amp;A"),
+            "<ruby>&amp;<rp>(</rp><rt>and</rt><rp>)</rp>A<rp>(</rp><rt>a</rt><rp>)</rp></ruby>"
+        );
+    }
+
+    #[test]
+    fn test_grapheme_clusters_and_markup() {
+        // 葛 with a variation selector and a base with a combining mark
+        // are one character each
+        assert_eq!(
+            render_ruby("かつ.しか", "葛\u{E0100}飾"),
+            "<ruby>葛\u{E0100}<rp>(</rp><rt>かつ</rt><rp>)</rp>飾<rp>(</rp><rt>しか</rt><rp>)</rp></ruby>"
+        );
+        assert_eq!(
+            render_ruby("e.x", "e\u{301}x"),
+            "<ruby>e\u{301}<rp>(</rp><rt>e</rt><rp>)</rp>x<rp>(</rp><rt>x</rt><rp>)</rp></ruby>"
+        );
+        // Tags are never split; the reading becomes group ruby
+        assert_eq!(
+            render_ruby("a.b", "<em>ab</em>"),
+            "<ruby><em>ab</em><rp>(</rp><rt>a.b</rt><rp>)</rp></ruby>"
+        );
+    }
+
+    #[test]
+    fn test_double_sided_ruby() {
+        assert_eq!(
+            render_ruby("かん.じ, Chinese characters", "漢字"),
+            "<ruby><ruby>漢<rp>(</rp><rt>かん</rt><rp>)</rp>字<rp>(</rp><rt>じ</rt><rp>)</rp></ruby><rp>(</rp><rt>Chinese characters</rt><rp>)</rp></ruby>"
+        );
+        assert_eq!(
+            render_ruby("あした,", "明日"),
+            render_ruby("あした", "明日")
+        );
+    }
+}
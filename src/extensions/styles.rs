@@ -0,0 +1,550 @@
+//! Custom style values for decorations
+//!
+//! `COLOR()` and `SIZE()` map Bootstrap names to classes; any other value
+//! becomes a custom style declaration. Those values come straight from user
+//! input, so every declaration goes through [`Styles::apply`], which only
+//! accepts plain colors and lengths:
+//!
+//! - Colors: named colors, `#rgb`/`#rgba`/`#rrggbb`/`#rrggbbaa`, and
+//!   `rgb()`/`hsl()`/`oklch()` (and related functions) with numeric arguments
+//! - Lengths: a non-negative number with an allowlisted unit
+//!
+//! Anything else (`red;position:fixed`, `url(...)`, `expression(...)`) is
+//! dropped and reported as a [`Diagnostic`].
+//!
+//! Valid declarations are emitted according to the [`StylePolicy`]: as
+//! inline `style` attributes, not at all, or as generated classes whose
+//! rules are collected into a stylesheet (for a CSP without
+//! `style-src 'unsafe-inline'`).
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+
+use crate::diagnostics::Diagnostic;
+
+/// Prefix of the classes generated in [`StylePolicy::Classes`] mode
+pub const CLASS_PREFIX: &str = "umd-c-";
+
+/// How custom style values are emitted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StylePolicy {
+    /// Emit validated values as inline `style` attributes
+    #[default]
+    Inline,
+    /// Never emit `style` attributes; custom values are dropped
+    Disallow,
+    /// Emit a generated class per distinct declaration and collect the
+    /// rules into a stylesheet
+    Classes,
+}
+
+/// Validates custom style values and records what was dropped
+#[derive(Debug, Clone, Default)]
+pub struct Styles {
+    policy: StylePolicy,
+    diagnostics: Vec<Diagnostic>,
+    /// Properties and values rejected by validation, in order
+    invalid_values: Vec<(String, String)>,
+    /// Generated classes and their declarations, in order of first use
+    rules: Vec<(String, String)>,
+    /// Generated class of each declaration in `rules`
+    classes: HashMap<String, String>,
+    /// Classes in `rules`, to detect hash collisions
+    generated: HashSet<String>,
+}
+
+impl Styles {
+    /// Create a collector for the given policy
+    pub fn new(policy: StylePolicy) -> Self {
+        Self {
+            policy,
+            diagnostics: Vec::new(),
+            invalid_values: Vec::new(),
+            rules: Vec::new(),
+            classes: HashMap::new(),
+            generated: HashSet::new(),
+        }
+    }
+
+    /// Emit a custom value as a declaration or a generated class
+    ///
+    /// `font-size` and `width` values must be lengths; every other property
+    /// takes a color.
+    /// Invalid values (and every value under [`StylePolicy::Disallow`]) are
+    /// dropped with a diagnostic.
+    ///
+    /// # Arguments
+    ///
+    /// * `property` - CSS property (`color`, `background-color`, `font-size`
+    ///   or `width`)
+    /// * `value` - Custom value from the input
+    /// * `classes` - Class list of the element; receives generated classes
+    /// * `declarations` - Style declarations of the element
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use universal_markdown::extensions::styles::{StylePolicy, Styles};
+    ///
+    /// let (mut classes, mut declarations) = (Vec::new(), Vec::new());
+    /// let mut styles = Styles::default();
+    /// styles.apply("color", "#f00", &mut classes, &mut declarations);
+    /// styles.apply("color", "red;position:fixed", &mut classes, &mut declarations);
+    /// assert_eq!(declarations, vec!["color: #f00"]);
+    /// assert_eq!(styles.diagnostics().len(), 1);
+    ///
+    /// let mut styles = Styles::new(StylePolicy::Classes);
+    /// styles.apply("color", "#f00", &mut classes, &mut declarations);
+    /// assert!(classes[0].starts_with("umd-c-"));
+    /// assert!(styles.stylesheet().unwrap().contains("{color: #f00}"));
+    /// ```
+    pub fn apply(
+        &mut self,
+        property: &str,
+        value: &str,
+        classes: &mut Vec<String>,
+        declarations: &mut Vec<String>,
+    ) {
+        if let Some(declaration) = self.validate(property, value) {
+            self.emit(declaration, classes, declarations);
+        }
+    }
+
+    /// Validate a custom value without emitting it
+    ///
+    /// Used where declarations have to be compared by property before they
+    /// are emitted, e.g. table cells and their column defaults.
+    ///
+    /// # Returns
+    ///
+    /// The `property: value` declaration, or `None` if the value was
+    /// dropped
+    pub(crate) fn validate(&mut self, property: &str, value: &str) -> Option<String> {
+        let value = value.trim();
+        let valid = match property {
+            "font-size" | "width" => is_valid_length(value),
+            _ => is_valid_color(value),
+        };
+
+        if !valid {
+            self.diagnostics.push(Diagnostic::new(
+                "invalid-style-value",
+                format!("Dropped invalid {} value: {}", property, value),
+            ));
+            self.invalid_values
+                .push((property.to_string(), value.to_string()));
+            return None;
+        }
+
+        Some(format!("{}: {}", property, value))
+    }
+
+    /// Emit a validated declaration according to the policy
+    pub(crate) fn emit(
+        &mut self,
+        declaration: String,
+        classes: &mut Vec<String>,
+        declarations: &mut Vec<String>,
+    ) {
+        match self.policy {
+            StylePolicy::Inline => declarations.push(declaration),
+            StylePolicy::Disallow => self.diagnostics.push(Diagnostic::new(
+                "inline-style-disallowed",
+                format!("Dropped inline style: {}", declaration),
+            )),
+            StylePolicy::Classes => classes.push(self.class_for(declaration)),
+        }
+    }
+
+    /// Generated class for `declaration`, registering its rule on first use
+    ///
+    /// Class names are derived from a 64-bit SHA-256 prefix of the
+    /// declaration, so the same value gets the same class in every
+    /// document. Should two declarations of a document share a hash, the
+    /// later one gets a numbered suffix.
+    fn class_for(&mut self, declaration: String) -> String {
+        if let Some(class) = self.classes.get(&declaration) {
+            return class.clone();
+        }
+
+        let base = base_class(&declaration);
+        let mut class = base.clone();
+        let mut suffix = 1;
+        while self.generated.contains(&class) {
+            suffix += 1;
+            class = format!("{}-{}", base, suffix);
+        }
+
+        self.generated.insert(class.clone());
+        self.classes.insert(declaration.clone(), class.clone());
+        self.rules.push((class.clone(), declaration));
+        class
+    }
+
+    /// Stylesheet with the rules of the generated classes
+    ///
+    /// # Returns
+    ///
+    /// One `.class {declaration}` rule per line, or `None` if no classes
+    /// were generated
+    pub fn stylesheet(&self) -> Option<String> {
+        if self.rules.is_empty() {
+            return None;
+        }
+
+        Some(
+            self.rules
+                .iter()
+                .map(|(class, declaration)| format!(".{} {{{}}}", class, declaration))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )
+    }
+
+    /// Record a diagnostic about a decoration value
+    pub(crate) fn report(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Diagnostics recorded so far
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Properties and values dropped as invalid, as `(property, value)`
+    pub fn invalid_values(&self) -> &[(String, String)] {
+        &self.invalid_values
+    }
+
+    /// Consume the collector and return its diagnostics
+    pub fn into_diagnostics(self) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+}
+
+/// Generated class of a declaration before collision handling
+fn base_class(declaration: &str) -> String {
+    let digest = Sha256::digest(declaration.as_bytes());
+    let hash: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}{}", CLASS_PREFIX, hash)
+}
+
+// Hex colors: #rgb, #rgba, #rrggbb, #rrggbbaa
+static HEX_COLOR: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^#(?:[0-9a-fA-F]{3,4}|[0-9a-fA-F]{6}|[0-9a-fA-F]{8})$").unwrap());
+
+// Color functions and their argument list
+static COLOR_FUNCTION: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(?i:rgba?|hsla?|hwb|lab|lch|oklab|oklch)\(([^()]*)\)$").unwrap());
+
+// A numeric color function argument: number with optional percent or angle unit
+static COLOR_ARGUMENT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?i:[+-]?(?:\d+(?:\.\d*)?|\.\d+)(?:e[+-]?\d+)?(?:%|deg|grad|rad|turn)?|none)$")
+        .unwrap()
+});
+
+// A non-negative length with an allowlisted unit
+static LENGTH: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?:\d+(?:\.\d+)?|\.\d+)(?i:px|pt|em|rem|ex|ch|%|vw|vh|vmin|vmax)$").unwrap()
+});
+
+// CSS named colors
+const NAMED_COLORS: &[&str] = &[
+    "aliceblue",
+    "antiquewhite",
+    "aqua",
+    "aquamarine",
+    "azure",
+    "beige",
+    "bisque",
+    "black",
+    "blanchedalmond",
+    "blue",
+    "blueviolet",
+    "brown",
+    "burlywood",
+    "cadetblue",
+    "chartreuse",
+    "chocolate",
+    "coral",
+    "cornflowerblue",
+    "cornsilk",
+    "crimson",
+    "currentcolor",
+    "cyan",
+    "darkblue",
+    "darkcyan",
+    "darkgoldenrod",
+    "darkgray",
+    "darkgreen",
+    "darkgrey",
+    "darkkhaki",
+    "darkmagenta",
+    "darkolivegreen",
+    "darkorange",
+    "darkorchid",
+    "darkred",
+    "darksalmon",
+    "darkseagreen",
+    "darkslateblue",
+    "darkslategray",
+    "darkslategrey",
+    "darkturquoise",
+    "darkviolet",
+    "deeppink",
+    "deepskyblue",
+    "dimgray",
+    "dimgrey",
+    "dodgerblue",
+    "firebrick",
+    "floralwhite",
+    "forestgreen",
+    "fuchsia",
+    "gainsboro",
+    "ghostwhite",
+    "gold",
+    "goldenrod",
+    "gray",
+    "green",
+    "greenyellow",
+    "grey",
+    "honeydew",
+    "hotpink",
+    "indianred",
+    "indigo",
+    "ivory",
+    "khaki",
+    "lavender",
+    "lavenderblush",
+    "lawngreen",
+    "lemonchiffon",
+    "lightblue",
+    "lightcoral",
+    "lightcyan",
+    "lightgoldenrodyellow",
+    "lightgray",
+    "lightgreen",
+    "lightgrey",
+    "lightpink",
+    "lightsalmon",
+    "lightseagreen",
+    "lightskyblue",
+    "lightslategray",
+    "lightslategrey",
+    "lightsteelblue",
+    "lightyellow",
+    "lime",
+    "limegreen",
+    "linen",
+    "magenta",
+    "maroon",
+    "mediumaquamarine",
+    "mediumblue",
+    "mediumorchid",
+    "mediumpurple",
+    "mediumseagreen",
+    "mediumslateblue",
+    "mediumspringgreen",
+    "mediumturquoise",
+    "mediumvioletred",
+    "midnightblue",
+    "mintcream",
+    "mistyrose",
+    "moccasin",
+    "navajowhite",
+    "navy",
+    "oldlace",
+    "olive",
+    "olivedrab",
+    "orange",
+    "orangered",
+    "orchid",
+    "palegoldenrod",
+    "palegreen",
+    "paleturquoise",
+    "palevioletred",
+    "papayawhip",
+    "peachpuff",
+    "peru",
+    "pink",
+    "plum",
+    "powderblue",
+    "purple",
+    "rebeccapurple",
+    "red",
+    "rosybrown",
+    "royalblue",
+    "saddlebrown",
+    "salmon",
+    "sandybrown",
+    "seagreen",
+    "seashell",
+    "sienna",
+    "silver",
+    "skyblue",
+    "slateblue",
+    "slategray",
+    "slategrey",
+    "snow",
+    "springgreen",
+    "steelblue",
+    "tan",
+    "teal",
+    "thistle",
+    "tomato",
+    "transparent",
+    "turquoise",
+    "violet",
+    "wheat",
+    "white",
+    "whitesmoke",
+    "yellow",
+    "yellowgreen",
+];
+
+/// Check whether `value` is a plain CSS color
+///
+/// # Examples
+///
+/// ```
+/// use universal_markdown::extensions::styles::is_valid_color;
+///
+/// assert!(is_valid_color("RebeccaPurple"));
+/// assert!(is_valid_color("oklch(70% 0.1 200 / 50%)"));
+/// assert!(!is_valid_color("red;position:fixed"));
+/// assert!(!is_valid_color("rgb(var(--x))"));
+/// ```
+pub fn is_valid_color(value: &str) -> bool {
+    let lower = value.to_ascii_lowercase();
+    if NAMED_COLORS.binary_search(&lower.as_str()).is_ok() || HEX_COLOR.is_match(value) {
+        return true;
+    }
+
+    let Some(caps) = COLOR_FUNCTION.captures(value) else {
+        return false;
+    };
+    let arguments: Vec<&str> = caps[1]
+        .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+        .filter(|a| !a.is_empty())
+        .collect();
+    (3..=4).contains(&arguments.len()) && arguments.iter().all(|a| COLOR_ARGUMENT.is_match(a))
+}
+
+/// Check whether `value` is a non-negative length with an allowlisted unit
+///
+/// Allowed units are `px`, `pt`, `em`, `rem`, `ex`, `ch`, `%`, `vw`, `vh`,
+/// `vmin` and `vmax`.
+pub fn is_valid_length(value: &str) -> bool {
+    LENGTH.is_match(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_named_colors_are_sorted() {
+        assert!(NAMED_COLORS.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_valid_colors() {
+        for color in [
+            "red",
+            "Transparent",
+            "#abc",
+            "#abcd",
+            "#aabbcc",
+            "#aabbccdd",
+            "rgb(255, 0, 0)",
+            "rgba(255,0,0,.5)",
+            "hsl(120deg 50% 50%)",
+            "oklch(0.7 0.1 200 / 50%)",
+        ] {
+            assert!(is_valid_color(color), "{}", color);
+        }
+    }
+
+    #[test]
+    fn test_invalid_colors() {
+        for color in [
+            "",
+            "#ab",
+            "#ggg",
+            "redd",
+            "red;position:fixed",
+            "red; background:url(//evil)",
+            "rgb(1,2)",
+            "rgb(1,2,3,4,5)",
+            "rgb(calc(1),2,3)",
+            "url(x)",
+            "expression(alert(1))",
+            "var(--x)",
+        ] {
+            assert!(!is_valid_color(color), "{}", color);
+        }
+    }
+
+    #[test]
+    fn test_lengths() {
+        for length in ["1px", "1.5rem", ".8em", "120%", "2VW"] {
+            assert!(is_valid_length(length), "{}", length);
+        }
+        for length in [
+            "1",
+            "-1px",
+            "1px;background:url(//evil)",
+            "1in",
+            "calc(1px)",
+            "px",
+        ] {
+            assert!(!is_valid_length(length), "{}", length);
+        }
+    }
+
+    #[test]
+    fn test_disallow_policy_drops_valid_values() {
+        let (mut classes, mut declarations) = (Vec::new(), Vec::new());
+        let mut styles = Styles::new(StylePolicy::Disallow);
+        styles.apply("font-size", "2rem", &mut classes, &mut declarations);
+        assert!(classes.is_empty() && declarations.is_empty());
+        assert_eq!(styles.diagnostics()[0].code, "inline-style-disallowed");
+    }
+
+    #[test]
+    fn test_class_policy_reuses_classes() {
+        let (mut classes, mut declarations) = (Vec::new(), Vec::new());
+        let mut styles = Styles::new(StylePolicy::Classes);
+        styles.apply("color", "#abc", &mut classes, &mut declarations);
+        styles.apply("color", " #abc ", &mut classes, &mut declarations);
+        styles.apply("background-color", "#abc", &mut classes, &mut declarations);
+
+        assert!(declarations.is_empty());
+        assert_eq!(classes[0], classes[1]);
+        assert_ne!(classes[0], classes[2]);
+        assert_eq!(
+            styles.stylesheet().unwrap(),
+            format!(
+                ".{} {{color: #abc}}\n.{} {{background-color: #abc}}",
+                classes[0], classes[2]
+            )
+        );
+        assert_eq!(Styles::default().stylesheet(), None);
+    }
+
+    #[test]
+    fn test_class_collisions_get_suffixes() {
+        let (mut classes, mut declarations) = (Vec::new(), Vec::new());
+        let mut styles = Styles::new(StylePolicy::Classes);
+        // Another declaration already holds the hash of `color: #abc`
+        let taken = base_class("color: #abc");
+        styles.generated.insert(taken.clone());
+        styles.apply("color", "#abc", &mut classes, &mut declarations);
+        styles.apply("color", "#abc", &mut classes, &mut declarations);
+
+        assert_eq!(classes, vec![format!("{}-2", taken); 2]);
+        assert_eq!(taken.len(), CLASS_PREFIX.len() + 16);
+    }
+}
@@ -0,0 +1,362 @@
+//! CSV and TSV data tables
+//!
+//! Spreadsheet exports can be pasted as a fenced block or a block plugin:
+//!
+//! ````text
+//! ```csv header
+//! Name,Price
+//! "Apple, red",100
+//! ```
+//!
+//! @csv(header,delimiter=;){{
+//! Name;Price
+//! Pear;120
+//! }}
+//! ````
+//!
+//! ```` ```tsv ```` blocks use tabs. Fields follow RFC 4180: quoted fields
+//! may contain delimiters, line breaks and doubled quotes. Field values are
+//! text, not markup. Columns whose values are all numbers are right-aligned.
+//!
+//! Options (after the fence language, or as plugin arguments):
+//!
+//! - `header` - the first record is the `<thead>` row
+//! - `delimiter=;` - field delimiter (`tab` for a tab)
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use super::model::{Cell, Column, Table};
+use super::umd::placeholder;
+use crate::extensions::plugin_syntax::parse_args;
+
+/// Opening line of the plugin: `@csv(args){{`
+static CSV_PLUGIN_OPEN: Lazy<Regex> = Lazy::new(|| Regex::new(r"^@csv\(([^)]*)\)\{\{$").unwrap());
+
+/// Number with optional sign, thousands separators, decimals and percent
+static NUMBER: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^[+-]?((\d{1,3}(,\d{3})+|\d+)(\.\d*)?|\.\d+)%?$").unwrap());
+
+/// Options of a CSV block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvOptions {
+    /// The first record is the header row
+    pub header: bool,
+    /// Field delimiter
+    pub delimiter: char,
+}
+
+impl CsvOptions {
+    /// Parse comma-separated options over a default delimiter
+    ///
+    /// Unknown options are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Options such as `header,delimiter=;`
+    /// * `delimiter` - Delimiter when none is given
+    pub fn parse(args: &str, delimiter: char) -> Self {
+        let mut options = Self {
+            header: false,
+            delimiter,
+        };
+        for arg in parse_args(args) {
+            if arg == "header" {
+                options.header = true;
+            } else if let Some(value) = arg.strip_prefix("delimiter=") {
+                options.delimiter = match value {
+                    "tab" | "\\t" => '\t',
+                    value => value.chars().next().unwrap_or(delimiter),
+                };
+            }
+        }
+        options
+    }
+}
+
+/// Split CSV text into records of fields (RFC 4180)
+///
+/// Quoted fields may contain the delimiter, line breaks and `""` for a
+/// quote. Both `\n` and `\r\n` end a record; empty lines are skipped.
+///
+/// # Arguments
+///
+/// * `text` - CSV text
+/// * `delimiter` - Field delimiter
+///
+/// # Returns
+///
+/// The records, each a list of field values
+///
+/// # Examples
+///
+/// ```
+/// use universal_markdown::extensions::table::csv::parse_records;
+///
+/// let records = parse_records("a,\"b, \"\"c\"\"\"\n1,2", ',');
+/// assert_eq!(records, vec![vec!["a", "b, \"c\""], vec!["1", "2"]]);
+/// ```
+pub fn parse_records(text: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+        } else if c == '"' && field.is_empty() {
+            in_quotes = true;
+        } else if c == delimiter {
+            record.push(std::mem::take(&mut field));
+        } else if c == '\n' || c == '\r' {
+            if c == '\r' && chars.peek() == Some(&'\n') {
+                chars.next();
+            }
+            end_record(&mut records, &mut record, &mut field);
+        } else {
+            field.push(c);
+        }
+    }
+    end_record(&mut records, &mut record, &mut field);
+
+    records
+}
+
+fn end_record(records: &mut Vec<Vec<String>>, record: &mut Vec<String>, field: &mut String) {
+    if record.is_empty() && field.is_empty() {
+        return;
+    }
+    record.push(std::mem::take(field));
+    records.push(std::mem::take(record));
+}
+
+/// Build a table from CSV text
+///
+/// Field values are escaped as text; line breaks inside a field become
+/// `<br />`.
+///
+/// # Arguments
+///
+/// * `text` - CSV text
+/// * `options` - Header and delimiter options
+///
+/// # Returns
+///
+/// The table, with numeric columns right-aligned
+pub fn build_table(text: &str, options: &CsvOptions) -> Table {
+    let mut rows: Vec<Vec<Cell>> = parse_records(text, options.delimiter)
+        .into_iter()
+        .map(|record| {
+            record
+                .iter()
+                .map(|value| Cell::new(escape_field(value), false))
+                .collect()
+        })
+        .collect();
+
+    let head = if options.header && !rows.is_empty() {
+        let mut head = rows.remove(0);
+        for cell in &mut head {
+            cell.is_header = true;
+        }
+        vec![head]
+    } else {
+        Vec::new()
+    };
+
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let columns = (0..width)
+        .map(|index| Column {
+            classes: if is_numeric_column(&rows, index) {
+                vec!["text-end".to_string()]
+            } else {
+                Vec::new()
+            },
+            ..Default::default()
+        })
+        .collect();
+
+    Table {
+        classes: vec!["table".to_string(), "csv-table".to_string()],
+        head,
+        body: rows,
+        columns,
+        ..Default::default()
+    }
+}
+
+fn escape_field(value: &str) -> String {
+    html_escape::encode_text(value.trim())
+        .replace("\r\n", "\n")
+        .replace('\n', "<br />")
+}
+
+/// Whether every non-empty value of a column is a number
+fn is_numeric_column(rows: &[Vec<Cell>], index: usize) -> bool {
+    let mut values = rows
+        .iter()
+        .filter_map(|row| row.get(index))
+        .map(|cell| cell.content.as_str())
+        .filter(|value| !value.is_empty())
+        .peekable();
+    values.peek().is_some() && values.all(|value| NUMBER.is_match(value))
+}
+
+/// Replace CSV/TSV fenced blocks and `@csv(...){{ … }}` calls with table
+/// placeholders
+///
+/// The tables are appended to `tables` and restored like UMD tables (see
+/// [`super::umd::restore_umd_tables`]). Other fenced code blocks are left
+/// untouched.
+///
+/// # Arguments
+///
+/// * `input` - The wiki markup
+/// * `tables` - Side table receiving the parsed tables
+///
+/// # Returns
+///
+/// The markup with the blocks replaced
+pub fn protect_csv_blocks(input: &str, tables: &mut Vec<Table>) -> String {
+    replace_blocks(input, |options, body| {
+        let table = build_table(&body, &options);
+        let token = placeholder(tables.len(), &Table::default());
+        tables.push(table);
+        token
+    })
+}
+
+/// Number of cells of each CSV/TSV block, in document order
+///
+/// Used to apply the table cell limit before the document is rendered.
+pub(crate) fn table_cell_counts(input: &str) -> Vec<usize> {
+    let mut counts = Vec::new();
+    replace_blocks(input, |options, body| {
+        let records = parse_records(&body, options.delimiter);
+        counts.push(records.iter().map(Vec::len).sum());
+        String::new()
+    });
+    counts
+}
+
+/// Replace each CSV/TSV block outside fenced code with the output of
+/// `replace`, which receives the block options and body
+fn replace_blocks(input: &str, mut replace: impl FnMut(CsvOptions, String) -> String) -> String {
+    let lines: Vec<&str> = input.split_inclusive('\n').collect();
+    let mut result = String::with_capacity(input.len());
+    let mut code_fence: Option<&str> = None;
+    let mut i = 0;
+
+    while i < lines.len() {
+        let trimmed = lines[i].trim();
+
+        if let Some(fence) = code_fence {
+            if is_closing_fence(trimmed, fence) {
+                code_fence = None;
+            }
+        } else if let Some((options, body, end)) = csv_block(&lines, i) {
+            result.push_str(&replace(options, body));
+            i = end;
+            continue;
+        } else if trimmed.starts_with("```") {
+            code_fence = Some("```");
+        } else if trimmed.starts_with("~~~") {
+            code_fence = Some("~~~");
+        }
+
+        result.push_str(lines[i]);
+        i += 1;
+    }
+
+    result
+}
+
+/// Match a CSV/TSV fenced block or an `@csv` call starting at `start`
+///
+/// Returns the options, the body and the index of the line after the block.
+fn csv_block(lines: &[&str], start: usize) -> Option<(CsvOptions, String, usize)> {
+    let line = lines[start].trim();
+
+    let (fence, options, end_marker) = if let Some(caps) = CSV_PLUGIN_OPEN.captures(line) {
+        (None, CsvOptions::parse(&caps[1], ','), "}}")
+    } else {
+        let fence = if line.starts_with("```") {
+            "```"
+        } else if line.starts_with("~~~") {
+            "~~~"
+        } else {
+            return None;
+        };
+        let info = line.trim_start_matches(&fence[..1]).trim();
+        let (language, args) = info.split_once(' ').unwrap_or((info, ""));
+        let delimiter = match language.to_ascii_lowercase().as_str() {
+            "csv" => ',',
+            "tsv" => '\t',
+            _ => return None,
+        };
+        (Some(fence), CsvOptions::parse(args, delimiter), fence)
+    };
+
+    let close = (start + 1..lines.len()).find(|&j| {
+        let trimmed = lines[j].trim();
+        match fence {
+            Some(fence) => is_closing_fence(trimmed, fence),
+            None => trimmed == end_marker,
+        }
+    })?;
+    Some((options, lines[start + 1..close].concat(), close + 1))
+}
+
+/// A closing fence has no info string
+fn is_closing_fence(line: &str, fence: &str) -> bool {
+    line.starts_with(fence) && line.chars().all(|c| fence.starts_with(c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rfc4180_quoting() {
+        let records = parse_records("\"a\nb\",\"\"\"q\"\"\",c\r\n,\r\nx", ',');
+        assert_eq!(
+            records,
+            vec![vec!["a\nb", "\"q\"", "c"], vec!["", ""], vec!["x"]]
+        );
+    }
+
+    #[test]
+    fn test_numeric_columns() {
+        let table = build_table(
+            "Item;Price;Share\nA;1,200.50;10%\nB;-3;\nC;n/a;5%",
+            &CsvOptions::parse("header,delimiter=;", ','),
+        );
+        let classes: Vec<_> = table.columns.iter().map(|c| c.classes.clone()).collect();
+        assert_eq!(classes, vec![vec![], vec![], vec!["text-end".to_string()]]);
+        assert_eq!(table.head[0][1].content, "Price");
+        assert!(table.head[0][1].is_header);
+    }
+
+    #[test]
+    fn test_protect_blocks() {
+        let mut tables = Vec::new();
+        let input = "```tsv header\na\tb\n```\n\n```\n```csv\n```\n\n@csv(){{\n<x>,\"y\nz\"\n}}\n";
+        let output = protect_csv_blocks(input, &mut tables);
+        assert_eq!(tables.len(), 2);
+        assert_eq!(tables[0].head[0][1].content, "b");
+        assert_eq!(tables[1].body[0][0].content, "&lt;x&gt;");
+        assert_eq!(tables[1].body[0][1].content, "y<br />z");
+        // Inside another code block the fence is just text
+        assert!(output.contains("```\n```csv\n```\n"));
+        assert!(!output.contains("@csv"));
+    }
+}
@@ -0,0 +1,545 @@
+//! Table data export
+//!
+//! [`extract_tables`] returns the data of every table in a document (GFM,
+//! UMD and CSV tables) as plain-text cells, for use outside the rendered
+//! page. The table models are recorded by a [`TableRecorder`] as the
+//! pipeline renders them, and each cell takes its fully post-processed
+//! content, so cell text is what the reader sees: inline markup is removed
+//! and entities are decoded.
+//!
+//! Spanned cells are expanded into a grid: a cell with `colspan="2"` fills
+//! two positions, the second of which points back at the first through
+//! [`CellData::span_of`]. Classes and style declarations are kept as
+//! metadata, including the defaults a cell takes from its column; custom
+//! values are reported as declarations even under
+//! [`StylePolicy::Classes`](crate::extensions::styles::StylePolicy::Classes).
+//!
+//! The data serializes to JSON with serde and to CSV with
+//! [`TableData::to_csv`].
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+
+use super::model::{Column, Row, Table, inherit};
+use super::plugin::TablePlugin;
+use super::render::render_table;
+use crate::extensions::markers;
+use crate::extensions::styles::StylePolicy;
+use crate::parser::ParserOptions;
+
+/// Token recorded before a rendered table
+static TABLE_TOKEN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(&markers::token_pattern(markers::EXPORTED_TABLE)).unwrap());
+
+/// Line break in cell content
+static LINE_BREAK: Lazy<Regex> = Lazy::new(|| Regex::new(r"<br\s*/?>").unwrap());
+
+/// Any other tag in cell content
+static TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"<[^>]*>").unwrap());
+
+/// Section of a table row
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Section {
+    Head,
+    Body,
+    Foot,
+}
+
+/// A cell of the expanded grid
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CellData {
+    /// Plain text; line breaks are kept as `\n`
+    pub text: String,
+    /// Header cell (`<th>`)
+    pub header: bool,
+    /// Number of columns the cell spans
+    pub colspan: usize,
+    /// Number of rows the cell spans
+    pub rowspan: usize,
+    /// For positions covered by a spanning cell, the `[row, column]` of
+    /// that cell; the position repeats its text and decorations
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span_of: Option<[usize; 2]>,
+    /// Bootstrap classes, e.g. `text-end`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub classes: Vec<String>,
+    /// Inline style declarations, e.g. `color: red`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub styles: Vec<String>,
+}
+
+/// A row of the expanded grid
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RowData {
+    /// Section the row belongs to
+    pub section: Section,
+    /// Cells, one per grid column
+    pub cells: Vec<CellData>,
+}
+
+/// The data of one table
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TableData {
+    /// Caption text
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub caption: Option<String>,
+    /// Classes of the `<table>` element
+    pub classes: Vec<String>,
+    /// Rows in document order: head, body, foot
+    pub rows: Vec<RowData>,
+}
+
+impl TableData {
+    /// Build the expanded grid from a table model
+    ///
+    /// Cell content is HTML and is converted to plain text.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - A rendered table model
+    pub fn from_table(table: &Table) -> Self {
+        let sections = [
+            (Section::Head, &table.head),
+            (Section::Body, &table.body),
+            (Section::Foot, &table.foot),
+        ];
+
+        let mut rows: Vec<RowData> = Vec::new();
+        // Cells still spanning into later rows, by grid column
+        let mut pending: Vec<Option<(usize, CellData)>> = Vec::new();
+        for (section, section_rows) in sections {
+            for row in section_rows {
+                let index = rows.len();
+                rows.push(RowData {
+                    section,
+                    cells: expand_row(row, index, &table.columns, &mut pending),
+                });
+            }
+        }
+
+        Self {
+            caption: table.caption.as_deref().map(plain_text),
+            classes: table.classes.clone(),
+            rows,
+        }
+    }
+
+    /// Number of grid columns (the length of the longest row)
+    pub fn width(&self) -> usize {
+        self.rows
+            .iter()
+            .map(|row| row.cells.len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Serialize the cell text as CSV (RFC 4180)
+    ///
+    /// Every row has [`TableData::width`] fields; short rows are padded
+    /// with empty fields. Records end with `\r\n`.
+    ///
+    /// Fields that a spreadsheet would run as a formula (starting with `=`,
+    /// `+`, `-`, `@`, a tab or a carriage return) get a leading `'`.
+    /// Numbers such as `-5` are written unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use universal_markdown::extract_tables;
+    ///
+    /// let tables = extract_tables("| Name | Note |\n|---|---|\n| A | \"x, y\" |");
+    /// assert_eq!(tables[0].to_csv(), "Name,Note\r\nA,\"\"\"x, y\"\"\"\r\n");
+    ///
+    /// let tables = extract_tables("| a | =1+2 | -5 |");
+    /// assert_eq!(tables[0].to_csv(), "a,'=1+2,-5\r\n");
+    /// ```
+    pub fn to_csv(&self) -> String {
+        let width = self.width();
+        let mut csv = String::new();
+        for row in &self.rows {
+            let fields: Vec<String> = (0..width)
+                .map(|column| {
+                    row.cells
+                        .get(column)
+                        .map_or_else(String::new, |cell| csv_field(&cell.text))
+                })
+                .collect();
+            csv.push_str(&fields.join(","));
+            csv.push_str("\r\n");
+        }
+        csv
+    }
+
+    /// Serialize the table as JSON
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use universal_markdown::extract_tables;
+    ///
+    /// let tables = extract_tables("| a |\n|---|\n| 1 |");
+    /// assert!(tables[0].to_json().contains(r#"{"text":"1","header":false"#));
+    /// ```
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Table data is always serializable")
+    }
+}
+
+/// Place the cells of a row on the grid, filling positions covered by
+/// spans from earlier rows
+fn expand_row(
+    row: &Row,
+    index: usize,
+    columns: &[Column],
+    pending: &mut Vec<Option<(usize, CellData)>>,
+) -> Vec<CellData> {
+    let mut cells = Vec::new();
+    let mut source = row.iter();
+
+    loop {
+        let column = cells.len();
+        if let Some(Some((rows, cell))) = pending.get_mut(column) {
+            cells.push(cell.clone());
+            *rows -= 1;
+            if *rows == 0 {
+                pending[column] = None;
+            }
+            continue;
+        }
+
+        let Some(cell) = source.next() else {
+            break;
+        };
+        let mut classes = cell.classes.clone();
+        let mut styles = cell.styles.clone();
+        if let Some(column) = columns.get(column) {
+            inherit(&mut classes, &mut styles, column);
+        }
+        let data = CellData {
+            text: plain_text(&cell.content),
+            header: cell.is_header,
+            colspan: cell.colspan.max(1),
+            rowspan: cell.rowspan.max(1),
+            span_of: None,
+            classes,
+            styles,
+        };
+        let covered = CellData {
+            span_of: Some([index, column]),
+            ..data.clone()
+        };
+
+        let end = column + data.colspan;
+        if pending.len() < end {
+            pending.resize(end, None);
+        }
+        if data.rowspan > 1 {
+            for slot in &mut pending[column..end] {
+                *slot = Some((data.rowspan - 1, covered.clone()));
+            }
+        }
+        cells.push(data);
+        cells.extend(std::iter::repeat_n(covered, end - column - 1));
+    }
+
+    cells
+}
+
+/// Extract the data of every table in a document
+///
+/// # Arguments
+///
+/// * `input` - The Universal Markdown source text
+///
+/// # Returns
+///
+/// The tables in document order
+///
+/// # Examples
+///
+/// ```
+/// use universal_markdown::extract_tables;
+///
+/// let tables = extract_tables("|~Name|~Total|\n|Apples|RIGHT: *1,200*|\n|^|RIGHT: 80|");
+/// let rows = &tables[0].rows;
+/// assert!(rows[0].cells[0].header);
+/// assert_eq!(rows[1].cells[1].text, "1,200");
+/// assert_eq!(rows[1].cells[1].classes, vec!["text-end"]);
+/// // The spanning cell fills the position below it
+/// assert_eq!(rows[2].cells[0].text, "Apples");
+/// assert_eq!(rows[2].cells[0].span_of, Some([1, 0]));
+/// ```
+pub fn extract_tables(input: &str) -> Vec<TableData> {
+    extract_tables_with_options(input, &ParserOptions::default())
+}
+
+/// Extract the data of every table, rendering with custom parser options
+///
+/// # Arguments
+///
+/// * `input` - The Universal Markdown source text
+/// * `options` - Parser configuration options
+///
+/// # Returns
+///
+/// The tables in document order
+pub fn extract_tables_with_options(input: &str, options: &ParserOptions) -> Vec<TableData> {
+    if options.limits.check_input(input).is_err() {
+        return Vec::new();
+    }
+
+    // Generated classes would hide the declarations they stand for
+    let mut options = options.clone();
+    if options.style_policy == StylePolicy::Classes {
+        options.style_policy = StylePolicy::Inline;
+    }
+
+    let mut recorder = TableRecorder::new();
+    crate::render(input, &options, Some(&mut recorder));
+    recorder
+        .into_tables()
+        .iter()
+        .map(TableData::from_table)
+        .collect()
+}
+
+/// Tables recorded while a document is rendered
+///
+/// [`TableRecorder::render`] renders a table with a token in front of it
+/// and a pair of tokens around the content of each cell. The rest of the
+/// pipeline then runs as usual, and [`TableRecorder::finish`] takes each
+/// cell's post-processed content back from between its tokens, before
+/// leftover tokens are removed. Tables nested in a cell are recorded on
+/// their own.
+#[derive(Debug, Default)]
+pub struct TableRecorder {
+    /// Recorded tables, in order of rendering
+    tables: Vec<Table>,
+    /// Token index of the first cell of each table
+    first_cells: Vec<usize>,
+    /// Number of cell tokens issued
+    cells: usize,
+    /// Indices of `tables` in document order, set by `finish`
+    order: Vec<usize>,
+}
+
+impl TableRecorder {
+    /// Create an empty recorder
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a table and render it with its tokens
+    pub(crate) fn render(&mut self, table: &Table) -> String {
+        let index = self.tables.len();
+        self.tables.push(table.clone());
+        self.first_cells.push(self.cells);
+
+        let mut marked = table.clone();
+        for cell in marked.rows_mut().flatten() {
+            cell.content = format!(
+                "{}{}{}",
+                markers::token(markers::EXPORTED_CELL, self.cells),
+                cell.content,
+                markers::token(markers::EXPORTED_CELL_END, self.cells)
+            );
+            self.cells += 1;
+        }
+        format!(
+            "{}{}",
+            markers::token(markers::EXPORTED_TABLE, index),
+            render_table(&marked)
+        )
+    }
+
+    /// Apply the options of an `@table(...)` call to the first table
+    /// recorded in `html`
+    pub(crate) fn decorate(&mut self, html: &str, plugin: &TablePlugin) {
+        let table = TABLE_TOKEN
+            .captures(html)
+            .and_then(|caps| caps[1].parse::<usize>().ok())
+            .and_then(|index| self.tables.get_mut(index));
+        if let Some(table) = table {
+            table.classes.extend(plugin.classes.iter().cloned());
+            if let Some(caption) = plugin.caption_html() {
+                table.caption = Some(caption);
+            }
+        }
+    }
+
+    /// Take the post-processed cell content back from `html`
+    ///
+    /// # Arguments
+    ///
+    /// * `html` - The document HTML, with its tokens still in place
+    pub(crate) fn finish(&mut self, html: &str) {
+        self.order = TABLE_TOKEN
+            .captures_iter(html)
+            .filter_map(|caps| caps[1].parse::<usize>().ok())
+            .filter(|&index| index < self.tables.len())
+            .collect();
+
+        for (table, &first) in self.tables.iter_mut().zip(&self.first_cells) {
+            for (offset, cell) in table.rows_mut().flatten().enumerate() {
+                let start = markers::token(markers::EXPORTED_CELL, first + offset);
+                let end = markers::token(markers::EXPORTED_CELL_END, first + offset);
+                if let Some(content) = html
+                    .split_once(&start)
+                    .and_then(|(_, rest)| rest.split_once(&end))
+                    .map(|(content, _)| content)
+                {
+                    cell.content = content.to_string();
+                }
+            }
+        }
+    }
+
+    /// The recorded tables in document order
+    pub fn into_tables(mut self) -> Vec<Table> {
+        let order = std::mem::take(&mut self.order);
+        let mut tables: Vec<Option<Table>> = self.tables.into_iter().map(Some).collect();
+        order
+            .into_iter()
+            .filter_map(|index| tables[index].take())
+            .collect()
+    }
+}
+
+/// Convert cell HTML to plain text
+///
+/// Tokens of nested tables are removed along with the tags.
+fn plain_text(html: &str) -> String {
+    let text = LINE_BREAK.replace_all(html, "\n");
+    let text = TAG.replace_all(&text, "");
+    let text = markers::restore_leftovers(&text, |_, _| None);
+    decode(text.trim())
+}
+
+fn decode(text: &str) -> String {
+    html_escape::decode_html_entities(text).into_owned()
+}
+
+/// Quote a CSV field when it contains a delimiter, quote or line break
+///
+/// Fields a spreadsheet would evaluate as a formula are prefixed with `'`.
+fn csv_field(text: &str) -> String {
+    let text = if text.starts_with(['=', '+', '-', '@', '\t', '\r'])
+        && text.trim().parse::<f64>().is_err()
+    {
+        format!("'{}", text)
+    } else {
+        text.to_string()
+    };
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extensions::table::model::Cell;
+
+    #[test]
+    fn test_span_expansion() {
+        let tables = extract_tables("| A | B |\n|^| C |\n|> | D |");
+        let text: Vec<Vec<&str>> = tables[0]
+            .rows
+            .iter()
+            .map(|row| row.cells.iter().map(|c| c.text.as_str()).collect())
+            .collect();
+        assert_eq!(text[0][..2], ["A", "B"]);
+        assert_eq!(text[1][..2], ["A", "C"]);
+        assert_eq!(tables[0].rows[1].cells[0].span_of, Some([0, 0]));
+        assert_eq!(text[2][..2], ["D", "D"]);
+        assert_eq!(tables[0].rows[2].cells[1].span_of, Some([2, 0]));
+    }
+
+    #[test]
+    fn test_plain_text() {
+        assert_eq!(plain_text("<strong>a</strong> &amp; b<br />c"), "a & b\nc");
+    }
+
+    #[test]
+    fn test_csv_quoting() {
+        let table = TableData::from_table(&Table {
+            body: vec![
+                vec![Cell::new("a\"b".to_string(), false)],
+                vec![
+                    Cell::new("x<br />y".to_string(), false),
+                    Cell::new("1,2".to_string(), false),
+                ],
+            ],
+            ..Default::default()
+        });
+        assert_eq!(table.to_csv(), "\"a\"\"b\",\r\n\"x\ny\",\"1,2\"\r\n");
+    }
+
+    #[test]
+    fn test_csv_formulas_neutralized() {
+        let tables = extract_tables("```csv\na,\"=cmd\"\n+1,-2.5\n@x,\"-a,b\"\n```");
+        assert_eq!(
+            tables[0].to_csv(),
+            "a,'=cmd\r\n+1,-2.5\r\n'@x,\"'-a,b\"\r\n"
+        );
+    }
+
+    #[test]
+    fn test_recorded_options_and_declarations() {
+        let options = ParserOptions {
+            style_policy: StylePolicy::Classes,
+            ..Default::default()
+        };
+        let tables = extract_tables_with_options(
+            "@table(striped,caption=\"A, B\"){{\n|COLOR(#abc):x|&color(red){y};|\n}}",
+            &options,
+        );
+        assert_eq!(tables[0].caption.as_deref(), Some("A, B"));
+        assert_eq!(tables[0].classes, ["table", "umd-table", "table-striped"]);
+        assert_eq!(tables[0].rows[0].cells[0].styles, ["color: #abc"]);
+        assert_eq!(tables[0].rows[0].cells[1].text, "y");
+    }
+
+    #[test]
+    fn test_nested_tables() {
+        let mut recorder = TableRecorder::new();
+        let inner = Table {
+            body: vec![vec![Cell::new("in".to_string(), false)]],
+            ..Default::default()
+        };
+        let inner_html = recorder.render(&inner);
+        let outer = Table {
+            body: vec![vec![
+                Cell::new("out".to_string(), false),
+                Cell::new(inner_html, false),
+            ]],
+            ..Default::default()
+        };
+        let html = recorder.render(&outer);
+        recorder.finish(&html);
+
+        let tables: Vec<TableData> = recorder
+            .into_tables()
+            .iter()
+            .map(TableData::from_table)
+            .collect();
+        assert_eq!(tables.len(), 2);
+        assert_eq!(tables[0].rows[0].cells[0].text, "out");
+        assert_eq!(tables[0].rows[0].cells[1].text, "in");
+        assert_eq!(tables[1].rows[0].cells[0].text, "in");
+    }
+
+    #[test]
+    fn test_sections_and_json() {
+        let tables = extract_tables("```csv header\nName,Price\nPear,120\n```");
+        let json = tables[0].to_json();
+        assert!(json.starts_with(r#"{"classes":["table","csv-table"],"rows":[{"section":"head""#));
+        assert!(json.contains(
+            r#"{"text":"120","header":false,"colspan":1,"rowspan":1,"classes":["text-end"]}"#
+        ));
+    }
+}
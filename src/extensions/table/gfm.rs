@@ -0,0 +1,266 @@
+//! GFM tables
+//!
+//! comrak parses GFM tables; this module converts each `Table` node of the
+//! AST to the shared [`Table`] model, applies the UMD cell decorations
+//! (`TOP:`, `~`, etc.) to the rendered cell content, resolves the `>` and
+//! `^` spanning markers as in UMD tables, and replaces the node with the
+//! output of the shared renderer.
+//!
+//! UMD tables reach comrak as single-column placeholder tables (see
+//! [`super::umd::extract_umd_tables`]); their cells are rendered and wrapped
+//! in tokens for [`super::umd::restore_umd_tables`] instead.
+
+use comrak::nodes::{AstNode, NodeValue, Sourcepos, TableAlignment};
+use comrak::options::Plugins;
+use comrak::{Arena, Options, format_html_with_plugins};
+
+use super::export::TableRecorder;
+use super::model::{Cell, Column, Table};
+use super::render::render_table;
+use super::umd::{emit_styles, normalize_section, parse_cell_content};
+use crate::extensions::markers;
+use crate::extensions::styles::Styles;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// Regex matching the header of a UMD table placeholder
+static PLACEHOLDER_HEADER: Lazy<Regex> =
+    Lazy::new(|| Regex::new(&format!("^{}$", markers::token_pattern(markers::TABLE))).unwrap());
+
+/// Replace every GFM table in the document with rendered HTML
+///
+/// # Arguments
+///
+/// * `arena` - The arena the document was parsed into
+/// * `root` - The document node
+/// * `input` - The text `root` was parsed from, used to find marker cells
+/// * `options` - comrak options, used to render cell content
+/// * `id_prefix` - Prefix of the header cell ids of the document's tables
+/// * `styles` - Validator for custom cell `COLOR()`/`SIZE()` values
+/// * `recorder` - Recorder of the rendered tables, if they are exported
+pub fn replace_tables<'a>(
+    arena: &'a Arena<'a>,
+    root: &'a AstNode<'a>,
+    input: &str,
+    options: &Options,
+    id_prefix: &str,
+    styles: &mut Styles,
+    mut recorder: Option<&mut TableRecorder>,
+) {
+    let tables: Vec<_> = root
+        .descendants()
+        .filter(|node| matches!(node.data().value, NodeValue::Table(_)))
+        .collect();
+    if tables.is_empty() {
+        return;
+    }
+    let source = SourceLines::new(input);
+
+    for (index, node) in tables.into_iter().enumerate() {
+        let html = match render_placeholder(node, options) {
+            Some(html) => html,
+            None => {
+                let mut table = table_from_node(node, &source, options, styles);
+                table.id_prefix = Some(format!("{}gfm-table-{}", id_prefix, index));
+                match recorder.as_deref_mut() {
+                    Some(recorder) => recorder.render(&table),
+                    None => render_table(&table),
+                }
+            }
+        };
+        let raw = arena.alloc(NodeValue::Raw(html).into());
+        node.insert_before(raw);
+        node.detach();
+    }
+}
+
+/// Build the table model from a comrak `Table` node
+fn table_from_node<'a>(
+    node: &'a AstNode<'a>,
+    source: &SourceLines,
+    options: &Options,
+    styles: &mut Styles,
+) -> Table {
+    let mut table = Table {
+        classes: vec!["table".to_string()],
+        ..Default::default()
+    };
+
+    if let NodeValue::Table(ref nt) = node.data().value {
+        table.columns = nt
+            .alignments
+            .iter()
+            .map(|alignment| Column {
+                classes: match alignment {
+                    TableAlignment::Left => vec!["text-start".to_string()],
+                    TableAlignment::Center => vec!["text-center".to_string()],
+                    TableAlignment::Right => vec!["text-end".to_string()],
+                    TableAlignment::None => Vec::new(),
+                },
+                ..Default::default()
+            })
+            .collect();
+    }
+
+    for row_node in node.children() {
+        let NodeValue::TableRow(is_header) = row_node.data().value else {
+            continue;
+        };
+
+        let row = row_node
+            .children()
+            .map(|cell_node| {
+                // A cell that is exactly `>` or `^` in the source is a
+                // spanning marker (the sanitizer has already turned `>`
+                // into `&gt;`). Escaped markers are content, kept apart
+                // from markers by a character reference
+                let content = match source.text(cell_node.data().sourcepos).trim() {
+                    ">" | "&gt;" => ">".to_string(),
+                    "^" => "^".to_string(),
+                    _ => match render_children(cell_node, options) {
+                        html if html == "^" => "&#94;".to_string(),
+                        html => html,
+                    },
+                };
+                let mut cell = Cell::new(content, is_header);
+                parse_cell_content(&mut cell, styles);
+                cell
+            })
+            .collect();
+
+        if is_header {
+            table.head.push(row);
+        } else {
+            table.body.push(row);
+        }
+    }
+
+    let width = table.columns.len();
+    let mut diagnostics = Vec::new();
+    let mut first_row = 0;
+    for rows in [&mut table.head, &mut table.body] {
+        let count = rows.len();
+        *rows = normalize_section(std::mem::take(rows), width, first_row, &mut diagnostics);
+        first_row += count;
+    }
+    for diagnostic in diagnostics {
+        styles.report(diagnostic);
+    }
+
+    emit_styles(&mut table, styles);
+    table
+}
+
+/// Source text addressed by comrak source positions
+struct SourceLines<'a> {
+    input: &'a str,
+    /// Byte offset of the start of each line
+    starts: Vec<usize>,
+}
+
+impl<'a> SourceLines<'a> {
+    fn new(input: &'a str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(input.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { input, starts }
+    }
+
+    /// Text of a single-line source range; columns are 1-based byte
+    /// offsets and the end is inclusive
+    fn text(&self, position: Sourcepos) -> &'a str {
+        if position.start.line != position.end.line {
+            return "";
+        }
+        let Some(&line) = self.starts.get(position.start.line.wrapping_sub(1)) else {
+            return "";
+        };
+        self.input
+            .get(line + position.start.column.saturating_sub(1)..line + position.end.column)
+            .unwrap_or("")
+    }
+}
+
+/// Render the cells of a UMD table placeholder
+///
+/// Returns the table token followed by each rendered cell in a
+/// [`markers::TABLE_CELL`] pair, or `None` for any other table.
+fn render_placeholder<'a>(node: &'a AstNode<'a>, options: &Options) -> Option<String> {
+    let mut rows = node.children().filter_map(|row| row.first_child());
+    let token = render_children(rows.next()?, options);
+    if !PLACEHOLDER_HEADER.is_match(&token) {
+        return None;
+    }
+
+    let mut html = token;
+    for cell in rows {
+        html.push_str(&markers::open(markers::TABLE_CELL));
+        html.push_str(&render_children(cell, options));
+        html.push_str(&markers::close(markers::TABLE_CELL));
+    }
+    Some(html)
+}
+
+/// Render the inline content of a cell
+fn render_children<'a>(node: &'a AstNode<'a>, options: &Options) -> String {
+    let mut html = String::new();
+    for child in node.children() {
+        format_html_with_plugins(child, options, &mut html, &Plugins::default())
+            .expect("Failed to render HTML");
+    }
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::{ParserOptions, parse_to_html};
+
+    fn render(input: &str) -> String {
+        parse_to_html(input, &ParserOptions::default())
+    }
+
+    #[test]
+    fn test_column_alignment_classes() {
+        let html = render("| a | b | c |\n| :-- | :-: | --: |\n| 1 | 2 | 3 |");
+        assert!(html.contains(r#"<th class="text-start" scope="col">a</th>"#));
+        assert!(html.contains(r#"<td class="text-center">2</td>"#));
+        assert!(html.contains(r#"<td class="text-end">3</td>"#));
+        assert!(!html.contains("align="));
+    }
+
+    #[test]
+    fn test_cell_decorations() {
+        let html = render("| BASELINE: Header | x |\n|---|---|\n| ~Row | BOTTOM: *Data* |");
+        assert!(html.contains(r#"<th class="align-baseline" scope="col">Header</th>"#));
+        assert!(html.contains(r#"<th scope="row">Row</th>"#));
+        assert!(html.contains(r#"<td class="align-bottom"><em>Data</em></td>"#));
+    }
+
+    #[test]
+    fn test_spanning_markers_match_umd() {
+        let tbody = |html: &str| {
+            let start = html.find("<tbody>").unwrap();
+            html[start..html.find("</tbody>").unwrap()].to_string()
+        };
+        let gfm = crate::parse("| a | b | c |\n|---|---|---|\n| > | x | y |\n| ^ | ^ | z |");
+        let umd = crate::parse("|a|b|c|h\n|>|x|y|\n|^|^|z|");
+
+        assert_eq!(
+            tbody(&gfm),
+            r#"<tbody><tr><td colspan="2" rowspan="2">x</td><td>y</td></tr><tr><td>z</td></tr>"#
+        );
+        assert_eq!(tbody(&gfm), tbody(&umd));
+    }
+
+    #[test]
+    fn test_escaped_markers_are_content() {
+        let html = crate::parse("| a | b |\n|---|---|\n| \\^ | x |");
+        assert!(html.contains("<tr><td>&#94;</td><td>x</td></tr>"));
+    }
+
+    #[test]
+    fn test_inline_content() {
+        let html = render("| a |\n|---|\n| [link](/x) `code` |");
+        assert!(html.contains(r#"<td><a href="/x">link</a> <code>code</code></td>"#));
+    }
+}
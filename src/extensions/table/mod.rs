@@ -2,6 +2,17 @@
 //!
 //! This module provides extended table functionality:
 //! - UMD-style tables with cell spanning and decorations
-//! - GFM tables are handled by comrak (no extension needed)
+//! - GFM tables, parsed by comrak and converted from its AST
+//! - The `@table(...)` block plugin for Bootstrap table variants
+//! - CSV/TSV data blocks
+//!
+//! Both are converted to the same [`model::Table`] and rendered by
+//! [`render::render_table`].
 
+pub mod csv;
+pub mod export;
+pub mod gfm;
+pub mod model;
+pub mod plugin;
+pub mod render;
 pub mod umd;
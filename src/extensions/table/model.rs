@@ -0,0 +1,146 @@
+//! Table model shared by GFM and UMD tables
+//!
+//! Both syntaxes are parsed into a [`Table`] and rendered by
+//! [`super::render::render_table`], so cell decorations, spanning,
+//! alignment and Bootstrap classes behave the same in both.
+
+/// Cell information
+#[derive(Debug, Clone)]
+pub struct Cell {
+    /// Cell content (HTML once the table is built)
+    pub content: String,
+    pub is_header: bool,
+    pub colspan: usize,
+    pub rowspan: usize,
+    pub classes: Vec<String>,
+    pub styles: Vec<String>,
+    /// Source of a computed cell, emitted as `data-formula`
+    /// (see [`super::umd::evaluate_formulas`])
+    pub formula: Option<String>,
+}
+
+impl Cell {
+    pub fn new(content: String, is_header: bool) -> Self {
+        Self {
+            content,
+            is_header,
+            colspan: 1,
+            rowspan: 1,
+            classes: Vec::new(),
+            styles: Vec::new(),
+            formula: None,
+        }
+    }
+}
+
+/// A table row
+pub type Row = Vec<Cell>;
+
+/// Defaults for the cells of a column
+///
+/// Cells keep their own decorations; a column class or style only applies
+/// to cells without one of the same kind (see [`decoration_kind`]).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Column {
+    pub classes: Vec<String>,
+    pub styles: Vec<String>,
+}
+
+/// A parsed table
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    /// Classes of the `<table>` element
+    pub classes: Vec<String>,
+    /// Caption HTML
+    pub caption: Option<String>,
+    /// Rows of `<thead>`
+    pub head: Vec<Row>,
+    /// Rows of `<tbody>`
+    pub body: Vec<Row>,
+    /// Rows of `<tfoot>`
+    pub foot: Vec<Row>,
+    /// Column defaults, indexed by grid column
+    pub columns: Vec<Column>,
+    /// Prefix of the header cell ids; tables whose headers need explicit
+    /// `id`/`headers` associations only get them when it is set
+    pub id_prefix: Option<String>,
+}
+
+impl Table {
+    /// All rows in document order: head, body, foot
+    pub fn rows(&self) -> impl Iterator<Item = &Row> {
+        self.head.iter().chain(&self.body).chain(&self.foot)
+    }
+
+    /// All rows in document order, mutably
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut Row> {
+        self.head
+            .iter_mut()
+            .chain(&mut self.body)
+            .chain(&mut self.foot)
+    }
+}
+
+/// Kind of decoration a class or style declaration sets
+///
+/// Decorations of the same kind override each other, e.g. `text-center`
+/// and `text-end` are both text alignment while `text-danger` is a color.
+///
+/// # Examples
+///
+/// ```
+/// use universal_markdown::extensions::table::model::decoration_kind;
+///
+/// assert_eq!(decoration_kind("text-center"), decoration_kind("text-end"));
+/// assert_ne!(decoration_kind("text-center"), decoration_kind("text-danger"));
+/// assert_eq!(decoration_kind("color: red"), decoration_kind("text-danger"));
+/// ```
+pub fn decoration_kind(decoration: &str) -> &str {
+    if let Some((property, _)) = decoration.split_once(':') {
+        return match property.trim() {
+            "color" => "color",
+            "background-color" => "background",
+            "font-size" => "size",
+            "text-align" => "align",
+            "vertical-align" => "vertical-align",
+            property => property,
+        };
+    }
+    match decoration {
+        "text-start" | "text-center" | "text-end" | "text-justify" => "align",
+        _ if decoration.starts_with("align-") => "vertical-align",
+        _ if decoration.starts_with("text-") => "color",
+        _ if decoration.starts_with("bg-") => "background",
+        _ if decoration.starts_with("fs-") => "size",
+        _ => decoration,
+    }
+}
+
+/// Add the column defaults whose kind the cell does not set itself
+///
+/// A default is skipped when any class or style of the cell has the same
+/// [`decoration_kind`], so a cell's `text-danger` also overrides a
+/// column's `color: #abc`.
+///
+/// # Arguments
+///
+/// * `classes` - Classes of the cell
+/// * `styles` - Style declarations of the cell
+/// * `column` - Defaults of the column
+pub(crate) fn inherit(classes: &mut Vec<String>, styles: &mut Vec<String>, column: &Column) {
+    let sets = |decoration: &String| {
+        classes
+            .iter()
+            .chain(styles.iter())
+            .any(|own| decoration_kind(own) == decoration_kind(decoration))
+    };
+    let missing_classes: Vec<String> = column
+        .classes
+        .iter()
+        .filter(|d| !sets(d))
+        .cloned()
+        .collect();
+    let missing_styles: Vec<String> = column.styles.iter().filter(|d| !sets(d)).cloned().collect();
+    classes.extend(missing_classes);
+    styles.extend(missing_styles);
+}
@@ -0,0 +1,422 @@
+//! `@table(...)` block plugin
+//!
+//! Wraps a GFM or UMD table and adds Bootstrap table variants:
+//!
+//! ```text
+//! CENTER:
+//! @table(striped,hover,responsive,caption=Sales){{
+//! | Month | Total |
+//! | Jan   | 100   |
+//! }}
+//! ```
+//!
+//! - `striped`, `hover`, `dark`, `bordered`, `borderless`, `sm` add the
+//!   matching `table-*` class
+//! - `responsive` wraps the table in `<div class="table-responsive">`
+//! - `caption=text` adds a `<caption>`; quote the text to use commas or
+//!   parentheses (`caption="Sales, 2024"`)
+//! - A `LEFT:`/`CENTER:`/`RIGHT:`/`JUSTIFY:` line before the plugin (or
+//!   before a bare table) places it like other block content
+//! - A `Table: text` line directly before or after a table (or before the
+//!   plugin) is its caption
+//!
+//! The body stays in the document, between two tokens, so the table is
+//! parsed like any other table; the options are applied to the rendered
+//! `<table>` in post-processing.
+
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+
+use super::export::TableRecorder;
+use crate::extensions::block_decorations::placement_class;
+use crate::extensions::markers;
+
+/// Bootstrap table variants accepted as options
+const VARIANTS: [&str; 6] = ["striped", "hover", "dark", "bordered", "borderless", "sm"];
+
+/// Opening line of the plugin: `@table(args){{`
+static TABLE_PLUGIN_OPEN: Lazy<Regex> = Lazy::new(|| Regex::new(r"^@table\((.*)\)\{\{$").unwrap());
+
+/// Placement line on its own: `CENTER:`
+static PLACEMENT_LINE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(LEFT|CENTER|RIGHT|JUSTIFY):$").unwrap());
+
+/// Caption line: `Table: text`
+static CAPTION_LINE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^Table:\s+(.+)$").unwrap());
+
+/// Rendered wrapper: opening token, content, closing token
+static RENDERED_WRAPPER: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(&format!(
+        r"(?s)<p>{}</p>\s*(.*?)\s*<p>{}</p>",
+        markers::token_pattern(markers::TABLE_PLUGIN),
+        markers::close(markers::TABLE_PLUGIN)
+    ))
+    .unwrap()
+});
+
+/// Opening tag of a rendered table
+static TABLE_TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r#"<table class="([^"]*)">"#).unwrap());
+
+/// Options of an `@table(...)` call
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TablePlugin {
+    /// Classes added to the `<table>` element, e.g. `table-striped`
+    pub classes: Vec<String>,
+    /// Wrap the table in `<div class="table-responsive">`
+    pub responsive: bool,
+    /// Caption text, as written in the source
+    pub caption: Option<String>,
+    /// Placement: `LEFT`, `CENTER`, `RIGHT` or `JUSTIFY`
+    pub placement: Option<String>,
+}
+
+impl TablePlugin {
+    /// Parse the options of an `@table(...)` call
+    ///
+    /// Unknown options are ignored. A double-quoted value may contain
+    /// commas.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Comma-separated options
+    /// * `placement` - Placement from a preceding `LEFT:`-style line
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use universal_markdown::extensions::table::plugin::TablePlugin;
+    ///
+    /// let plugin = TablePlugin::parse("striped,responsive,caption=Sales", None);
+    /// assert_eq!(plugin.classes, vec!["table-striped"]);
+    /// assert!(plugin.responsive);
+    /// assert_eq!(plugin.caption.as_deref(), Some("Sales"));
+    ///
+    /// let plugin = TablePlugin::parse(r#"caption="Sales, 2024",sm"#, None);
+    /// assert_eq!(plugin.caption.as_deref(), Some("Sales, 2024"));
+    /// ```
+    pub fn parse(args: &str, placement: Option<&str>) -> Self {
+        let mut plugin = Self {
+            placement: placement.map(str::to_string),
+            ..Default::default()
+        };
+
+        for arg in split_options(args) {
+            if let Some(caption) = arg.strip_prefix("caption=") {
+                let caption = caption.trim();
+                let caption = caption
+                    .strip_prefix('"')
+                    .and_then(|c| c.strip_suffix('"'))
+                    .unwrap_or(caption);
+                plugin.caption = Some(caption.to_string());
+            } else if arg == "responsive" {
+                plugin.responsive = true;
+            } else if VARIANTS.contains(&arg.as_str()) {
+                plugin.classes.push(format!("table-{}", arg));
+            }
+        }
+        plugin
+    }
+
+    /// Caption HTML
+    pub(crate) fn caption_html(&self) -> Option<String> {
+        // Entities in the source are decoded first so they are not escaped
+        // twice
+        self.caption.as_deref().map(|caption| {
+            html_escape::encode_text(&html_escape::decode_html_entities(caption)).into_owned()
+        })
+    }
+
+    /// Apply the options to rendered table HTML
+    fn apply(&self, html: &str) -> String {
+        let mut result = TABLE_TAG
+            .replacen(html, 1, |caps: &Captures| {
+                let mut classes = vec![caps[1].to_string()];
+                classes.extend(self.classes.iter().cloned());
+                let mut tag = format!(r#"<table class="{}">"#, classes.join(" "));
+                if let Some(caption) = self.caption_html() {
+                    tag.push_str(&format!("<caption>{}</caption>", caption));
+                }
+                tag
+            })
+            .into_owned();
+
+        if self.responsive {
+            result = format!(r#"<div class="table-responsive">{}</div>"#, result);
+        }
+        if let Some(placement) = &self.placement {
+            result = format!(
+                r#"<div class="{}">{}</div>"#,
+                placement_class(placement),
+                result
+            );
+        }
+        result
+    }
+}
+
+/// Split options at commas outside double quotes
+fn split_options(args: &str) -> Vec<String> {
+    let mut options = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in args.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            ',' if !quoted => options.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    options.push(current);
+    options
+        .into_iter()
+        .map(|option| option.trim().to_string())
+        .filter(|option| !option.is_empty())
+        .collect()
+}
+
+/// Replace `@table(...){{ … }}` calls and placed tables with tokens
+///
+/// The body is kept in the text between an opening token (indexing
+/// `plugins`) and a closing token. Lines inside fenced code blocks are
+/// left untouched.
+///
+/// # Arguments
+///
+/// * `input` - The wiki markup
+/// * `plugins` - Side table receiving the options of each call
+///
+/// # Returns
+///
+/// The markup with the calls replaced
+pub fn protect_table_plugins(input: &str, plugins: &mut Vec<TablePlugin>) -> String {
+    let lines: Vec<&str> = input.split_inclusive('\n').collect();
+    let mut result = String::with_capacity(input.len());
+    let mut in_code_block = false;
+    let mut code_fence_marker = "";
+    let mut i = 0;
+
+    while i < lines.len() {
+        let trimmed = lines[i].trim();
+
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            if !in_code_block {
+                in_code_block = true;
+                code_fence_marker = if trimmed.starts_with("```") {
+                    "```"
+                } else {
+                    "~~~"
+                };
+            } else if trimmed.contains(code_fence_marker) {
+                in_code_block = false;
+            }
+        }
+
+        if !in_code_block && let Some((plugin, content, end)) = table_block(&lines, i) {
+            result.push_str(&format!(
+                "\n\n{}\n\n{}\n\n{}\n\n",
+                markers::token(markers::TABLE_PLUGIN, plugins.len()),
+                content,
+                markers::close(markers::TABLE_PLUGIN)
+            ));
+            plugins.push(plugin);
+            i = end;
+            continue;
+        }
+
+        result.push_str(lines[i]);
+        i += 1;
+    }
+
+    result
+}
+
+/// Match an `@table` call, or a table with a placement or caption line,
+/// starting at `start`
+///
+/// Returns the options, the body and the index of the line after the block.
+fn table_block(lines: &[&str], start: usize) -> Option<(TablePlugin, String, usize)> {
+    let mut first = start;
+    let placement = PLACEMENT_LINE
+        .captures(lines[first].trim())
+        .map(|caps| caps[1].to_string());
+    if placement.is_some() {
+        first += 1;
+    }
+    let mut caption = lines
+        .get(first)
+        .and_then(|line| CAPTION_LINE.captures(line.trim()))
+        .map(|caps| caps[1].trim().to_string());
+    if caption.is_some() {
+        first += 1;
+    }
+    let line = lines.get(first)?.trim();
+
+    if let Some(caps) = TABLE_PLUGIN_OPEN.captures(line) {
+        let close = closing_line(lines, first + 1)?;
+        let mut plugin = TablePlugin::parse(&caps[1], placement.as_deref());
+        plugin.caption = plugin.caption.or(caption);
+        let content = lines[first + 1..close].concat();
+        return Some((plugin, content.trim_end().to_string(), close + 1));
+    }
+
+    // Only the first line of a bare table starts a block
+    if !line.starts_with('|') || start > 0 && lines[start - 1].trim().starts_with('|') {
+        return None;
+    }
+    let table_end = (first..lines.len())
+        .find(|&j| !lines[j].trim().starts_with('|'))
+        .unwrap_or(lines.len());
+    let mut end = table_end;
+    if caption.is_none()
+        && let Some(caps) = lines.get(end).and_then(|l| CAPTION_LINE.captures(l.trim()))
+    {
+        caption = Some(caps[1].trim().to_string());
+        end += 1;
+    }
+    if placement.is_none() && caption.is_none() {
+        return None;
+    }
+
+    let plugin = TablePlugin {
+        placement,
+        caption,
+        ..Default::default()
+    };
+    let content = lines[first..table_end].concat();
+    Some((plugin, content.trim_end().to_string(), end))
+}
+
+/// Index of the `}}` line closing a block opened before `start`
+///
+/// Block plugins opened inside the body (`@csv(...){{`) are skipped.
+fn closing_line(lines: &[&str], start: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (j, line) in lines.iter().enumerate().skip(start) {
+        let line = line.trim();
+        if line == "}}" {
+            if depth == 0 {
+                return Some(j);
+            }
+            depth -= 1;
+        } else if line.starts_with('@') && line.ends_with("{{") {
+            depth += 1;
+        }
+    }
+    None
+}
+
+/// Apply the options of each `@table` call to the table it wraps
+///
+/// # Arguments
+///
+/// * `html` - HTML with the tables already restored
+/// * `plugins` - Options collected by [`protect_table_plugins`]
+/// * `recorder` - Recorder of the rendered tables, which receives the
+///   options too
+///
+/// # Returns
+///
+/// HTML with the wrapped tables decorated
+pub fn restore_table_plugins(
+    html: &str,
+    plugins: &[TablePlugin],
+    mut recorder: Option<&mut TableRecorder>,
+) -> String {
+    RENDERED_WRAPPER
+        .replace_all(html, |caps: &Captures| {
+            match caps[1].parse::<usize>().ok().and_then(|i| plugins.get(i)) {
+                Some(plugin) => {
+                    if let Some(recorder) = recorder.as_deref_mut() {
+                        recorder.decorate(&caps[2], plugin);
+                    }
+                    plugin.apply(&caps[2])
+                }
+                None => caps[2].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protect_keeps_body() {
+        let mut plugins = Vec::new();
+        let input = "Intro\n@table(striped,sm){{\n| a |\n}}\nEnd\n";
+        let output = protect_table_plugins(input, &mut plugins);
+        assert_eq!(plugins.len(), 1);
+        assert_eq!(plugins[0].classes, vec!["table-striped", "table-sm"]);
+        assert!(output.starts_with("Intro\n"));
+        assert!(output.contains("\n\n| a |\n\n"));
+        assert!(output.ends_with("\n\nEnd\n"));
+        assert!(!output.contains("@table"));
+    }
+
+    #[test]
+    fn test_placement_before_bare_table() {
+        let mut plugins = Vec::new();
+        let output = protect_table_plugins("RIGHT:\n| a |\n| b |\ntext", &mut plugins);
+        assert_eq!(plugins[0].placement.as_deref(), Some("RIGHT"));
+        assert!(output.contains("\n\n| a |\n| b |\n\n"));
+        assert!(output.ends_with("\n\ntext"));
+    }
+
+    #[test]
+    fn test_nested_block_plugin() {
+        let mut plugins = Vec::new();
+        let input = "@table(sm){{\n@csv(){{\na,b\n}}\n}}\nafter";
+        let output = protect_table_plugins(input, &mut plugins);
+        assert!(output.contains("\n\n@csv(){{\na,b\n}}\n\n"));
+        assert!(output.ends_with("\n\nafter"));
+    }
+
+    #[test]
+    fn test_code_block_untouched() {
+        let mut plugins = Vec::new();
+        let input = "```\n@table(striped){{\n| a |\n}}\n```\n";
+        assert_eq!(protect_table_plugins(input, &mut plugins), input);
+        assert!(plugins.is_empty());
+    }
+
+    #[test]
+    fn test_apply_options() {
+        let plugin = TablePlugin::parse("hover,responsive,caption=<b>Q1</b>", Some("CENTER"));
+        let html = plugin.apply(r#"<table class="table umd-table"><tbody></tbody></table>"#);
+        assert_eq!(
+            html,
+            r#"<div class="w-auto mx-auto"><div class="table-responsive"><table class="table umd-table table-hover"><caption>&lt;b&gt;Q1&lt;/b&gt;</caption><tbody></tbody></table></div></div>"#
+        );
+    }
+
+    #[test]
+    fn test_caption_values() {
+        let plugin = TablePlugin::parse(r#"caption="x, (y)",striped"#, None);
+        assert_eq!(plugin.caption.as_deref(), Some("x, (y)"));
+        assert_eq!(plugin.classes, vec!["table-striped"]);
+
+        let plugin = TablePlugin::parse("caption=A &amp; B &lt;C&gt;", None);
+        let html = plugin.apply(r#"<table class="table"></table>"#);
+        assert!(html.contains("<caption>A &amp; B &lt;C&gt;</caption>"));
+
+        let output = crate::parse("@table(caption=\"Q1, Q2\"){{\n| a |\n}}");
+        assert!(output.contains("<caption>Q1, Q2</caption>"), "{}", output);
+        let output = crate::parse("Table: R&amp;D\n| a |");
+        assert!(output.contains("<caption>R&amp;D</caption>"), "{}", output);
+    }
+
+    #[test]
+    fn test_caption_lines() {
+        let mut plugins = Vec::new();
+        let input = "Table: Before\n| a |\n\n| b |\nTable: After\n\nTable: text only\n";
+        let output = protect_table_plugins(input, &mut plugins);
+        let captions: Vec<_> = plugins.iter().map(|p| p.caption.as_deref()).collect();
+        assert_eq!(captions, [Some("Before"), Some("After")]);
+        assert!(output.contains("\n\n| b |\n\n"));
+        assert!(output.ends_with("\nTable: text only\n"));
+    }
+}
@@ -0,0 +1,413 @@
+//! HTML rendering of the table model
+
+use super::model::{Cell, Column, Table, inherit};
+
+/// Render a table as HTML
+///
+/// Column defaults are applied by grid position, so cells after a
+/// `colspan` or below a `rowspan` pick up the defaults of the column they
+/// occupy.
+///
+/// Header cells get a `scope`. When that is not enough to tell which
+/// headers apply to a cell (spanning headers, headers away from the first
+/// row or column) and the table has an [`Table::id_prefix`], header cells
+/// also get an `id` and every cell lists its headers in `headers`.
+///
+/// # Arguments
+///
+/// * `table` - The table to render; cell content is HTML
+///
+/// # Returns
+///
+/// The `<table>` element
+///
+/// # Examples
+///
+/// ```
+/// use universal_markdown::extensions::table::model::{Cell, Column, Table};
+/// use universal_markdown::extensions::table::render::render_table;
+///
+/// let table = Table {
+///     classes: vec!["table".to_string()],
+///     body: vec![vec![Cell::new("1".to_string(), false)]],
+///     columns: vec![Column {
+///         classes: vec!["text-end".to_string()],
+///         ..Default::default()
+///     }],
+///     ..Default::default()
+/// };
+/// assert_eq!(
+///     render_table(&table),
+///     r#"<table class="table"><tbody><tr><td class="text-end">1</td></tr></tbody></table>"#
+/// );
+/// ```
+pub fn render_table(table: &Table) -> String {
+    let layout = layout(table);
+    // Header associations are only needed where scope is ambiguous
+    let prefix = table.id_prefix.as_deref().filter(|_| is_irregular(&layout));
+
+    let mut html = String::from("<table");
+    push_attribute(&mut html, "class", &table.classes.join(" "));
+    html.push('>');
+
+    if let Some(caption) = &table.caption {
+        html.push_str(&format!("<caption>{}</caption>", caption));
+    }
+
+    let headers = prefix.map(|_| Headers::new(&layout));
+
+    let mut placements = layout.iter();
+    for (tag, rows) in [
+        ("thead", &table.head),
+        ("tbody", &table.body),
+        ("tfoot", &table.foot),
+    ] {
+        if rows.is_empty() {
+            continue;
+        }
+        html.push_str(&format!("<{}>", tag));
+        for (row, placements) in rows.iter().zip(placements.by_ref()) {
+            html.push_str("<tr>");
+            for (cell, placement) in row.iter().zip(placements) {
+                let headers = prefix
+                    .zip(headers.as_ref())
+                    .map(|(prefix, headers)| (prefix, headers.ids(placement, prefix).join(" ")));
+                render_cell(
+                    &mut html,
+                    cell,
+                    placement,
+                    table.columns.get(placement.column),
+                    headers,
+                );
+            }
+            html.push_str("</tr>");
+        }
+        html.push_str(&format!("</{}>", tag));
+    }
+
+    html.push_str("</table>");
+    html
+}
+
+/// Grid column of every cell, row by row in document order
+pub(crate) fn grid_columns(table: &Table) -> Vec<Vec<usize>> {
+    layout(table)
+        .iter()
+        .map(|row| row.iter().map(|placement| placement.column).collect())
+        .collect()
+}
+
+/// Grid position of a cell and, for header cells, their scope
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Placement {
+    row: usize,
+    column: usize,
+    colspan: usize,
+    rowspan: usize,
+    scope: Option<&'static str>,
+}
+
+impl Placement {
+    fn is_column_header(&self) -> bool {
+        matches!(self.scope, Some("col" | "colgroup"))
+    }
+
+    fn is_row_header(&self) -> bool {
+        matches!(self.scope, Some("row" | "rowgroup"))
+    }
+}
+
+/// Place every cell on the grid, following colspan and rowspan
+///
+/// Header cells in `<thead>` or in a row of header cells head their
+/// column (`col`, or `colgroup` when spanning columns); other header cells
+/// head their row (`row`, or `rowgroup` when spanning rows).
+fn layout(table: &Table) -> Vec<Vec<Placement>> {
+    let mut layout = Vec::new();
+    // Columns still covered by a rowspan from an earlier row
+    let mut covered: Vec<usize> = Vec::new();
+
+    for (is_head, rows) in [
+        (true, &table.head),
+        (false, &table.body),
+        (false, &table.foot),
+    ] {
+        for row in rows {
+            let heads_columns = is_head || row.iter().all(|cell| cell.is_header);
+            let mut placements = Vec::with_capacity(row.len());
+            let mut column = 0;
+            for cell in row {
+                while covered.get(column).is_some_and(|&rows| rows > 0) {
+                    column += 1;
+                }
+                let (colspan, rowspan) = (cell.colspan.max(1), cell.rowspan.max(1));
+                let scope = cell.is_header.then_some(match heads_columns {
+                    true if colspan > 1 => "colgroup",
+                    true => "col",
+                    false if rowspan > 1 => "rowgroup",
+                    false => "row",
+                });
+                placements.push(Placement {
+                    row: layout.len(),
+                    column,
+                    colspan,
+                    rowspan,
+                    scope,
+                });
+
+                let end = column + colspan;
+                if covered.len() < end {
+                    covered.resize(end, 0);
+                }
+                for rows in &mut covered[column..end] {
+                    *rows = rowspan;
+                }
+                column = end;
+            }
+            layout.push(placements);
+
+            for rows in covered.iter_mut() {
+                *rows = rows.saturating_sub(1);
+            }
+        }
+    }
+    layout
+}
+
+/// Whether `scope` alone cannot associate the cells with their headers
+///
+/// That is the case when a header spans several cells, when column
+/// headers appear below the first row or when row headers appear after
+/// the first column.
+fn is_irregular(layout: &[Vec<Placement>]) -> bool {
+    layout.iter().flatten().any(|placement| {
+        placement.scope.is_some() && (placement.colspan > 1 || placement.rowspan > 1)
+            || placement.is_column_header() && placement.row > 0
+            || placement.is_row_header() && placement.column > 0
+    })
+}
+
+/// Header cells by the grid columns and rows they head
+struct Headers<'a> {
+    /// Column headers covering each grid column
+    columns: Vec<Vec<&'a Placement>>,
+    /// Row headers covering each grid row
+    rows: Vec<Vec<&'a Placement>>,
+}
+
+impl<'a> Headers<'a> {
+    fn new(layout: &'a [Vec<Placement>]) -> Self {
+        let mut headers = Self {
+            columns: Vec::new(),
+            rows: Vec::new(),
+        };
+        for header in layout.iter().flatten() {
+            let (lines, start, span) = if header.is_column_header() {
+                (&mut headers.columns, header.column, header.colspan)
+            } else if header.is_row_header() {
+                (&mut headers.rows, header.row, header.rowspan)
+            } else {
+                continue;
+            };
+            if lines.len() < start + span {
+                lines.resize_with(start + span, Vec::new);
+            }
+            for line in &mut lines[start..start + span] {
+                line.push(header);
+            }
+        }
+        headers
+    }
+
+    /// Ids of the headers of a cell: column headers above it and row
+    /// headers before it, in document order
+    fn ids(&self, cell: &Placement, prefix: &str) -> Vec<String> {
+        fn lines<'b>(
+            lines: &'b [Vec<&'b Placement>],
+            start: usize,
+            span: usize,
+        ) -> impl Iterator<Item = &'b Placement> {
+            lines
+                .get(start..(start + span).min(lines.len()))
+                .unwrap_or_default()
+                .iter()
+                .flatten()
+                .copied()
+        }
+        let mut headers: Vec<&Placement> = lines(&self.columns, cell.column, cell.colspan)
+            .filter(|header| header.row + header.rowspan <= cell.row)
+            .chain(
+                lines(&self.rows, cell.row, cell.rowspan)
+                    .filter(|header| header.column + header.colspan <= cell.column),
+            )
+            .collect();
+        // Spanning headers are found once per covered line
+        headers.sort_by_key(|header| (header.row, header.column));
+        headers.dedup_by_key(|header| (header.row, header.column));
+        headers
+            .into_iter()
+            .map(|header| header_id(prefix, header))
+            .collect()
+    }
+}
+
+fn header_id(prefix: &str, header: &Placement) -> String {
+    format!("{}-{}-{}", prefix, header.row, header.column)
+}
+
+/// Render a cell; `headers` holds the id prefix and the header ids of the
+/// cell in tables that need explicit associations
+fn render_cell(
+    html: &mut String,
+    cell: &Cell,
+    placement: &Placement,
+    column: Option<&Column>,
+    headers: Option<(&str, String)>,
+) {
+    let tag = if cell.is_header { "th" } else { "td" };
+
+    let mut classes = cell.classes.clone();
+    let mut styles = cell.styles.clone();
+    if let Some(column) = column {
+        inherit(&mut classes, &mut styles, column);
+    }
+
+    html.push('<');
+    html.push_str(tag);
+    if let Some((prefix, _)) = &headers
+        && cell.is_header
+    {
+        push_attribute(html, "id", &header_id(prefix, placement));
+    }
+    push_attribute(html, "class", &classes.join(" "));
+    push_attribute(html, "style", &styles.join("; "));
+    if let Some(scope) = placement.scope {
+        push_attribute(html, "scope", scope);
+    }
+    if cell.colspan > 1 {
+        html.push_str(&format!(r#" colspan="{}""#, cell.colspan));
+    }
+    if cell.rowspan > 1 {
+        html.push_str(&format!(r#" rowspan="{}""#, cell.rowspan));
+    }
+    if let Some((_, ids)) = &headers {
+        push_attribute(html, "headers", ids);
+    }
+    if let Some(formula) = &cell.formula {
+        push_attribute(html, "data-formula", formula);
+    }
+    html.push('>');
+    html.push_str(&cell.content);
+    html.push_str(&format!("</{}>", tag));
+}
+
+fn push_attribute(html: &mut String, name: &str, value: &str) {
+    if !value.is_empty() {
+        html.push_str(&format!(
+            r#" {}="{}""#,
+            name,
+            html_escape::encode_double_quoted_attribute(value)
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell(content: &str) -> Cell {
+        Cell::new(content.to_string(), false)
+    }
+
+    #[test]
+    fn test_sections_and_caption() {
+        let table = Table {
+            caption: Some("Sales".to_string()),
+            head: vec![vec![Cell::new("H".to_string(), true)]],
+            body: vec![vec![cell("B")]],
+            foot: vec![vec![cell("F")]],
+            ..Default::default()
+        };
+        assert_eq!(
+            render_table(&table),
+            "<table><caption>Sales</caption><thead><tr><th scope=\"col\">H</th></tr></thead><tbody><tr><td>B</td></tr></tbody><tfoot><tr><td>F</td></tr></tfoot></table>"
+        );
+    }
+
+    #[test]
+    fn test_column_defaults_follow_spans() {
+        let mut wide = cell("A");
+        wide.colspan = 2;
+        let mut tall = cell("B");
+        tall.rowspan = 2;
+        let mut own = cell("D");
+        own.classes.push("text-start".to_string());
+        let columns = ["text-start", "text-center", "text-end"]
+            .iter()
+            .map(|class| Column {
+                classes: vec![class.to_string()],
+                ..Default::default()
+            })
+            .collect();
+
+        let table = Table {
+            body: vec![vec![wide, tall], vec![cell("C"), own]],
+            columns,
+            ..Default::default()
+        };
+        let html = render_table(&table);
+        assert!(html.contains(r#"<td class="text-start" colspan="2">A</td>"#));
+        assert!(html.contains(r#"<td class="text-end" rowspan="2">B</td>"#));
+        assert!(html.contains(r#"<td class="text-start">C</td>"#));
+        // The cell's own alignment wins over the column default
+        assert!(html.contains(r#"<td class="text-start">D</td>"#));
+    }
+
+    #[test]
+    fn test_header_scopes_and_associations() {
+        let header = |content: &str| Cell::new(content.to_string(), true);
+        let mut group = header("Q1");
+        group.colspan = 2;
+        let mut region = header("North");
+        region.rowspan = 2;
+        let mut table = Table {
+            head: vec![
+                vec![header("Region"), group],
+                vec![header(""), header("Jan"), header("Feb")],
+            ],
+            body: vec![
+                vec![region, cell("1"), cell("2")],
+                vec![cell("3"), cell("4")],
+            ],
+            ..Default::default()
+        };
+
+        // Scopes are always set; associations need an id prefix
+        let html = render_table(&table);
+        assert!(html.contains(r#"<th scope="colgroup" colspan="2">Q1</th>"#));
+        assert!(html.contains(r#"<th scope="rowgroup" rowspan="2">North</th>"#));
+        assert!(!html.contains("headers="));
+
+        table.id_prefix = Some("t".to_string());
+        let html = render_table(&table);
+        assert!(html.contains(r#"<th id="t-1-1" scope="col" headers="t-0-1">Jan</th>"#));
+        assert!(html.contains(r#"<td headers="t-0-1 t-1-2 t-2-0">4</td>"#));
+    }
+
+    #[test]
+    fn test_regular_table_has_no_associations() {
+        let table = Table {
+            head: vec![vec![
+                Cell::new("A".to_string(), true),
+                Cell::new("B".to_string(), true),
+            ]],
+            body: vec![vec![Cell::new("Row".to_string(), true), cell("1")]],
+            id_prefix: Some("t".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            render_table(&table),
+            r#"<table><thead><tr><th scope="col">A</th><th scope="col">B</th></tr></thead><tbody><tr><th scope="row">Row</th><td>1</td></tr></tbody></table>"#
+        );
+    }
+}
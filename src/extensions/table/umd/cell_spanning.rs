@@ -1,99 +1,283 @@
 //! Cell spanning support for UMD tables
 //!
 //! Provides colspan and rowspan functionality using special markers:
-//! - `|>` for horizontal spanning (colspan)
-//! - `|^` for vertical spanning (rowspan)
+//! - `|>` for horizontal spanning (colspan): the slot joins the cell on its
+//!   right, as in PukiWiki (`|>|Title|` is one cell two columns wide)
+//! - `|^` for vertical spanning (rowspan): the slot joins the cell above
+//!
+//! Every row lists one slot per grid column, markers included. The rows of
+//! a section are normalized into a grid: short rows are padded with empty
+//! cells, and markers that cannot form a rectangular cell (`|^` in the
+//! first row, `|>` in the last column, a span that crosses another span)
+//! become empty cells. Each of these repairs is reported as a diagnostic,
+//! so the rendered table is always a valid HTML table.
 
 use super::parser::Cell;
+use crate::diagnostics::Diagnostic;
 
-/// Process cell spanning (colspan and rowspan)
-pub fn process_cell_spanning(rows: &mut Vec<Vec<Cell>>) {
-    process_colspan(rows);
-    process_rowspan(rows);
-}
+/// Colspan marker content, left by `parse_cells` for `|>`
+const COLSPAN: &str = ">";
+/// Rowspan marker content, left by `parse_cells` for `|^`
+const ROWSPAN: &str = "^";
+
+/// Normalize the rows of a table section into a grid
+///
+/// # Arguments
+///
+/// * `rows` - Parsed rows of the section, one slot per grid column
+/// * `width` - Number of grid columns of the whole table
+/// * `first_row` - Index of the first row of the section in the table,
+///   used to number rows in diagnostics
+/// * `diagnostics` - Collector for the repairs made to the grid
+///
+/// # Returns
+///
+/// The rows with markers resolved into `colspan`/`rowspan`; the cells of
+/// each row cover exactly `width` columns together with the rowspans from
+/// the rows above.
+pub fn normalize_section(
+    rows: Vec<Vec<Cell>>,
+    width: usize,
+    first_row: usize,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Vec<Vec<Cell>> {
+    let mut slots: Vec<Vec<Cell>> = rows;
+    for (index, row) in slots.iter_mut().enumerate() {
+        if row.len() < width {
+            diagnostics.push(Diagnostic::new(
+                "table-ragged-row",
+                format!(
+                    "Table row {} has {} cells; padded to {} with empty cells",
+                    first_row + index + 1,
+                    row.len(),
+                    width
+                ),
+            ));
+            row.resize_with(width, || Cell::new(String::new(), false));
+        }
+    }
 
-/// Process colspan (horizontal spanning)
-fn process_colspan(rows: &mut Vec<Vec<Cell>>) {
-    for row in rows.iter_mut() {
-        let mut i = 0;
-        while i < row.len() {
-            if row[i].content.ends_with("|>") || row[i].content == "|>" {
-                // Remove marker
-                row[i].content = row[i].content.trim_end_matches("|>").trim().to_string();
-
-                // Count consecutive empty or |> cells
-                let mut span = 1;
-                let j = i + 1;
-                while j < row.len() && (row[j].content.is_empty() || row[j].content == "|>") {
-                    span += 1;
-                    row.remove(j); // Remove merged cell
+    // Slot owners: the slot holding the content of the cell covering it.
+    // `>` slots take the owner of their right neighbour, so each row is
+    // resolved from right to left
+    let mut owners: Vec<Vec<(usize, usize)>> = Vec::with_capacity(slots.len());
+    for row in 0..slots.len() {
+        let mut owner_row = vec![(row, 0); width];
+        for column in (0..width).rev() {
+            owner_row[column] = match slots[row][column].content.as_str() {
+                COLSPAN if column + 1 < width => owner_row[column + 1],
+                ROWSPAN if row > 0 => owners[row - 1][column],
+                marker @ (COLSPAN | ROWSPAN) => {
+                    diagnostics.push(Diagnostic::new(
+                        "table-span-invalid",
+                        format!(
+                            "`|{}` at table row {}, column {} has no cell to join; rendered as an empty cell",
+                            marker,
+                            first_row + row + 1,
+                            column + 1
+                        ),
+                    ));
+                    slots[row][column] = Cell::new(String::new(), false);
+                    (row, column)
                 }
-                row[i].colspan = span;
+                _ => (row, column),
+            };
+        }
+        owners.push(owner_row);
+    }
+
+    // Give every origin the largest rectangle its slots form: the run of
+    // `>` slots on its left, extended down by the rows of `^` slots below
+    // the whole run
+    let mut placed: Vec<Vec<(usize, Cell)>> = (0..slots.len()).map(|_| Vec::new()).collect();
+    let mut rectangles: Vec<Vec<Option<(usize, usize)>>> = vec![vec![None; width]; slots.len()];
+    for row in 0..slots.len() {
+        for column in 0..width {
+            let origin = (row, column);
+            if owners[row][column] != origin {
+                continue;
             }
-            i += 1;
+            let left = column
+                - (0..column)
+                    .rev()
+                    .take_while(|&c| owners[row][c] == origin)
+                    .count();
+            let colspan = column + 1 - left;
+            let rowspan = (row..slots.len())
+                .take_while(|&r| (left..=column).all(|c| owners[r][c] == origin))
+                .count();
+            rectangles[row][column] = Some((left, row + rowspan));
+
+            let mut cell =
+                std::mem::replace(&mut slots[row][column], Cell::new(String::new(), false));
+            cell.colspan = colspan;
+            cell.rowspan = rowspan;
+            placed[row].push((left, cell));
         }
     }
-}
 
-/// Process rowspan (vertical spanning)
-fn process_rowspan(rows: &mut Vec<Vec<Cell>>) {
-    let max_cols = rows.iter().map(|r| r.len()).max().unwrap_or(0);
-    for col in 0..max_cols {
-        let mut row_idx = 0;
-        while row_idx < rows.len() {
-            if col < rows[row_idx].len() && rows[row_idx][col].content == "|^" {
-                // Find source cell above
-                if row_idx > 0 && col < rows[row_idx - 1].len() {
-                    rows[row_idx - 1][col].rowspan += 1;
-                    rows[row_idx].remove(col);
-                }
+    // Slots outside of their owner's rectangle cross another span and are
+    // detached
+    let mut detached = Vec::new();
+    for (row, owner_row) in owners.iter().enumerate() {
+        for (column, &(origin_row, origin_column)) in owner_row.iter().enumerate() {
+            let Some((left, bottom)) = rectangles[origin_row][origin_column] else {
+                continue;
+            };
+            let inside = row < bottom && (left..=origin_column).contains(&column);
+            if !inside {
+                detached.push((row, column));
             }
-            row_idx += 1;
         }
     }
+
+    for (row, column) in detached {
+        diagnostics.push(Diagnostic::new(
+            "table-span-overlap",
+            format!(
+                "Span marker at table row {}, column {} crosses another span; rendered as an empty cell",
+                first_row + row + 1,
+                column + 1
+            ),
+        ));
+        placed[row].push((column, Cell::new(String::new(), false)));
+    }
+
+    placed
+        .into_iter()
+        .map(|mut cells| {
+            cells.sort_by_key(|(column, _)| *column);
+            cells.into_iter().map(|(_, cell)| cell).collect()
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn rows(source: &[&[&str]]) -> Vec<Vec<Cell>> {
+        source
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|content| Cell::new(content.to_string(), false))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn shape(rows: &[Vec<Cell>]) -> Vec<Vec<(String, usize, usize)>> {
+        rows.iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| (cell.content.clone(), cell.colspan, cell.rowspan))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn cell(content: &str, colspan: usize, rowspan: usize) -> (String, usize, usize) {
+        (content.to_string(), colspan, rowspan)
+    }
+
     #[test]
     fn test_colspan() {
-        let mut rows = vec![
-            vec![
-                Cell::new("A |>".to_string(), true),
-                Cell::new("".to_string(), true),
-            ],
+        let mut diagnostics = Vec::new();
+        let rows = normalize_section(rows(&[&[">", "A"], &["C", "D"]]), 2, 0, &mut diagnostics);
+
+        assert_eq!(
+            shape(&rows),
             vec![
-                Cell::new("C".to_string(), false),
-                Cell::new("D".to_string(), false),
-            ],
-        ];
+                vec![cell("A", 2, 1)],
+                vec![cell("C", 1, 1), cell("D", 1, 1)]
+            ]
+        );
+        assert!(diagnostics.is_empty());
+    }
 
-        process_cell_spanning(&mut rows);
+    #[test]
+    fn test_rowspan() {
+        let mut diagnostics = Vec::new();
+        let rows = normalize_section(rows(&[&["A", "B"], &["^", "D"]]), 2, 0, &mut diagnostics);
 
-        assert_eq!(rows[0].len(), 1);
-        assert_eq!(rows[0][0].colspan, 2);
-        assert_eq!(rows[0][0].content, "A");
+        assert_eq!(
+            shape(&rows),
+            vec![
+                vec![cell("A", 1, 2), cell("B", 1, 1)],
+                vec![cell("D", 1, 1)]
+            ]
+        );
+        assert!(diagnostics.is_empty());
     }
 
     #[test]
-    fn test_rowspan() {
-        let mut rows = vec![
+    fn test_combined_span() {
+        let mut diagnostics = Vec::new();
+        let rows = normalize_section(
+            rows(&[&[">", "A", "B"], &["^", "^", "C"]]),
+            3,
+            0,
+            &mut diagnostics,
+        );
+
+        assert_eq!(
+            shape(&rows),
             vec![
-                Cell::new("A".to_string(), true),
-                Cell::new("B".to_string(), true),
-            ],
+                vec![cell("A", 2, 2), cell("B", 1, 1)],
+                vec![cell("C", 1, 1)]
+            ]
+        );
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_ragged_rows_are_padded() {
+        let mut diagnostics = Vec::new();
+        let rows = normalize_section(rows(&[&["A", "B", "C"], &["D"]]), 3, 2, &mut diagnostics);
+
+        assert_eq!(rows[1].len(), 3);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "table-ragged-row");
+        assert!(diagnostics[0].message.contains("row 4"));
+    }
+
+    #[test]
+    fn test_markers_without_target() {
+        let mut diagnostics = Vec::new();
+        let rows = normalize_section(rows(&[&["^", "A"], &["B", ">"]]), 2, 0, &mut diagnostics);
+
+        assert_eq!(
+            shape(&rows),
             vec![
-                Cell::new("|^".to_string(), false),
-                Cell::new("D".to_string(), false),
-            ],
-        ];
+                vec![cell("", 1, 1), cell("A", 1, 1)],
+                vec![cell("B", 1, 1), cell("", 1, 1)]
+            ]
+        );
+        let codes: Vec<_> = diagnostics.iter().map(|d| d.code).collect();
+        assert_eq!(codes, vec!["table-span-invalid", "table-span-invalid"]);
+    }
 
-        process_cell_spanning(&mut rows);
+    #[test]
+    fn test_crossing_spans() {
+        // A spans two columns; the `^` below its first column would make
+        // it L-shaped, and the `>` before it would reach further left
+        let mut diagnostics = Vec::new();
+        let rows = normalize_section(
+            rows(&[&["X", ">", "A"], &[">", "^", "B"]]),
+            3,
+            0,
+            &mut diagnostics,
+        );
 
-        assert_eq!(rows[0][0].rowspan, 2);
-        assert_eq!(rows[1].len(), 1);
-        assert_eq!(rows[1][0].content, "D");
+        assert_eq!(
+            shape(&rows),
+            vec![
+                vec![cell("X", 1, 1), cell("A", 2, 1)],
+                vec![cell("", 1, 1), cell("", 1, 1), cell("B", 1, 1)]
+            ]
+        );
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics.iter().all(|d| d.code == "table-span-overlap"));
     }
 }
@@ -6,21 +6,28 @@
 //! - Alignment prefixes: TOP:, MIDDLE:, BOTTOM:, CENTER:, etc.
 
 use super::parser::Cell;
+use crate::extensions::styles::Styles;
+use crate::extensions::table::model::Table;
+use once_cell::sync::Lazy;
 use regex::Regex;
 
+// COLOR(fg,bg): prefix and the rest of the cell
+static COLOR_PREFIX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^COLOR\(([^)]*)\):\s*(.*)$").unwrap());
+
+// SIZE(value): prefix and the rest of the cell
+static SIZE_PREFIX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^SIZE\(([^)]+)\):\s*(.*)$").unwrap());
+
 /// Parse cell content for decorations and markers
-pub fn parse_cell_content(cell: &mut Cell) {
+///
+/// Custom `COLOR()`/`SIZE()` values are validated with `styles` and kept
+/// as `property: value` declarations in [`Cell::styles`], so they can be
+/// compared with column defaults by property; [`emit_styles`] applies the
+/// style policy to them once the table is complete.
+pub fn parse_cell_content(cell: &mut Cell, styles: &mut Styles) {
     let content = &cell.content;
 
-    // Check for colspan marker: |> - mark but don't remove yet
-    if content == "|>" || content.ends_with(" |>") {
-        // Keep the marker for process_cell_spanning
-        return;
-    }
-
-    // Check for rowspan marker: |^
-    if content == "|^" {
-        // Keep the marker for process_cell_spanning
+    // Keep spanning markers (`|>`, `|^`) for normalize_section
+    if content == ">" || content == "^" {
         return;
     }
 
@@ -34,20 +41,19 @@ pub fn parse_cell_content(cell: &mut Cell) {
     }
 
     // Parse COLOR(fg,bg):
-    let color_pattern = Regex::new(r"^COLOR\(([^)]*)\):\s*(.*)$").unwrap();
-    if let Some(caps) = color_pattern.captures(&remaining) {
+    if let Some(caps) = COLOR_PREFIX.captures(&remaining) {
         let args = caps[1].to_string();
         remaining = caps[2].to_string();
 
         let parts: Vec<&str> = args.split(',').collect();
-        let fg = parts.get(0).map_or("", |s| s.trim());
+        let fg = parts.first().map_or("", |s| s.trim());
         let bg = parts.get(1).map_or("", |s| s.trim());
 
         if !fg.is_empty() && fg != "inherit" {
             if is_bootstrap_color(fg) {
                 cell.classes.push(format!("text-{}", fg));
             } else {
-                cell.styles.push(format!("color: {}", fg));
+                cell.styles.extend(styles.validate("color", fg));
             }
         }
 
@@ -55,14 +61,13 @@ pub fn parse_cell_content(cell: &mut Cell) {
             if is_bootstrap_color(bg) {
                 cell.classes.push(format!("bg-{}", bg));
             } else {
-                cell.styles.push(format!("background-color: {}", bg));
+                cell.styles.extend(styles.validate("background-color", bg));
             }
         }
     }
 
     // Parse SIZE(value):
-    let size_pattern = Regex::new(r"^SIZE\(([^)]+)\):\s*(.*)$").unwrap();
-    if let Some(caps) = size_pattern.captures(&remaining) {
+    if let Some(caps) = SIZE_PREFIX.captures(&remaining) {
         let value = caps[1].to_string();
         remaining = caps[2].to_string();
 
@@ -77,7 +82,8 @@ pub fn parse_cell_content(cell: &mut Cell) {
                 } else {
                     format!("{}rem", value)
                 };
-            cell.styles.push(format!("font-size: {}", size_value));
+            cell.styles
+                .extend(styles.validate("font-size", &size_value));
         }
     }
 
@@ -107,6 +113,19 @@ pub fn parse_cell_content(cell: &mut Cell) {
     cell.content = remaining;
 }
 
+/// Apply the style policy to the custom declarations of every cell
+///
+/// Under [`crate::extensions::styles::StylePolicy::Classes`] the
+/// declarations become generated classes; under `Disallow` they are
+/// dropped.
+pub(crate) fn emit_styles(table: &mut Table, styles: &mut Styles) {
+    for cell in table.rows_mut().flatten() {
+        for declaration in std::mem::take(&mut cell.styles) {
+            styles.emit(declaration, &mut cell.classes, &mut cell.styles);
+        }
+    }
+}
+
 /// Check if a color is a Bootstrap color name
 fn is_bootstrap_color(color: &str) -> bool {
     matches!(
@@ -175,7 +194,7 @@ mod tests {
     #[test]
     fn test_color_decoration() {
         let mut cell = Cell::new("COLOR(red,blue): Text".to_string(), false);
-        parse_cell_content(&mut cell);
+        parse_cell_content(&mut cell, &mut Styles::default());
 
         assert_eq!(cell.content, "Text");
         assert!(cell.classes.contains(&"text-red".to_string()));
@@ -186,7 +205,7 @@ mod tests {
     #[test]
     fn test_header_marker() {
         let mut cell = Cell::new("~Header Text".to_string(), false);
-        parse_cell_content(&mut cell);
+        parse_cell_content(&mut cell, &mut Styles::default());
 
         assert_eq!(cell.content, "Header Text");
         assert!(cell.is_header);
@@ -195,7 +214,7 @@ mod tests {
     #[test]
     fn test_header_marker_with_decoration() {
         let mut cell = Cell::new("COLOR(red): ~Header".to_string(), false);
-        parse_cell_content(&mut cell);
+        parse_cell_content(&mut cell, &mut Styles::default());
 
         assert_eq!(cell.content, "Header");
         assert!(cell.is_header);
@@ -205,16 +224,30 @@ mod tests {
     #[test]
     fn test_size_decoration() {
         let mut cell = Cell::new("SIZE(1.5): Text".to_string(), false);
-        parse_cell_content(&mut cell);
+        parse_cell_content(&mut cell, &mut Styles::default());
 
         assert_eq!(cell.content, "Text");
         assert!(cell.classes.contains(&"fs-4".to_string()));
     }
 
+    #[test]
+    fn test_invalid_custom_values_dropped() {
+        let mut styles = Styles::default();
+        let mut cell = Cell::new(
+            "COLOR(#abc,red;top:0): SIZE(2px;x:y): Text".to_string(),
+            false,
+        );
+        parse_cell_content(&mut cell, &mut styles);
+
+        assert_eq!(cell.content, "Text");
+        assert_eq!(cell.styles, vec!["color: #abc".to_string()]);
+        assert_eq!(styles.diagnostics().len(), 2);
+    }
+
     #[test]
     fn test_alignment_decoration() {
         let mut cell = Cell::new("CENTER: Text".to_string(), false);
-        parse_cell_content(&mut cell);
+        parse_cell_content(&mut cell, &mut Styles::default());
 
         assert_eq!(cell.content, "Text");
         assert!(cell.classes.contains(&"text-center".to_string()));
@@ -0,0 +1,515 @@
+//! Formula cells
+//!
+//! With [`crate::parser::ParserOptions::table_formulas`] enabled, a UMD
+//! table cell whose content starts with `=` is computed from other cells:
+//!
+//! ```text
+//! |~Item|~Price|h
+//! |Pen|120|
+//! |Ink|1,080|
+//! |~Total|=SUM(B2:B3)|f
+//! ```
+//!
+//! - Functions: `SUM`, `AVG` (or `AVERAGE`), `COUNT`, `MIN`, `MAX`
+//! - Arguments, separated by commas: a cell (`B2`), a range (`B2:C9`), a
+//!   whole column (`B` or `B:B`), `col` or `row` (the other cells of the
+//!   formula's own column or row), or a number
+//! - Rows are numbered from 1 over the whole table (header rows included)
+//!   and columns lettered from `A` by grid position, so spanned cells
+//!   count once at every position they cover
+//! - Whole columns, `col` and `row` skip header cells; empty cells are
+//!   skipped everywhere
+//!
+//! The cell shows the result and keeps the formula in a `data-formula`
+//! attribute. Errors are shown as `#NAME?` (unknown function or syntax),
+//! `#REF!` (reference outside the table), `#VALUE!` (text where a number
+//! is needed), `#DIV/0!` (average of nothing) or `#CYCLE!` (circular
+//! reference), and reported as diagnostics.
+
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::diagnostics::Diagnostic;
+use crate::extensions::table::model::Table;
+
+/// Supported functions
+const FUNCTIONS: [&str; 6] = ["SUM", "AVG", "AVERAGE", "COUNT", "MIN", "MAX"];
+
+/// A formula: `=NAME(args)`
+static FORMULA: Lazy<Regex> = Lazy::new(|| Regex::new(r"^=\s*([A-Za-z]+)\s*\((.*)\)$").unwrap());
+
+/// A cell, a range or a whole column: `B2`, `B2:C9`, `B`, `B:C`
+static REFERENCE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^([A-Z]+)([0-9]+)?(?::([A-Z]+)([0-9]+)?)?$").unwrap());
+
+/// Number with optional sign, thousands separators and decimals
+static NUMBER: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^[+-]?((\d{1,3}(,\d{3})+|\d+)(\.\d*)?|\.\d+)$").unwrap());
+
+/// Why a formula has no value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FormulaError {
+    Syntax,
+    Reference,
+    Type,
+    DivisionByZero,
+    Cycle,
+}
+
+impl FormulaError {
+    /// Text shown in the cell
+    fn marker(self) -> &'static str {
+        match self {
+            Self::Syntax => "#NAME?",
+            Self::Reference => "#REF!",
+            Self::Type => "#VALUE!",
+            Self::DivisionByZero => "#DIV/0!",
+            Self::Cycle => "#CYCLE!",
+        }
+    }
+}
+
+/// Position of a cell: row over all sections, index within the row
+type CellId = (usize, usize);
+
+/// Evaluation state of a formula cell
+enum State {
+    Evaluating,
+    Done(Result<f64, FormulaError>),
+}
+
+struct Evaluator<'a> {
+    /// Content and header flag of each cell, by row
+    cells: Vec<Vec<(&'a str, bool)>>,
+    /// Cell at each grid position, spans expanded
+    grid: Vec<Vec<Option<CellId>>>,
+    states: HashMap<CellId, State>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Compute the formula cells of a table
+///
+/// Formula cells get the result as content and the formula in
+/// [`crate::extensions::table::model::Cell::formula`].
+///
+/// # Arguments
+///
+/// * `table` - A UMD table, after cell spanning
+///
+/// # Returns
+///
+/// Diagnostics for formulas that could not be computed
+///
+/// # Examples
+///
+/// ```
+/// use universal_markdown::extensions::table::model::{Cell, Table};
+/// use universal_markdown::extensions::table::umd::evaluate_formulas;
+///
+/// let cell = |content: &str| Cell::new(content.to_string(), false);
+/// let mut table = Table {
+///     body: vec![
+///         vec![cell("1,000"), cell("=AVG(col)")],
+///         vec![cell("500"), cell("=SUM(A1:A2)")],
+///     ],
+///     ..Default::default()
+/// };
+/// assert!(evaluate_formulas(&mut table).is_empty());
+/// assert_eq!(table.body[0][1].content, "1500");
+/// assert_eq!(table.body[1][1].formula.as_deref(), Some("=SUM(A1:A2)"));
+/// ```
+pub fn evaluate_formulas(table: &mut Table) -> Vec<Diagnostic> {
+    let results: Vec<(CellId, Result<f64, FormulaError>)>;
+    let diagnostics;
+    {
+        let cells: Vec<Vec<(&str, bool)>> = table
+            .rows()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| (cell.content.as_str(), cell.is_header))
+                    .collect()
+            })
+            .collect();
+        let formulas: Vec<CellId> = cells
+            .iter()
+            .enumerate()
+            .flat_map(|(r, row)| {
+                row.iter()
+                    .enumerate()
+                    .filter(|(_, (content, _))| content.starts_with('='))
+                    .map(move |(i, _)| (r, i))
+            })
+            .collect();
+        if formulas.is_empty() {
+            return Vec::new();
+        }
+
+        let mut evaluator = Evaluator {
+            grid: build_grid(table),
+            cells,
+            states: HashMap::new(),
+            diagnostics: Vec::new(),
+        };
+        results = formulas
+            .into_iter()
+            .map(|id| (id, evaluator.evaluate(id)))
+            .collect();
+        diagnostics = evaluator.diagnostics;
+    }
+
+    let mut rows: Vec<_> = table.rows_mut().collect();
+    for ((r, i), result) in results {
+        let cell = &mut rows[r][i];
+        let formula = std::mem::replace(
+            &mut cell.content,
+            match result {
+                Ok(value) => format_number(value),
+                Err(error) => error.marker().to_string(),
+            },
+        );
+        cell.formula = Some(formula);
+    }
+    diagnostics
+}
+
+/// Map grid positions to cells, following colspan and rowspan
+fn build_grid(table: &Table) -> Vec<Vec<Option<CellId>>> {
+    let mut grid: Vec<Vec<Option<CellId>>> = Vec::new();
+    for (r, row) in table.rows().enumerate() {
+        if grid.len() <= r {
+            grid.resize(r + 1, Vec::new());
+        }
+        let mut column = 0;
+        for (i, cell) in row.iter().enumerate() {
+            while grid[r].get(column).is_some_and(Option::is_some) {
+                column += 1;
+            }
+            for covered in r..r + cell.rowspan.max(1) {
+                if grid.len() <= covered {
+                    grid.resize(covered + 1, Vec::new());
+                }
+                let line = &mut grid[covered];
+                let end = column + cell.colspan.max(1);
+                if line.len() < end {
+                    line.resize(end, None);
+                }
+                for slot in &mut line[column..end] {
+                    *slot = Some((r, i));
+                }
+            }
+            column += cell.colspan.max(1);
+        }
+    }
+
+    // Spans past the last row are dropped
+    grid.truncate(table.rows().count());
+    grid
+}
+
+impl Evaluator<'_> {
+    /// Value of a formula cell, computing it on first use
+    fn evaluate(&mut self, id: CellId) -> Result<f64, FormulaError> {
+        match self.states.get(&id) {
+            Some(State::Done(result)) => return *result,
+            Some(State::Evaluating) => return Err(FormulaError::Cycle),
+            None => {}
+        }
+
+        self.states.insert(id, State::Evaluating);
+        let formula = self.cells[id.0][id.1].0;
+        let result = self.compute(id, formula).map_err(|(error, message)| {
+            if let Some(message) = message {
+                self.diagnostics.push(Diagnostic::new(
+                    match error {
+                        FormulaError::Syntax => "table-formula-syntax",
+                        FormulaError::Reference => "table-formula-reference",
+                        FormulaError::Type => "table-formula-type",
+                        FormulaError::DivisionByZero => "table-formula-division",
+                        FormulaError::Cycle => "table-formula-cycle",
+                    },
+                    format!("{} in {} ({})", message, self.address(id), formula),
+                ));
+            }
+            error
+        });
+        self.states.insert(id, State::Done(result));
+        result
+    }
+
+    /// Compute a formula
+    ///
+    /// Errors carry a message when they arise in this formula, and none
+    /// when they come from a referenced formula (already reported).
+    fn compute(
+        &mut self,
+        id: CellId,
+        formula: &str,
+    ) -> Result<f64, (FormulaError, Option<String>)> {
+        let Some(caps) = FORMULA.captures(formula) else {
+            return Err((FormulaError::Syntax, Some("Invalid formula".to_string())));
+        };
+        let function = caps[1].to_ascii_uppercase();
+        if !FUNCTIONS.contains(&function.as_str()) {
+            return Err((
+                FormulaError::Syntax,
+                Some(format!("Unknown function {}", function)),
+            ));
+        }
+
+        let mut values = Vec::new();
+        for arg in caps[2].split(',').map(str::trim).filter(|a| !a.is_empty()) {
+            if let Some(number) = parse_number(arg) {
+                values.push(number);
+                continue;
+            }
+            let Some(targets) = self.resolve(id, arg) else {
+                return Err((
+                    FormulaError::Reference,
+                    Some(format!("Invalid reference {}", arg)),
+                ));
+            };
+            for target in targets {
+                values.extend(self.value(target)?);
+            }
+        }
+
+        let count = values.len() as f64;
+        match function.as_str() {
+            "SUM" => Ok(values.iter().sum()),
+            "AVG" | "AVERAGE" if values.is_empty() => Err((
+                FormulaError::DivisionByZero,
+                Some("Average of no values".to_string()),
+            )),
+            "AVG" | "AVERAGE" => Ok(values.iter().sum::<f64>() / count),
+            "COUNT" => Ok(count),
+            "MIN" => Ok(values.into_iter().reduce(f64::min).unwrap_or(0.0)),
+            _ => Ok(values.into_iter().reduce(f64::max).unwrap_or(0.0)),
+        }
+    }
+
+    /// Number in a referenced cell; `None` for an empty cell
+    fn value(&mut self, target: CellId) -> Result<Option<f64>, (FormulaError, Option<String>)> {
+        let content = self.cells[target.0][target.1].0;
+        if content.starts_with('=') {
+            return match self.evaluate(target) {
+                Ok(value) => Ok(Some(value)),
+                // The cycle is reported by the formula that closes it
+                Err(FormulaError::Cycle) if self.is_evaluating(target) => Err((
+                    FormulaError::Cycle,
+                    Some(format!("Circular reference to {}", self.address(target))),
+                )),
+                Err(error) => Err((error, None)),
+            };
+        }
+        if content.is_empty() {
+            return Ok(None);
+        }
+        match parse_number(content) {
+            Some(number) => Ok(Some(number)),
+            None => Err((
+                FormulaError::Type,
+                Some(format!("{} is not a number", self.address(target))),
+            )),
+        }
+    }
+
+    fn is_evaluating(&self, id: CellId) -> bool {
+        matches!(self.states.get(&id), Some(State::Evaluating))
+    }
+
+    /// Cells referenced by an argument, each once, in grid order
+    fn resolve(&self, id: CellId, arg: &str) -> Option<Vec<CellId>> {
+        let (row, column) = self.position(id)?;
+        let height = self.grid.len();
+        let width = self.grid.iter().map(Vec::len).max().unwrap_or(0);
+
+        // Row and column bounds, and whether header cells are skipped
+        let (rows, columns, skip_headers) = match arg.to_ascii_lowercase().as_str() {
+            "col" => (0..height, column..column + 1, true),
+            "row" => (row..row + 1, 0..width, true),
+            _ => {
+                let caps = REFERENCE.captures(arg)?;
+                let first = column_index(&caps[1]);
+                let last = caps.get(3).map_or(first, |c| column_index(c.as_str()));
+                let first_row = caps.get(2).map(|r| r.as_str().parse::<usize>());
+                let last_row = match caps.get(3) {
+                    Some(_) => caps.get(4).map(|r| r.as_str().parse::<usize>()),
+                    None => first_row.clone(),
+                };
+                match (first_row, last_row) {
+                    (Some(Ok(first_row)), Some(Ok(last_row))) if first_row > 0 && last_row > 0 => (
+                        first_row.min(last_row) - 1..first_row.max(last_row),
+                        first.min(last)..first.max(last) + 1,
+                        false,
+                    ),
+                    (None, None) => (0..height, first.min(last)..first.max(last) + 1, true),
+                    _ => return None,
+                }
+            }
+        };
+        if rows.end > height || columns.end > width {
+            return None;
+        }
+
+        let mut targets: Vec<CellId> = Vec::new();
+        for r in rows {
+            for c in columns.clone() {
+                let Some(&Some(target)) = self.grid[r].get(c) else {
+                    continue;
+                };
+                let is_header = self.cells[target.0][target.1].1;
+                let implicit = skip_headers && (target == id || is_header);
+                if !implicit && !targets.contains(&target) {
+                    targets.push(target);
+                }
+            }
+        }
+        Some(targets)
+    }
+
+    /// First grid position of a cell
+    fn position(&self, id: CellId) -> Option<(usize, usize)> {
+        self.grid.iter().enumerate().find_map(|(r, line)| {
+            line.iter()
+                .position(|&cell| cell == Some(id))
+                .map(|c| (r, c))
+        })
+    }
+
+    /// Spreadsheet-style address of a cell, e.g. `B3`
+    fn address(&self, id: CellId) -> String {
+        match self.position(id) {
+            Some((row, column)) => format!("{}{}", column_name(column), row + 1),
+            None => "?".to_string(),
+        }
+    }
+}
+
+/// Index of a column name: `A` is 0, `Z` is 25, `AA` is 26
+fn column_index(name: &str) -> usize {
+    name.bytes()
+        .fold(0, |index, b| index * 26 + usize::from(b - b'A') + 1)
+        - 1
+}
+
+/// Name of a column index
+fn column_name(mut index: usize) -> String {
+    let mut name = Vec::new();
+    loop {
+        name.push(b'A' + (index % 26) as u8);
+        if index < 26 {
+            break;
+        }
+        index = index / 26 - 1;
+    }
+    name.reverse();
+    String::from_utf8(name).unwrap()
+}
+
+fn parse_number(text: &str) -> Option<f64> {
+    if !NUMBER.is_match(text) {
+        return None;
+    }
+    text.replace(',', "").parse().ok()
+}
+
+/// Format a result without floating-point noise
+fn format_number(value: f64) -> String {
+    let rounded = (value * 1e10).round() / 1e10;
+    if rounded == 0.0 {
+        "0".to_string()
+    } else {
+        rounded.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extensions::table::model::Cell;
+
+    fn table(rows: &[&[&str]]) -> Table {
+        Table {
+            body: rows
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|content| Cell::new(content.to_string(), false))
+                        .collect()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_functions() {
+        let mut table = table(&[
+            &[
+                "2",
+                "=SUM(A1:A3)",
+                "=AVG(col)",
+                "=COUNT(A)",
+                "=MIN(A1:A3,-1)",
+                "=max(row)",
+            ],
+            &["3.5", "", "1", "", "", ""],
+            &["", "", "2", "", "", ""],
+        ]);
+        assert!(evaluate_formulas(&mut table).is_empty());
+        let row: Vec<_> = table.body[0].iter().map(|c| c.content.as_str()).collect();
+        assert_eq!(row, ["2", "5.5", "1.5", "2", "-1", "5.5"]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut table = table(&[
+            &["x", "=SUM(A1)", "=SUM(C2)", "=FOO(A1)"],
+            &["", "=SUM(Z9)", "=SUM(C1)", "=AVG(A2)"],
+        ]);
+        let diagnostics = evaluate_formulas(&mut table);
+        let markers: Vec<_> = table
+            .rows()
+            .flatten()
+            .filter(|c| c.formula.is_some())
+            .map(|c| c.content.as_str())
+            .collect();
+        assert_eq!(
+            markers,
+            [
+                "#VALUE!", "#CYCLE!", "#NAME?", "#REF!", "#CYCLE!", "#DIV/0!"
+            ]
+        );
+        let codes: Vec<_> = diagnostics.iter().map(|d| d.code).collect();
+        assert_eq!(
+            codes,
+            [
+                "table-formula-type",
+                "table-formula-cycle",
+                "table-formula-syntax",
+                "table-formula-reference",
+                "table-formula-division",
+            ]
+        );
+        assert!(diagnostics[1].message.contains("C1"));
+    }
+
+    #[test]
+    fn test_spans_and_headers() {
+        let mut table = table(&[&["Qty", "Note"], &["4", "x"], &["6", "=SUM(A)"]]);
+        table.body[0][0].is_header = true;
+        table.body[1][1].rowspan = 2;
+        assert!(evaluate_formulas(&mut table).is_empty());
+        // The formula sits in column C, next to the spanned B2
+        assert_eq!(table.body[2][1].content, "10");
+    }
+
+    #[test]
+    fn test_column_names() {
+        assert_eq!(column_index("AA"), 26);
+        assert_eq!(column_name(26), "AA");
+        assert_eq!(column_name(column_index("ZZ")), "ZZ");
+    }
+}
@@ -3,11 +3,21 @@
 //! Provides support for UMD-style tables with extended features:
 //! - Cell spanning: `|>` for colspan, `|^` for rowspan
 //! - Cell decorations: COLOR(), SIZE(), alignment prefixes
+//! - Opt-in formula cells: `=SUM(B2:B9)`
 //! - No mandatory header row (unlike GFM)
+//! - Cell content is Markdown, rendered by comrak with the rest of the document
 
 mod cell_spanning;
 mod decorations;
+mod formula;
 mod parser;
 
 // Re-export main API
-pub use parser::{extract_umd_tables, parse_table};
+pub(crate) use cell_spanning::normalize_section;
+pub(crate) use decorations::{emit_styles, parse_cell_content};
+pub use formula::evaluate_formulas;
+pub use parser::{
+    extract_umd_tables, extract_umd_tables_with_styles, parse_table, parse_table_with_styles,
+    restore_umd_tables,
+};
+pub(crate) use parser::{extract_umd_tables_into, placeholder, render_source_table};
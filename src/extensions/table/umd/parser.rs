@@ -2,29 +2,16 @@
 //!
 //! Parses UMD-style table syntax into structured cell data
 
-/// Cell information
-#[derive(Debug, Clone)]
-pub struct Cell {
-    pub content: String,
-    pub is_header: bool,
-    pub colspan: usize,
-    pub rowspan: usize,
-    pub classes: Vec<String>,
-    pub styles: Vec<String>,
-}
-
-impl Cell {
-    pub fn new(content: String, is_header: bool) -> Self {
-        Self {
-            content,
-            is_header,
-            colspan: 1,
-            rowspan: 1,
-            classes: Vec::new(),
-            styles: Vec::new(),
-        }
-    }
-}
+use crate::diagnostics::Diagnostic;
+use crate::extensions::markers;
+use crate::extensions::styles::Styles;
+use crate::extensions::table::export::TableRecorder;
+pub use crate::extensions::table::model::Cell;
+use crate::extensions::table::model::{Column, Table, inherit};
+use crate::extensions::table::render::{grid_columns, render_table};
+use crate::sanitizer::sanitize;
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
 
 /// Check if a table is UMD format or GFM format
 ///
@@ -84,6 +71,10 @@ pub fn is_umd_table(lines: &[&str]) -> bool {
 
 /// Parse a UMD table and convert to HTML
 ///
+/// Cell content is emitted as sanitized text; tables in a document are
+/// extracted with [`extract_umd_tables`] instead, so their cells go through
+/// the Markdown pipeline.
+///
 /// # Arguments
 ///
 /// * `table_text` - The table text (multiple lines starting with |)
@@ -92,6 +83,20 @@ pub fn is_umd_table(lines: &[&str]) -> bool {
 ///
 /// HTML table string
 pub fn parse_table(table_text: &str) -> String {
+    parse_table_with_styles(table_text, &mut Styles::default())
+}
+
+/// Parse a UMD table, validating custom cell style values
+///
+/// # Arguments
+///
+/// * `table_text` - The table text (multiple lines starting with |)
+/// * `styles` - Validator for custom cell `COLOR()`/`SIZE()` values
+///
+/// # Returns
+///
+/// HTML table string
+pub fn parse_table_with_styles(table_text: &str, styles: &mut Styles) -> String {
     let lines: Vec<&str> = table_text.lines().collect();
 
     if lines.is_empty() {
@@ -104,236 +109,357 @@ pub fn parse_table(table_text: &str) -> String {
         return table_text.to_string();
     }
 
-    // Check if first row has 'h' suffix to determine if it's a header row
-    let has_thead = lines
-        .first()
-        .map_or(false, |line| line.trim().ends_with("h"));
+    render_source_table(&build_table(&lines, styles, &mut Vec::new()))
+}
+
+/// Render a table whose cell content is still source text
+pub(crate) fn render_source_table(table: &Table) -> String {
+    let mut table = table.clone();
+    for cell in table.rows_mut().flatten() {
+        cell.content = sanitize(&cell.content).into_owned();
+    }
+    render_table(&table)
+}
+
+/// Kind of a table row, set by the PukiWiki row modifier after the last `|`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RowKind {
+    /// `|...|h` - row of `<thead>`
+    Header,
+    /// No modifier - row of `<tbody>`
+    Body,
+    /// `|...|f` - row of `<tfoot>`
+    Footer,
+    /// `|...|c` - format row with column defaults for the rows after it,
+    /// not rendered
+    Format,
+}
+
+/// Split the row modifier off a trimmed table line
+fn row_kind(line: &str) -> (RowKind, &str) {
+    let kind = match line.chars().last() {
+        Some('h') => RowKind::Header,
+        Some('f') => RowKind::Footer,
+        Some('c') => RowKind::Format,
+        _ => return (RowKind::Body, line),
+    };
+    let rest = &line[..line.len() - 1];
+    if rest.ends_with('|') {
+        (kind, rest)
+    } else {
+        (RowKind::Body, line)
+    }
+}
 
-    // Parse table rows
-    let mut rows: Vec<Vec<Cell>> = Vec::new();
+/// Build the table model from UMD table lines
+///
+/// Cell content is kept as source text with the decoration prefixes and
+/// spanning markers removed. Rows go to `<thead>`, `<tbody>` or `<tfoot>`
+/// by their modifier, keeping their order within each section; spans do
+/// not cross sections.
+///
+/// As in PukiWiki, a format row sets the column defaults of the rows after
+/// it, up to the next format row. The defaults are resolved into each
+/// cell by grid column before the style policy is applied, so a cell's own
+/// decoration replaces the default for the same CSS property.
+fn build_table(lines: &[&str], styles: &mut Styles, diagnostics: &mut Vec<Diagnostic>) -> Table {
+    // Add umd-table class to identify Universal Markdown tables
+    let mut table = Table {
+        classes: vec!["table".to_string(), "umd-table".to_string()],
+        ..Default::default()
+    };
+    let mut formats: Vec<Vec<Column>> = Vec::new();
+    // Index of the format row in effect for each row of each section
+    let mut row_formats: [Vec<Option<usize>>; 3] = Default::default();
 
-    for (row_idx, line) in lines.iter().enumerate() {
-        let mut line = line.trim();
+    for line in lines {
+        let line = line.trim();
         if line.is_empty() || !line.starts_with('|') {
             continue;
         }
 
-        // Remove 'h' suffix from first row if present
-        if row_idx == 0 && line.ends_with('h') {
-            line = &line[..line.len() - 1];
+        let (kind, line) = row_kind(line);
+        let cells = parse_cells(line, styles);
+        let format = formats.len().checked_sub(1);
+        match kind {
+            RowKind::Header => {
+                table.head.push(cells);
+                row_formats[0].push(format);
+            }
+            RowKind::Body => {
+                table.body.push(cells);
+                row_formats[1].push(format);
+            }
+            RowKind::Footer => {
+                table.foot.push(cells);
+                row_formats[2].push(format);
+            }
+            RowKind::Format => formats.push(format_columns(cells, styles)),
         }
+    }
 
-        // Parse cells more carefully to handle |> and |^ markers
-        let mut cells: Vec<Cell> = Vec::new();
-        let mut current_pos = 1; // Skip first |
-        let chars: Vec<char> = line.chars().collect();
-        let mut current_cell = String::new();
-
-        while current_pos < chars.len() {
-            if chars[current_pos] == '|' {
-                // Check if this is |> or |^ marker
-                if current_pos + 1 < chars.len() {
-                    let next_char = chars[current_pos + 1];
-                    if next_char == '>' || next_char == '^' {
-                        // This is a marker, include it in the cell
-                        current_cell.push('|');
-                        current_cell.push(next_char);
-                        current_pos += 2;
-                        continue;
-                    }
-                }
+    // Resolve spanning markers on a grid as wide as the widest row
+    let width = table.rows().map(Vec::len).max().unwrap_or(0);
+    let mut first_row = 0;
+    for rows in [&mut table.head, &mut table.body, &mut table.foot] {
+        let count = rows.len();
+        *rows = super::cell_spanning::normalize_section(
+            std::mem::take(rows),
+            width,
+            first_row,
+            diagnostics,
+        );
+        first_row += count;
+    }
 
-                // Regular cell separator
-                let content = current_cell.trim().to_string();
-                let mut cell = Cell::new(content, false);
-                super::decorations::parse_cell_content(&mut cell);
-                cells.push(cell);
-                current_cell.clear();
-                current_pos += 1;
-            } else {
-                current_cell.push(chars[current_pos]);
-                current_pos += 1;
+    let row_formats = row_formats.into_iter().flatten();
+    let columns = grid_columns(&table);
+    for ((row, columns), format) in table.rows_mut().zip(columns).zip(row_formats) {
+        let Some(format) = format.map(|index| &formats[index]) else {
+            continue;
+        };
+        for (cell, column) in row.iter_mut().zip(columns) {
+            if let Some(defaults) = format.get(column) {
+                inherit(&mut cell.classes, &mut cell.styles, defaults);
             }
         }
-
-        // Add last cell if exists
-        if !current_cell.trim().is_empty() || !cells.is_empty() {
-            let content = current_cell.trim().to_string();
-            let mut cell = Cell::new(content, false);
-            super::decorations::parse_cell_content(&mut cell);
-            cells.push(cell);
-        }
-
-        // Don't remove trailing empty cells - they may be part of colspan
-
-        rows.push(cells);
     }
+    super::decorations::emit_styles(&mut table, styles);
 
-    // Process cell spanning
-    super::cell_spanning::process_cell_spanning(&mut rows);
-
-    // Generate HTML with header information
-    generate_table_html_with_header(&rows, has_thead)
+    table
 }
 
-/// Generate HTML table from parsed cells with header information
-fn generate_table_html_with_header(rows: &[Vec<Cell>], has_thead: bool) -> String {
-    // Add umd-table class to identify Universal Markdown tables
-    let mut html = String::from(r#"<table class="table umd-table">"#);
-
-    if rows.is_empty() {
-        html.push_str("</table>");
-        return html;
+/// Parse the cells of a table line without its row modifier
+///
+/// Every `|` separates two cells; the outer pipes only delimit the row and
+/// `\|` is a literal pipe, as in GFM. A cell that is exactly `>` or `^` is
+/// a spanning marker, resolved by [`super::cell_spanning::normalize_section`].
+fn parse_cells(line: &str, styles: &mut Styles) -> Vec<Cell> {
+    let mut contents = vec![String::new()];
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                chars.next();
+                contents.last_mut().unwrap().push('|');
+            }
+            '|' => contents.push(String::new()),
+            _ => contents.last_mut().unwrap().push(c),
+        }
+    }
+    // Drop what lies outside the outer pipes
+    if line.starts_with('|') {
+        contents.remove(0);
+    }
+    if contents.len() > 1 && contents.last().is_some_and(|last| last.trim().is_empty()) {
+        contents.pop();
     }
 
-    // Generate <thead> if first row has 'h' suffix
-    if has_thead && !rows.is_empty() {
-        html.push_str("<thead>");
-        if let Some(header_row) = rows.first() {
-            html.push_str("<tr>");
-            for cell in header_row {
-                let tag = if cell.is_header { "th" } else { "td" };
-
-                // Build attributes
-                let mut attrs = Vec::new();
-
-                if !cell.classes.is_empty() {
-                    attrs.push(format!(r#"class="{}""#, cell.classes.join(" ")));
-                }
-
-                if !cell.styles.is_empty() {
-                    attrs.push(format!(r#"style="{}""#, cell.styles.join("; ")));
-                }
-
-                if cell.colspan > 1 {
-                    attrs.push(format!(r#"colspan="{}""#, cell.colspan));
-                }
-
-                if cell.rowspan > 1 {
-                    attrs.push(format!(r#"rowspan="{}""#, cell.rowspan));
-                }
+    contents
+        .into_iter()
+        .map(|content| {
+            let mut cell = Cell::new(content.trim().to_string(), false);
+            super::decorations::parse_cell_content(&mut cell, styles);
+            cell
+        })
+        .collect()
+}
 
-                let attrs_str = if attrs.is_empty() {
-                    String::new()
+/// Column defaults from the cells of a format row
+///
+/// Each cell takes the same prefixes as a regular cell (`COLOR()`,
+/// `SIZE()`, alignment); what is left is the column width, in pixels when
+/// it is a bare number (`CENTER:120`).
+fn format_columns(cells: Vec<Cell>, styles: &mut Styles) -> Vec<Column> {
+    cells
+        .into_iter()
+        .map(|cell| {
+            let mut column = Column {
+                classes: cell.classes,
+                styles: cell.styles,
+            };
+            let width = cell.content.trim();
+            if !width.is_empty() && !width.starts_with('|') {
+                let width = if width.chars().all(|c| c.is_ascii_digit()) {
+                    format!("{}px", width)
                 } else {
-                    format!(" {}", attrs.join(" "))
+                    width.to_string()
                 };
-
-                html.push_str(&format!("<{tag}{attrs_str}>{}</{tag}>", cell.content));
+                column.styles.extend(styles.validate("width", &width));
             }
-            html.push_str("</tr>");
-        }
-        html.push_str("</thead>");
-    }
-
-    // Generate <tbody> for remaining rows (or all rows if no thead)
-    let body_rows = if has_thead { &rows[1..] } else { rows };
+            column
+        })
+        .collect()
+}
 
-    if !body_rows.is_empty() {
-        html.push_str("<tbody>");
-        for row in body_rows {
-            html.push_str("<tr>");
-            for cell in row {
-                let tag = if cell.is_header { "th" } else { "td" };
+/// Detect and extract UMD tables from input text
+///
+/// Returns a tuple of (processed_text, tables). Each table is replaced by a
+/// single-column GFM table whose header is the table token and whose rows
+/// are the cell contents in document order, so comrak renders the cells
+/// with the rest of the document; [`restore_umd_tables`] puts the rendered
+/// cells back into the table.
+pub fn extract_umd_tables(input: &str) -> (String, Vec<Table>) {
+    extract_umd_tables_with_styles(input, &mut Styles::default())
+}
 
-                // Build attributes
-                let mut attrs = Vec::new();
+/// Detect and extract UMD tables, validating custom cell style values
+///
+/// Returns a tuple of (processed_text, tables); see [`extract_umd_tables`].
+/// Lines inside fenced code blocks are left untouched.
+pub fn extract_umd_tables_with_styles(input: &str, styles: &mut Styles) -> (String, Vec<Table>) {
+    let mut tables = Vec::new();
+    let result = extract_umd_tables_into(input, &mut tables, styles, &mut Vec::new());
+    (result, tables)
+}
 
-                if !cell.classes.is_empty() {
-                    attrs.push(format!(r#"class="{}""#, cell.classes.join(" ")));
-                }
+/// Extract UMD tables, appending them to `tables`
+///
+/// Placeholder tokens index into `tables`, which may already hold tables
+/// from other sources (see [`crate::extensions::table::csv`]). Repairs to
+/// malformed spans and ragged rows are reported to `diagnostics`.
+pub(crate) fn extract_umd_tables_into(
+    input: &str,
+    tables: &mut Vec<Table>,
+    styles: &mut Styles,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> String {
+    let mut result = String::with_capacity(input.len());
+    let mut table_lines: Vec<&str> = Vec::new();
+    let mut in_code_block = false;
+    let mut code_fence_marker = "";
 
-                if !cell.styles.is_empty() {
-                    attrs.push(format!(r#"style="{}""#, cell.styles.join("; ")));
-                }
+    // Find all potential tables (consecutive lines starting with |)
+    for line in input.split_inclusive('\n') {
+        let trimmed = line.trim();
 
-                if cell.colspan > 1 {
-                    attrs.push(format!(r#"colspan="{}""#, cell.colspan));
-                }
+        if !in_code_block && trimmed.starts_with('|') {
+            table_lines.push(line);
+            continue;
+        }
 
-                if cell.rowspan > 1 {
-                    attrs.push(format!(r#"rowspan="{}""#, cell.rowspan));
-                }
+        flush_table(&mut table_lines, &mut result, tables, styles, diagnostics);
 
-                let attrs_str = if attrs.is_empty() {
-                    String::new()
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            if !in_code_block {
+                in_code_block = true;
+                code_fence_marker = if trimmed.starts_with("```") {
+                    "```"
                 } else {
-                    format!(" {}", attrs.join(" "))
+                    "~~~"
                 };
-
-                html.push_str(&format!("<{tag}{attrs_str}>{}</{tag}>", cell.content));
+            } else if trimmed.contains(code_fence_marker) {
+                in_code_block = false;
             }
-            html.push_str("</tr>");
         }
-        html.push_str("</tbody>");
+        result.push_str(line);
     }
 
-    html.push_str("</table>");
-    html
+    // Handle table at end of file
+    flush_table(&mut table_lines, &mut result, tables, styles, diagnostics);
+
+    result
 }
 
-/// Detect and extract UMD tables from input text
-///
-/// Returns a tuple of (processed_text, table_map)
-/// where table_map contains markers and their corresponding HTML
-pub fn extract_umd_tables(input: &str) -> (String, Vec<(String, String)>) {
-    let mut result = input.to_string();
-    let mut tables = Vec::new();
-    let mut table_counter = 0;
+/// Replace the collected table lines with a placeholder if they form a UMD
+/// table, or copy them unchanged
+fn flush_table(
+    table_lines: &mut Vec<&str>,
+    result: &mut String,
+    tables: &mut Vec<Table>,
+    styles: &mut Styles,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    if table_lines.is_empty() {
+        return;
+    }
 
-    // Find all potential tables (consecutive lines starting with |)
-    let mut in_table = false;
-    let mut table_lines = Vec::new();
-    let lines: Vec<&str> = input.lines().collect();
+    let lines: Vec<&str> = table_lines.iter().map(|line| line.trim_end()).collect();
+    if is_umd_table(&lines) {
+        let table = build_table(&lines, styles, diagnostics);
+        result.push_str(&placeholder(tables.len(), &table));
+        tables.push(table);
+    } else {
+        result.extend(table_lines.iter().copied());
+    }
+    table_lines.clear();
+}
 
-    for line in lines.iter() {
-        let trimmed = line.trim();
+/// Source of the GFM table standing in for a UMD table
+///
+/// Tables built elsewhere (e.g. from CSV) can pass a table without cells;
+/// their content is kept as it is when restored.
+pub(crate) fn placeholder(index: usize, table: &Table) -> String {
+    // Blank lines keep comrak from merging the table with a paragraph
+    let mut source = format!("\n| {} |\n| --- |\n", markers::token(markers::TABLE, index));
+    for cell in table.rows().flatten() {
+        source.push_str(&format!("| {} |\n", cell.content.replace('|', "\\|")));
+    }
+    source.push('\n');
+    source
+}
 
-        if trimmed.starts_with('|') {
-            if !in_table {
-                in_table = true;
-                table_lines.clear();
+/// Regex matching a rendered placeholder: the table token and its cells
+static RENDERED_TABLE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(&format!(
+        "{}((?s:{}.*?{})*)",
+        markers::token_pattern(markers::TABLE),
+        markers::open(markers::TABLE_CELL),
+        markers::close(markers::TABLE_CELL)
+    ))
+    .unwrap()
+});
+
+/// Regex matching the content of one rendered cell
+static RENDERED_CELL: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(&format!(
+        "(?s){}(.*?){}",
+        markers::open(markers::TABLE_CELL),
+        markers::close(markers::TABLE_CELL)
+    ))
+    .unwrap()
+});
+
+/// Replace rendered placeholders with the UMD tables they stand for
+///
+/// # Arguments
+///
+/// * `html` - The HTML output from the Markdown parser
+/// * `tables` - Tables returned by [`extract_umd_tables`]; tables without an
+///   [`Table::id_prefix`] get `umd-table-{index}`
+/// * `recorder` - Recorder of the rendered tables, if they are exported
+///
+/// # Returns
+///
+/// HTML with the tables rendered, using the rendered cell content
+pub fn restore_umd_tables(
+    html: &str,
+    tables: &[Table],
+    mut recorder: Option<&mut TableRecorder>,
+) -> String {
+    RENDERED_TABLE
+        .replace_all(html, |caps: &Captures| {
+            let Some(index) = caps[1].parse::<usize>().ok().filter(|&i| i < tables.len()) else {
+                return caps[0].to_string();
+            };
+
+            let mut table = tables[index].clone();
+            if table.id_prefix.is_none() {
+                table.id_prefix = Some(format!("umd-table-{}", index));
             }
-            table_lines.push(*line);
-        } else {
-            if in_table && !table_lines.is_empty() {
-                // End of table
-                let table_text = table_lines.join("\n");
-
-                // Check if it's UMD format
-                let table_lines_refs: Vec<&str> = table_text.lines().collect();
-                if is_umd_table(&table_lines_refs) {
-                    // Parse and replace with marker
-                    let html = parse_table(&table_text);
-                    // Use a marker with newlines to make comrak treat it as block-level
-                    let marker = format!("\n\nUMD_TABLE_MARKER_{}_END\n\n", table_counter);
-                    tables.push((marker.clone(), html));
-
-                    // Replace in result
-                    result = result.replace(&table_text, &marker);
-                    table_counter += 1;
+            let contents = RENDERED_CELL.captures_iter(&caps[2]);
+            for (cell, content) in table.rows_mut().flatten().zip(contents) {
+                // Computed formula cells keep their value
+                if cell.formula.is_none() {
+                    cell.content = content[1].to_string();
                 }
-
-                table_lines.clear();
-                in_table = false;
             }
-        }
-    }
-
-    // Handle table at end of file
-    if in_table && !table_lines.is_empty() {
-        let table_text = table_lines.join("\n");
-        let table_lines_refs: Vec<&str> = table_text.lines().collect();
-        if is_umd_table(&table_lines_refs) {
-            let html = parse_table(&table_text);
-            // Use a marker with newlines
-            let marker = format!("\n\nUMD_TABLE_MARKER_{}_END\n\n", table_counter);
-            tables.push((marker.clone(), html));
-            result = result.replace(&table_text, &marker);
-        }
-    }
-
-    (result, tables)
+            match recorder.as_deref_mut() {
+                Some(recorder) => recorder.render(&table),
+                None => render_table(&table),
+            }
+        })
+        .into_owned()
 }
 
 #[cfg(test)]
@@ -372,8 +498,8 @@ mod tests {
         assert!(html.contains(r#"<table class="table umd-table">"#));
         assert!(html.contains("<thead>"));
         assert!(html.contains("<tbody>"));
-        assert!(html.contains("<th>A</th>"));
-        assert!(html.contains("<th>B</th>"));
+        assert!(html.contains(r#"<th scope="col">A</th>"#));
+        assert!(html.contains(r#"<th scope="col">B</th>"#));
         assert!(html.contains("<td>C</td>"));
         assert!(html.contains("<td>D</td>"));
     }
@@ -385,13 +511,13 @@ mod tests {
         let html = parse_table(input);
         eprintln!("Output: {}", html);
         assert!(html.contains("<tbody>"));
-        assert!(html.contains("<th>Row Header</th>"));
+        assert!(html.contains(r#"<th scope="row">Row Header</th>"#));
         assert!(html.contains("<td>Data</td>"));
     }
 
     #[test]
     fn test_parse_colspan() {
-        let input = "| A |> |h\n| C | D |";
+        let input = "|> | A |h\n| C | D |";
         let html = parse_table(input);
         eprintln!("Input: {}", input);
         eprintln!("Output: {}", html);
@@ -410,6 +536,76 @@ mod tests {
         assert!(html.contains("class="));
         assert!(html.contains("text-red"));
         // ~A becomes <th> with the color class
-        assert!(html.contains(r#"<th class="text-red">A</th>"#));
+        assert!(html.contains(r#"<th class="text-red" scope="col">A</th>"#));
+    }
+
+    #[test]
+    fn test_extract_and_restore() {
+        let (text, tables) = extract_umd_tables("Intro\n|> | **A** |\n| C | D |\nEnd");
+        assert_eq!(tables.len(), 1);
+        let token = markers::token(markers::TABLE, 0);
+        assert!(text.starts_with(&format!("Intro\n\n| {} |\n| --- |\n| **A** |\n", token)));
+        assert!(text.contains("| C |\n| D |\n"));
+        assert!(text.ends_with("|\n\nEnd"));
+
+        let cell = |html: &str| {
+            format!(
+                "{}{}{}",
+                markers::open(markers::TABLE_CELL),
+                html,
+                markers::close(markers::TABLE_CELL)
+            )
+        };
+        let rendered = format!(
+            "{}{}{}{}",
+            token,
+            cell("<strong>A</strong>"),
+            cell("C"),
+            cell("D")
+        );
+        let html = restore_umd_tables(&rendered, &tables, None);
+        assert!(html.contains(r#"<td colspan="2"><strong>A</strong></td>"#));
+        assert!(html.contains("<td>C</td><td>D</td>"));
+    }
+
+    #[test]
+    fn test_row_modifiers() {
+        let html = parse_table("| F |f\n| B1 |\n| H |h\n| B2 |");
+        assert!(html.starts_with(
+            r#"<table class="table umd-table"><thead><tr><td>H</td></tr></thead><tbody><tr><td>B1</td></tr><tr><td>B2</td></tr></tbody><tfoot><tr><td>F</td></tr></tfoot></table>"#
+        ));
+        // A trailing letter without a pipe is cell content
+        assert!(parse_table("| a | bath").contains("<td>bath</td>"));
+    }
+
+    #[test]
+    fn test_escaped_pipe() {
+        let html = parse_table("|a\\|b|c|\n|>|d|");
+        assert!(html.contains("<tr><td>a|b</td><td>c</td></tr>"));
+        assert!(html.contains(r#"<td colspan="2">d</td>"#));
+        // The pipe survives the Markdown pipeline
+        assert!(crate::parse("|a\\|b|c|").contains("<td>a|b</td>"));
+    }
+
+    #[test]
+    fn test_format_row() {
+        let html = parse_table("| LEFT: | CENTER:80 | COLOR(red): |c\n| a | b | RIGHT: c |");
+        assert!(!html.contains("CENTER:"));
+        assert!(html.contains(r#"<td class="text-start">a</td>"#));
+        assert!(html.contains(r#"<td class="text-center" style="width: 80px">b</td>"#));
+        // The cell's own alignment is kept, the color comes from the column
+        assert!(html.contains(r#"<td class="text-end text-red">c</td>"#));
+    }
+
+    #[test]
+    fn test_format_rows_apply_until_the_next() {
+        let html = parse_table("|CENTER:|RIGHT:|c\n|x|y|\n|LEFT:|LEFT:|c\n|p|q|");
+        assert!(html.contains(r#"<td class="text-center">x</td><td class="text-end">y</td>"#));
+        assert!(html.contains(r#"<td class="text-start">p</td><td class="text-start">q</td>"#));
+
+        // Rows before the first format row have no defaults
+        let html = parse_table("|a|\n|RIGHT:|c\n|b|");
+        assert!(html.contains("<td>a</td>"));
+        assert!(html.contains(r#"<td class="text-end">b</td>"#));
     }
 }
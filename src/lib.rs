@@ -40,12 +40,29 @@
 //! const html = parse_markdown('# Hello World');
 //! ```
 
+use std::collections::HashMap;
 use wasm_bindgen::prelude::*;
 
+pub mod aozora;
+pub mod diagnostics;
+pub mod entities;
 pub mod extensions;
 pub mod frontmatter;
+pub mod limits;
+pub mod link_policy;
+pub mod normalize;
+pub mod output_sanitizer;
 pub mod parser;
 pub mod sanitizer;
+pub mod security_report;
+
+pub use diagnostics::Diagnostic;
+pub use extensions::deferred::PendingPlugin;
+pub use extensions::plugin_syntax::{PluginCall, PluginKind, extract_plugin_calls};
+pub use extensions::table::export::{TableData, extract_tables};
+pub use limits::{LimitError, Limits};
+pub use link_policy::LinkPolicy;
+pub use security_report::{SecurityEvent, SecurityEventKind};
 
 /// Parse result with optional frontmatter and footnotes
 #[derive(Debug, Clone)]
@@ -56,6 +73,19 @@ pub struct ParseResult {
     pub frontmatter: Option<frontmatter::Frontmatter>,
     /// Footnotes HTML (if any footnotes are present)
     pub footnotes: Option<String>,
+    /// Plugin calls awaiting resolution (deferred mode only)
+    pub pending_plugins: Vec<PendingPlugin>,
+    /// Nonce of the plugin placeholders in `html` and `footnotes`
+    /// (deferred mode only)
+    pub plugin_nonce: Option<String>,
+    /// Problems found in the input, such as dropped style values
+    pub diagnostics: Vec<Diagnostic>,
+    /// CSS rules for the generated style classes
+    /// (`StylePolicy::Classes` only)
+    pub stylesheet: Option<String>,
+    /// Escaped tags, blocked URLs, rejected attributes and dropped style
+    /// values, with their source spans
+    pub security_report: Vec<SecurityEvent>,
 }
 
 /// Parse LukiWiki markup and convert to HTML
@@ -108,35 +138,269 @@ pub fn parse(input: &str) -> String {
 /// assert!(result.html.contains("<h1>"));
 /// ```
 pub fn parse_with_frontmatter(input: &str) -> ParseResult {
+    parse_with_options(input, &parser::ParserOptions::default())
+}
+
+/// Parse Universal Markdown with custom parser options
+///
+/// # Arguments
+///
+/// * `input` - The Universal Markdown source text
+/// * `options` - Parser configuration options
+///
+/// # Returns
+///
+/// ParseResult containing HTML, optional frontmatter and, in deferred
+/// plugin mode, the pending plugin calls
+///
+/// # Examples
+///
+/// ```
+/// use universal_markdown::parse_with_options;
+/// use universal_markdown::parser::ParserOptions;
+///
+/// let options = ParserOptions {
+///     deferred_plugins: true,
+///     ..Default::default()
+/// };
+/// let result = parse_with_options("@rss(https://example.com/feed)", &options);
+/// assert_eq!(result.pending_plugins[0].name, "rss");
+/// assert!(!result.html.contains("<template"));
+/// ```
+pub fn parse_with_options(input: &str, options: &parser::ParserOptions) -> ParseResult {
+    match try_parse_with_options(input, options) {
+        Ok(result) => result,
+        Err(error) => ParseResult {
+            html: escaped_source(input, options.limits.max_output_bytes),
+            frontmatter: None,
+            footnotes: None,
+            pending_plugins: Vec::new(),
+            plugin_nonce: None,
+            diagnostics: vec![error.to_diagnostic()],
+            stylesheet: None,
+            security_report: Vec::new(),
+        },
+    }
+}
+
+/// Parse Universal Markdown, failing when a resource limit is exceeded
+///
+/// Unlike [`parse_with_options`], which falls back to the escaped source
+/// text, this reports the exceeded limit as an error.
+///
+/// # Arguments
+///
+/// * `input` - The Universal Markdown source text
+/// * `options` - Parser configuration options, including the [`Limits`]
+///
+/// # Returns
+///
+/// The ParseResult, or the first limit exceeded
+///
+/// # Examples
+///
+/// ```
+/// use universal_markdown::parser::ParserOptions;
+/// use universal_markdown::{LimitError, Limits, try_parse_with_options};
+///
+/// let options = ParserOptions {
+///     limits: Limits {
+///         max_input_bytes: 8,
+///         ..Default::default()
+///     },
+///     ..Default::default()
+/// };
+/// assert!(try_parse_with_options("# Title", &options).is_ok());
+/// assert!(matches!(
+///     try_parse_with_options("# Long title", &options),
+///     Err(LimitError::InputTooLarge { size: 12, limit: 8 })
+/// ));
+/// ```
+pub fn try_parse_with_options(
+    input: &str,
+    options: &parser::ParserOptions,
+) -> Result<ParseResult, LimitError> {
+    options.limits.check_input(input)?;
+
+    let result = render(input, options, None);
+
+    let size = result.html.len() + result.footnotes.as_ref().map_or(0, String::len);
+    options.limits.check_output(size)?;
+
+    Ok(result)
+}
+
+/// Run the rendering pipeline, recording the rendered tables if `recorder`
+/// is given
+pub(crate) fn render(
+    input: &str,
+    options: &parser::ParserOptions,
+    mut recorder: Option<&mut extensions::table::export::TableRecorder>,
+) -> ParseResult {
     // Step 0: Extract frontmatter
     let (frontmatter_data, content) = frontmatter::extract_frontmatter(input);
 
+    // Remove token sentinels so placeholders cannot be forged from input
+    let content = extensions::markers::scrub(&content);
+
+    // Normalize to NFC and neutralize invisible or look-alike characters
+    let (content, mut diagnostics) = normalize::normalize(&content, options.bidi_policy);
+
     // Step 1: Pre-process Discord-style underline (__text__) to prevent CommonMark conversion
     let content = extensions::preprocessor::preprocess_discord_underline(&content);
 
     // Step 2: Pre-process to resolve syntax conflicts and extract custom header IDs
-    let (preprocessed, header_map) = extensions::conflict_resolver::preprocess_conflicts(&content);
+    let mut styles = extensions::styles::Styles::new(options.style_policy);
+    let (preprocessed, mut header_map) =
+        extensions::conflict_resolver::preprocess_conflicts_with_styles(&content, &mut styles);
+    diagnostics.append(&mut header_map.diagnostics);
+
+    // Header cell ids are numbered per document
+    for (index, table) in header_map.tables.iter_mut().enumerate() {
+        table.id_prefix = Some(format!("{}umd-table-{}", options.table_id_prefix, index));
+    }
+
+    // Step 2b: Compute formula cells of UMD tables (CSV tables are data only)
+    if options.table_formulas {
+        for table in &mut header_map.tables {
+            if table.classes.iter().any(|class| class == "umd-table") {
+                diagnostics.extend(extensions::table::umd::evaluate_formulas(table));
+            }
+        }
+    }
 
     // Step 3: Sanitize input
-    let sanitized = sanitizer::sanitize(&preprocessed);
+    let mut findings = security_report::Findings::default();
+    let sanitized = sanitizer::sanitize_recording(&preprocessed, &mut findings.escaped_tags);
 
     // Step 4: Parse with comrak-based parser
-    let options = parser::ParserOptions::default();
-    let html = parser::parse_to_html(&sanitized, &options);
+    let html = parser::parse_to_html_with_styles(
+        &sanitized,
+        options,
+        &mut styles,
+        recorder.as_deref_mut(),
+        &mut findings.blocked_urls,
+    );
 
     // Step 5: Restore Discord-style underline placeholders to <u> tags
     let html = extensions::preprocessor::postprocess_discord_underline(&html);
 
     // Step 6: Apply extended syntax and custom header IDs (includes post-processing)
-    let final_html = extensions::apply_extensions_with_headers(&html, &header_map);
+    let mut deferred = options
+        .deferred_plugins
+        .then(extensions::deferred::DeferredPlugins::new);
+    let final_html = extensions::apply_extensions_deferred(
+        &html,
+        &header_map,
+        deferred.as_mut(),
+        &mut styles,
+        recorder,
+        options.cjk,
+    );
+
+    // Step 7: Apply the link policy to every link and media URL
+    let mut final_html = options
+        .link_policy
+        .apply_recording(&final_html, &mut findings.blocked_urls);
+
+    // Step 8: Optionally clean the output with the UMD vocabulary
+    if options.sanitize_output {
+        final_html = output_sanitizer::sanitize_output(&final_html, &options.link_policy);
+    }
 
-    // Step 7: Extract footnotes from HTML
+    // Step 9: Extract footnotes from HTML
     let (body_html, footnotes_html) = extract_footnotes(&final_html);
 
+    let plugin_nonce = deferred.as_ref().map(|d| d.nonce().to_string());
+    let pending_plugins = deferred.map(|d| d.into_pending()).unwrap_or_default();
+    let stylesheet = styles.stylesheet();
+    findings.invalid_styles = styles.invalid_values().to_vec();
+    let security_report = security_report::audit(input, &options.link_policy, &findings);
+    diagnostics.extend(styles.into_diagnostics());
+
     ParseResult {
         html: body_html,
         frontmatter: frontmatter_data,
         footnotes: footnotes_html,
+        pending_plugins,
+        plugin_nonce,
+        diagnostics,
+        stylesheet,
+        security_report,
+    }
+}
+
+/// Escape the source text as preformatted text for the limit fallback,
+/// truncated to at most `limit` bytes of HTML
+fn escaped_source(input: &str, limit: usize) -> String {
+    const OPEN: &str = "<pre class=\"umd-source\">";
+    const CLOSE: &str = "</pre>";
+
+    let mut html = String::from(OPEN);
+    let budget = limit.saturating_sub(CLOSE.len());
+    let mut buffer = [0; 4];
+    for c in input.chars() {
+        let escaped = html_escape::encode_text(c.encode_utf8(&mut buffer));
+        if html.len() + escaped.len() > budget {
+            break;
+        }
+        html.push_str(&escaped);
+    }
+    html.push_str(CLOSE);
+    html
+}
+
+/// Splice resolved plugin output into a result rendered in deferred mode
+///
+/// Plugin output is trusted and inserted verbatim. Calls without an entry
+/// in `results` keep their placeholder and stay pending, so this can be
+/// called repeatedly as results arrive.
+///
+/// # Arguments
+///
+/// * `rendered` - A result from [`parse_with_options`] with `deferred_plugins` enabled
+/// * `results` - Plugin output HTML keyed by [`PendingPlugin::id`]
+///
+/// # Returns
+///
+/// A new ParseResult with resolved calls hydrated
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+/// use universal_markdown::parser::ParserOptions;
+/// use universal_markdown::{hydrate, parse_with_options};
+///
+/// let options = ParserOptions {
+///     deferred_plugins: true,
+///     ..Default::default()
+/// };
+/// let rendered = parse_with_options("Visits: &counter;", &options);
+/// let id = rendered.pending_plugins[0].id.clone();
+///
+/// let hydrated = hydrate(&rendered, &HashMap::from([(id, "42".to_string())]));
+/// assert!(hydrated.html.contains("Visits: 42"));
+/// assert!(hydrated.pending_plugins.is_empty());
+/// ```
+pub fn hydrate(rendered: &ParseResult, results: &HashMap<String, String>) -> ParseResult {
+    let Some(nonce) = rendered.plugin_nonce.as_deref() else {
+        return rendered.clone();
+    };
+
+    ParseResult {
+        html: extensions::deferred::hydrate_html(&rendered.html, nonce, results),
+        footnotes: rendered
+            .footnotes
+            .as_deref()
+            .map(|f| extensions::deferred::hydrate_html(f, nonce, results)),
+        pending_plugins: rendered
+            .pending_plugins
+            .iter()
+            .filter(|p| !results.contains_key(&p.id))
+            .cloned()
+            .collect(),
+        ..rendered.clone()
     }
 }
 
@@ -200,6 +464,20 @@ pub fn parse_wiki(input: &str) -> String {
     parse(input)
 }
 
+/// Extract the tables of a document as a JSON array (WASM entry point)
+///
+/// # Arguments
+///
+/// * `input` - The Universal Markdown source text
+///
+/// # Returns
+///
+/// JSON array of [`TableData`]
+#[wasm_bindgen]
+pub fn extract_tables_json(input: &str) -> String {
+    serde_json::to_string(&extract_tables(input)).expect("Table data is always serializable")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
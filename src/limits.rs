@@ -0,0 +1,378 @@
+//! Resource limits for untrusted input
+//!
+//! Every pipeline stage is a pass over the whole document, so very large or
+//! deeply nested input costs time and memory out of proportion to its
+//! value. [`Limits`] bounds the input before any work is done and the
+//! output afterwards:
+//!
+//! - Input size in bytes
+//! - Nesting depth of blockquotes, lists and plugin calls
+//! - Cells per table
+//! - Plugin calls per document
+//! - Output size in bytes
+//!
+//! [`crate::try_parse_with_options`] reports a violation as a
+//! [`LimitError`]; [`crate::parse_with_options`] degrades to the escaped
+//! source text and records the error as a diagnostic.
+
+use std::fmt;
+
+use crate::diagnostics::Diagnostic;
+use crate::extensions::plugin_syntax::extract_plugin_calls_up_to;
+use crate::extensions::table::csv;
+
+/// Resource limits applied by the parser
+///
+/// The defaults are generous for wiki pages; use [`Limits::unlimited`] to
+/// turn all checks off for trusted input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum input size in bytes
+    pub max_input_bytes: usize,
+    /// Maximum nesting depth of blockquotes, lists and plugin calls
+    pub max_nesting_depth: usize,
+    /// Maximum number of cells in a single table
+    pub max_table_cells: usize,
+    /// Maximum number of plugin calls in the document
+    pub max_plugin_calls: usize,
+    /// Maximum output size in bytes (body and footnotes)
+    pub max_output_bytes: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_input_bytes: 4 * 1024 * 1024,
+            max_nesting_depth: 32,
+            max_table_cells: 10_000,
+            max_plugin_calls: 1_000,
+            max_output_bytes: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// Kind of nested structure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NestingKind {
+    Blockquote,
+    List,
+    Plugin,
+}
+
+impl fmt::Display for NestingKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            NestingKind::Blockquote => "blockquote",
+            NestingKind::List => "list",
+            NestingKind::Plugin => "plugin",
+        })
+    }
+}
+
+/// A resource limit that was exceeded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LimitError {
+    InputTooLarge {
+        size: usize,
+        limit: usize,
+    },
+    NestingTooDeep {
+        kind: NestingKind,
+        depth: usize,
+        limit: usize,
+    },
+    TooManyTableCells {
+        count: usize,
+        limit: usize,
+    },
+    TooManyPluginCalls {
+        count: usize,
+        limit: usize,
+    },
+    OutputTooLarge {
+        size: usize,
+        limit: usize,
+    },
+}
+
+impl LimitError {
+    /// Stable identifier used for the diagnostic
+    pub fn code(&self) -> &'static str {
+        match self {
+            LimitError::InputTooLarge { .. } => "input-too-large",
+            LimitError::NestingTooDeep { .. } => "nesting-too-deep",
+            LimitError::TooManyTableCells { .. } => "too-many-table-cells",
+            LimitError::TooManyPluginCalls { .. } => "too-many-plugin-calls",
+            LimitError::OutputTooLarge { .. } => "output-too-large",
+        }
+    }
+
+    /// The error as a diagnostic
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic::new(self.code(), self.to_string())
+    }
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitError::InputTooLarge { size, limit } => {
+                write!(f, "Input is {} bytes (limit {})", size, limit)
+            }
+            LimitError::NestingTooDeep { kind, depth, limit } => {
+                write!(f, "{} nesting depth is {} (limit {})", kind, depth, limit)
+            }
+            LimitError::TooManyTableCells { count, limit } => {
+                write!(f, "Table has {} cells (limit {})", count, limit)
+            }
+            LimitError::TooManyPluginCalls { count, limit } => {
+                write!(f, "Document has {} plugin calls (limit {})", count, limit)
+            }
+            LimitError::OutputTooLarge { size, limit } => {
+                write!(f, "Output is {} bytes (limit {})", size, limit)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LimitError {}
+
+impl Limits {
+    /// Limits that never trigger
+    pub fn unlimited() -> Self {
+        Self {
+            max_input_bytes: usize::MAX,
+            max_nesting_depth: usize::MAX,
+            max_table_cells: usize::MAX,
+            max_plugin_calls: usize::MAX,
+            max_output_bytes: usize::MAX,
+        }
+    }
+
+    /// Check the input against every limit except the output size
+    ///
+    /// Nesting depth is measured on the source: `>` prefixes for
+    /// blockquotes, list markers and indentation (two spaces per level)
+    /// for lists, and enclosing calls for plugins.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The Universal Markdown source text
+    ///
+    /// # Returns
+    ///
+    /// The first limit exceeded, if any
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use universal_markdown::limits::{LimitError, Limits};
+    ///
+    /// let limits = Limits {
+    ///     max_table_cells: 3,
+    ///     ..Default::default()
+    /// };
+    /// assert!(limits.check_input("| a | b |").is_ok());
+    /// assert_eq!(
+    ///     limits.check_input("| a | b |\n| c | d |"),
+    ///     Err(LimitError::TooManyTableCells { count: 4, limit: 3 })
+    /// );
+    /// ```
+    pub fn check_input(&self, input: &str) -> Result<(), LimitError> {
+        if input.len() > self.max_input_bytes {
+            return Err(LimitError::InputTooLarge {
+                size: input.len(),
+                limit: self.max_input_bytes,
+            });
+        }
+
+        let mut table_cells = 0;
+        for line in input.lines() {
+            let depth = blockquote_depth(line);
+            if depth > self.max_nesting_depth {
+                return Err(self.nesting_error(NestingKind::Blockquote, depth));
+            }
+
+            let depth = list_depth(line);
+            if depth > self.max_nesting_depth {
+                return Err(self.nesting_error(NestingKind::List, depth));
+            }
+
+            // Consecutive lines starting with | form one table
+            let trimmed = line.trim();
+            if trimmed.starts_with('|') {
+                table_cells += trimmed.matches('|').count().saturating_sub(1).max(1);
+                if table_cells > self.max_table_cells {
+                    return Err(LimitError::TooManyTableCells {
+                        count: table_cells,
+                        limit: self.max_table_cells,
+                    });
+                }
+            } else {
+                table_cells = 0;
+            }
+        }
+
+        // CSV/TSV blocks are tables too, one cell per field
+        if self.max_table_cells != usize::MAX
+            && let Some(count) = csv::table_cell_counts(input)
+                .into_iter()
+                .find(|&count| count > self.max_table_cells)
+        {
+            return Err(LimitError::TooManyTableCells {
+                count,
+                limit: self.max_table_cells,
+            });
+        }
+
+        // Plugin calls are only detected when a limit could be exceeded
+        if self.max_plugin_calls == usize::MAX && self.max_nesting_depth == usize::MAX {
+            return Ok(());
+        }
+        // Stop collecting as soon as the call limit is exceeded
+        let calls = extract_plugin_calls_up_to(input, self.max_plugin_calls.saturating_add(1));
+        if calls.len() > self.max_plugin_calls {
+            return Err(LimitError::TooManyPluginCalls {
+                count: calls.len(),
+                limit: self.max_plugin_calls,
+            });
+        }
+        if let Some(depth) = calls.iter().map(|c| c.path.len() + 1).max()
+            && depth > self.max_nesting_depth
+        {
+            return Err(self.nesting_error(NestingKind::Plugin, depth));
+        }
+
+        Ok(())
+    }
+
+    /// Check the size of the rendered output
+    pub fn check_output(&self, size: usize) -> Result<(), LimitError> {
+        if size > self.max_output_bytes {
+            return Err(LimitError::OutputTooLarge {
+                size,
+                limit: self.max_output_bytes,
+            });
+        }
+        Ok(())
+    }
+
+    fn nesting_error(&self, kind: NestingKind, depth: usize) -> LimitError {
+        LimitError::NestingTooDeep {
+            kind,
+            depth,
+            limit: self.max_nesting_depth,
+        }
+    }
+}
+
+/// Number of `>` prefixes of a line
+fn blockquote_depth(line: &str) -> usize {
+    line.trim_start()
+        .chars()
+        .take_while(|&c| c == '>' || c == ' ' || c == '\t')
+        .filter(|&c| c == '>')
+        .count()
+}
+
+/// Estimated list nesting of a line: indentation levels plus the number of
+/// list markers it starts with (`- - 1. item` opens three lists)
+fn list_depth(line: &str) -> usize {
+    let indent: usize = line
+        .chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum();
+
+    let mut rest = line.trim_start();
+    let mut markers = 0;
+    loop {
+        let marker_len = match rest.as_bytes().first() {
+            Some(b'-' | b'*' | b'+') => 1,
+            Some(b'0'..=b'9') => {
+                let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+                match rest.as_bytes().get(digits) {
+                    Some(b'.' | b')') => digits + 1,
+                    _ => break,
+                }
+            }
+            _ => break,
+        };
+        match rest.as_bytes().get(marker_len) {
+            Some(b' ' | b'\t') => {
+                markers += 1;
+                rest = rest[marker_len..].trim_start();
+            }
+            _ => break,
+        }
+    }
+
+    if markers == 0 {
+        0
+    } else {
+        indent / 2 + markers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_size() {
+        let limits = Limits {
+            max_input_bytes: 4,
+            ..Default::default()
+        };
+        assert!(limits.check_input("abcd").is_ok());
+        assert_eq!(
+            limits.check_input("abcde"),
+            Err(LimitError::InputTooLarge { size: 5, limit: 4 })
+        );
+    }
+
+    #[test]
+    fn test_nesting_depth() {
+        assert_eq!(blockquote_depth("> > >x"), 3);
+        assert_eq!(blockquote_depth("a > b"), 0);
+        assert_eq!(list_depth("- - 1. item"), 3);
+        assert_eq!(list_depth("    - item"), 3);
+        assert_eq!(list_depth("-item"), 0);
+        assert_eq!(list_depth("2024. year"), 1);
+
+        let limits = Limits {
+            max_nesting_depth: 2,
+            ..Default::default()
+        };
+        assert!(limits.check_input("> > a\n- - b\n&a{&b;};").is_ok());
+        assert_eq!(
+            limits.check_input("- - - b"),
+            Err(limits.nesting_error(NestingKind::List, 3))
+        );
+        assert_eq!(
+            limits.check_input("&a{&b{&c;};};"),
+            Err(limits.nesting_error(NestingKind::Plugin, 3))
+        );
+    }
+
+    #[test]
+    fn test_plugin_calls() {
+        let limits = Limits {
+            max_plugin_calls: 2,
+            ..Default::default()
+        };
+        assert!(limits.check_input("&a; &b;").is_ok());
+        assert_eq!(
+            limits.check_input("&a; &b; @c()").map_err(|e| e.code()),
+            Err("too-many-plugin-calls")
+        );
+    }
+
+    #[test]
+    fn test_unlimited() {
+        let input = format!("{}x", "> ".repeat(100));
+        assert!(Limits::default().check_input(&input).is_err());
+        assert!(Limits::unlimited().check_input(&input).is_ok());
+        assert!(Limits::unlimited().check_output(usize::MAX).is_ok());
+    }
+}
@@ -0,0 +1,443 @@
+//! Link policy for URLs in rendered HTML
+//!
+//! Every `href` of an `<a>` element and every `src`/`poster` of a media
+//! element in the output is checked against a [`LinkPolicy`]. This covers
+//! Markdown links and images, autolinks, badge links and any other link
+//! produced by extensions, since the policy is applied to the final HTML.
+//!
+//! URLs are normalized the way a browser reads them before the scheme is
+//! checked: entities are decoded and ASCII whitespace and control characters
+//! are removed, so `java&#09;script:` is recognized as `javascript:`.
+
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+
+/// Replacement for URLs rejected by the policy
+pub const BLOCKED_URL: &str = "#blocked-url";
+
+// Opening tags whose URL attributes are checked
+static URL_TAG: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"<(a|img|video|audio|source)(\s[^>]*?)?(\s*/?)>").unwrap());
+
+// Attributes of an opening tag: name="value"
+static ATTRIBUTE: Lazy<Regex> = Lazy::new(|| Regex::new(r#"([\w-]+)="([^"]*)""#).unwrap());
+
+// Image types allowed as `data:` URLs in media sources (no SVG, which can script)
+const DATA_IMAGE_TYPES: &[&str] = &[
+    "image/png",
+    "image/gif",
+    "image/jpeg",
+    "image/jpg",
+    "image/webp",
+    "image/avif",
+];
+
+/// Which URL schemes are accepted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemePolicy {
+    /// Only these schemes are accepted (relative URLs are always accepted)
+    Allow(Vec<String>),
+    /// Every scheme except these is accepted
+    Block(Vec<String>),
+}
+
+impl SchemePolicy {
+    fn accepts(&self, scheme: &str) -> bool {
+        match self {
+            SchemePolicy::Allow(schemes) => schemes.iter().any(|s| s.eq_ignore_ascii_case(scheme)),
+            SchemePolicy::Block(schemes) => !schemes.iter().any(|s| s.eq_ignore_ascii_case(scheme)),
+        }
+    }
+}
+
+/// Rules applied to link and media URLs
+///
+/// # Examples
+///
+/// ```
+/// use universal_markdown::link_policy::{LinkPolicy, SchemePolicy};
+///
+/// let policy = LinkPolicy {
+///     schemes: SchemePolicy::Allow(vec!["https".into(), "mailto".into()]),
+///     internal_hosts: vec!["example.com".into()],
+///     external_target: Some("_blank".into()),
+///     ..Default::default()
+/// };
+/// let html = policy.apply(r#"<a href="https://other.org/">x</a>"#);
+/// assert_eq!(
+///     html,
+///     r#"<a href="https://other.org/" rel="nofollow ugc noopener" target="_blank">x</a>"#
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkPolicy {
+    /// Scheme allowlist or blocklist
+    pub schemes: SchemePolicy,
+    /// Accept `data:image/*` (raster types only) in media sources
+    pub allow_data_images: bool,
+    /// Hosts treated as internal; subdomains of an entry match too
+    pub internal_hosts: Vec<String>,
+    /// `rel` for external links
+    pub external_rel: Option<String>,
+    /// `target` for external links
+    pub external_target: Option<String>,
+    /// Class added to external links
+    pub external_class: Option<String>,
+}
+
+impl Default for LinkPolicy {
+    fn default() -> Self {
+        Self {
+            schemes: SchemePolicy::Block(
+                ["javascript", "data", "vbscript", "file"]
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect(),
+            ),
+            allow_data_images: false,
+            internal_hosts: Vec::new(),
+            external_rel: Some("nofollow ugc noopener".to_string()),
+            external_target: None,
+            external_class: None,
+        }
+    }
+}
+
+impl LinkPolicy {
+    /// Check a link URL against the policy
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL as written (entities allowed)
+    ///
+    /// # Returns
+    ///
+    /// The URL unchanged, or [`BLOCKED_URL`] if it is rejected
+    pub fn check_link<'a>(&self, url: &'a str) -> Cow<'a, str> {
+        self.check(url, false)
+    }
+
+    /// Check a media source URL against the policy
+    ///
+    /// Like [`check_link`](Self::check_link), but also accepts raster
+    /// `data:image/*` URLs when [`allow_data_images`](Self::allow_data_images) is set.
+    pub fn check_media<'a>(&self, url: &'a str) -> Cow<'a, str> {
+        self.check(url, true)
+    }
+
+    fn check<'a>(&self, url: &'a str, media: bool) -> Cow<'a, str> {
+        let normalized = normalize_url(url);
+        let Some(scheme) = url_scheme(&normalized) else {
+            // Relative URL
+            return Cow::Borrowed(url);
+        };
+
+        if media && self.allow_data_images && scheme == "data" && is_data_image(&normalized) {
+            return Cow::Borrowed(url);
+        }
+
+        if self.schemes.accepts(&scheme) {
+            Cow::Borrowed(url)
+        } else {
+            Cow::Borrowed(BLOCKED_URL)
+        }
+    }
+
+    /// Whether a link URL points outside the internal hosts
+    ///
+    /// Only `http`, `https` and protocol-relative URLs can be external.
+    pub fn is_external(&self, url: &str) -> bool {
+        let normalized = normalize_url(url);
+        let rest = match url_scheme(&normalized).as_deref() {
+            Some("http") | Some("https") => normalized.split_once(':').map_or("", |(_, r)| r),
+            Some(_) => return false,
+            None => normalized.as_str(),
+        };
+
+        let Some(authority) = rest.strip_prefix("//") else {
+            return false;
+        };
+        let host = authority
+            .split(['/', '?', '#'])
+            .next()
+            .unwrap_or("")
+            .rsplit('@')
+            .next()
+            .unwrap_or("");
+        let host = host
+            .rsplit_once(':')
+            .filter(|(_, port)| port.chars().all(|c| c.is_ascii_digit()))
+            .map_or(host, |(h, _)| h)
+            .trim_end_matches('.');
+
+        !self.internal_hosts.iter().any(|internal| {
+            let internal = internal.trim_end_matches('.');
+            host.eq_ignore_ascii_case(internal)
+                || host
+                    .to_ascii_lowercase()
+                    .ends_with(&format!(".{}", internal.to_ascii_lowercase()))
+        })
+    }
+
+    /// Apply the policy to every link and media element in `html`
+    ///
+    /// Rejected URLs are replaced with [`BLOCKED_URL`]. External links get
+    /// the configured `rel`, `target` and class.
+    ///
+    /// # Arguments
+    ///
+    /// * `html` - Rendered HTML
+    ///
+    /// # Returns
+    ///
+    /// HTML with the policy applied
+    pub fn apply(&self, html: &str) -> String {
+        self.apply_recording(html, &mut Vec::new())
+    }
+
+    /// Apply the policy, recording each rejected URL
+    ///
+    /// `blocked` receives the rule (`link-policy` or `media-policy`) and
+    /// the attribute value of every URL replaced with [`BLOCKED_URL`], in
+    /// document order.
+    pub(crate) fn apply_recording(
+        &self,
+        html: &str,
+        blocked: &mut Vec<(&'static str, String)>,
+    ) -> String {
+        URL_TAG
+            .replace_all(html, |caps: &Captures| {
+                let tag = &caps[1];
+                let attrs = caps.get(2).map_or("", |m| m.as_str());
+                let close = &caps[3];
+                format!(
+                    "<{}{}{}>",
+                    tag,
+                    self.apply_to_attributes(tag, attrs, blocked),
+                    close
+                )
+            })
+            .to_string()
+    }
+
+    fn apply_to_attributes(
+        &self,
+        tag: &str,
+        attrs: &str,
+        blocked: &mut Vec<(&'static str, String)>,
+    ) -> String {
+        let mut external = false;
+        let mut result = ATTRIBUTE
+            .replace_all(attrs, |caps: &Captures| {
+                let name = &caps[1];
+                let value = &caps[2];
+                let (checked, rule) = match (tag, name) {
+                    ("a", "href") => {
+                        let checked = self.check_link(value);
+                        external = checked != BLOCKED_URL && self.is_external(value);
+                        (checked, "link-policy")
+                    }
+                    ("img" | "video" | "audio" | "source", "src") | ("video", "poster") => {
+                        (self.check_media(value), "media-policy")
+                    }
+                    _ => return caps[0].to_string(),
+                };
+                if checked == BLOCKED_URL && value != BLOCKED_URL {
+                    blocked.push((rule, value.to_string()));
+                }
+                format!("{}=\"{}\"", name, checked)
+            })
+            .to_string();
+
+        if !external {
+            return result;
+        }
+
+        if let Some(rel) = &self.external_rel {
+            set_attribute(&mut result, "rel", rel);
+        }
+        if let Some(target) = &self.external_target {
+            set_attribute(&mut result, "target", target);
+        }
+        if let Some(class) = &self.external_class {
+            add_class(&mut result, class);
+        }
+        result
+    }
+}
+
+/// Set an attribute, replacing an existing value
+fn set_attribute(attrs: &mut String, name: &str, value: &str) {
+    let value = html_escape::encode_double_quoted_attribute(value);
+    let existing = Regex::new(&format!(r#"\s{}="[^"]*""#, regex::escape(name))).unwrap();
+    if existing.is_match(attrs) {
+        *attrs = existing
+            .replace(attrs, format!(" {}=\"{}\"", name, value).as_str())
+            .to_string();
+    } else {
+        attrs.push_str(&format!(" {}=\"{}\"", name, value));
+    }
+}
+
+/// Add a class, keeping existing classes
+fn add_class(attrs: &mut String, class: &str) {
+    let class = html_escape::encode_double_quoted_attribute(class);
+    if let Some(start) = attrs.find(" class=\"") {
+        let insert_at = start + " class=\"".len();
+        let end = attrs[insert_at..]
+            .find('"')
+            .map_or(insert_at, |e| insert_at + e);
+        let separator = if end > insert_at { " " } else { "" };
+        attrs.insert_str(end, &format!("{}{}", separator, class));
+    } else {
+        attrs.push_str(&format!(" class=\"{}\"", class));
+    }
+}
+
+/// Normalize a URL the way a browser reads it from an attribute
+///
+/// Entities are decoded (repeatedly, so double-encoded input cannot slip
+/// through), ASCII whitespace and control characters are removed and the
+/// result is lowercased.
+fn normalize_url(url: &str) -> String {
+    let mut decoded = url.to_string();
+    for _ in 0..3 {
+        let next = html_escape::decode_html_entities(&decoded).to_string();
+        if next == decoded {
+            break;
+        }
+        decoded = next;
+    }
+
+    decoded
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace() && !c.is_control())
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+/// Extract the scheme of a normalized URL, if it has one
+fn url_scheme(normalized: &str) -> Option<String> {
+    let (scheme, _) = normalized.split_once(':')?;
+    let mut chars = scheme.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    valid.then(|| scheme.to_string())
+}
+
+/// Whether a normalized `data:` URL holds an allowed raster image type
+fn is_data_image(normalized: &str) -> bool {
+    let media_type = normalized
+        .trim_start_matches("data:")
+        .split([';', ','])
+        .next()
+        .unwrap_or("");
+    DATA_IMAGE_TYPES.contains(&media_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocks_obfuscated_schemes() {
+        let policy = LinkPolicy::default();
+        for url in [
+            "javascript:alert(1)",
+            "java&#09;script:alert(1)",
+            "JAVA&#x53;CRIPT:alert(1)",
+            " \u{1}javascript:alert(1)",
+            "&#106;avascript:alert(1)",
+            "javascript&colon;alert(1)",
+            "java&amp;#09;script:alert(1)",
+            "vbscript:msgbox",
+            "data:text/html,x",
+        ] {
+            assert_eq!(policy.check_link(url), BLOCKED_URL, "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_accepts_safe_urls() {
+        let policy = LinkPolicy::default();
+        for url in [
+            "https://example.com",
+            "/path",
+            "#h-1",
+            "mailto:a@b.c",
+            "spotify:track:1",
+        ] {
+            assert_eq!(policy.check_link(url), url);
+        }
+    }
+
+    #[test]
+    fn test_allowlist() {
+        let policy = LinkPolicy {
+            schemes: SchemePolicy::Allow(vec!["https".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(
+            policy.check_link("https://example.com"),
+            "https://example.com"
+        );
+        assert_eq!(policy.check_link("ftp://example.com"), BLOCKED_URL);
+        assert_eq!(policy.check_link("relative/path"), "relative/path");
+    }
+
+    #[test]
+    fn test_data_images() {
+        let policy = LinkPolicy {
+            allow_data_images: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            policy.check_media("data:image/png;base64,AA"),
+            "data:image/png;base64,AA"
+        );
+        assert_eq!(policy.check_media("data:image/svg+xml,<svg>"), BLOCKED_URL);
+        assert_eq!(policy.check_link("data:image/png;base64,AA"), BLOCKED_URL);
+        assert_eq!(
+            LinkPolicy::default().check_media("data:image/png;base64,AA"),
+            BLOCKED_URL
+        );
+    }
+
+    #[test]
+    fn test_internal_hosts() {
+        let policy = LinkPolicy {
+            internal_hosts: vec!["example.com".to_string()],
+            ..Default::default()
+        };
+        assert!(!policy.is_external("https://example.com/a"));
+        assert!(!policy.is_external("https://docs.example.com:8080/a"));
+        assert!(!policy.is_external("/relative"));
+        assert!(!policy.is_external("mailto:a@b.c"));
+        assert!(policy.is_external("https://example.com.evil.org/"));
+        assert!(policy.is_external("//evil.org/"));
+        assert!(policy.is_external("https://example.com@evil.org/"));
+    }
+
+    #[test]
+    fn test_apply_external_link() {
+        let policy = LinkPolicy {
+            external_target: Some("_blank".to_string()),
+            external_class: Some("link-external".to_string()),
+            ..Default::default()
+        };
+        let html =
+            policy.apply(r#"<a href="https://e.com" class="badge">x</a> <a href="/in">y</a>"#);
+        assert_eq!(
+            html,
+            r#"<a href="https://e.com" class="badge link-external" rel="nofollow ugc noopener" target="_blank">x</a> <a href="/in">y</a>"#
+        );
+    }
+
+    #[test]
+    fn test_apply_media() {
+        let policy = LinkPolicy::default();
+        let html = policy.apply(r#"<img src="javascript:x" alt="a" />"#);
+        assert_eq!(html, r##"<img src="#blocked-url" alt="a" />"##);
+    }
+}
@@ -0,0 +1,254 @@
+//! Unicode normalization of the input
+//!
+//! Runs before sanitization and guards against text that renders
+//! differently from what it contains:
+//!
+//! - Text is normalized to NFC, so canonically equivalent input renders and
+//!   compares the same
+//! - Bidi embedding, override and isolate controls ("Trojan Source") can be
+//!   kept, stripped or made visible; those inside `&bdo` / `&bdi` are the
+//!   author's explicit choice and are always kept
+//! - Invisible characters (zero-width spaces and joiners, bidi controls)
+//!   are removed from URLs
+//! - Link hosts that mix scripts or imitate an ASCII host with look-alike
+//!   characters are reported
+//!
+//! Every change and finding is recorded as a [`Diagnostic`].
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use unicode_normalization::{UnicodeNormalization, is_nfc};
+use unicode_security::{MixedScript, skeleton};
+
+use crate::diagnostics::Diagnostic;
+
+/// Handling of bidi control characters outside `&bdo` / `&bdi`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BidiPolicy {
+    /// Leave them in place (still reported)
+    #[default]
+    Keep,
+    /// Remove them
+    Strip,
+    /// Replace them with a visible `[U+XXXX]` marker
+    Visualize,
+}
+
+// Explicit directional embeddings, overrides and isolates
+fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}')
+}
+
+// Characters with no visible rendering that never belong in a URL
+fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00AD}' | '\u{200B}'..='\u{200F}' | '\u{2060}' | '\u{FEFF}'
+    ) || is_bidi_control(c)
+}
+
+// Explicit bidi decorations, whose content is left untouched
+static BIDI_DECORATION: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"&bdo\([^)]*\)\{[^}]*\};|&bdi(?:\([^)]*\))?\{[^}]*\};|&bdi\([^)]*\);").unwrap());
+
+// Absolute URLs and Markdown link destinations
+static URL: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?i)\b[a-z][a-z0-9+.-]*://[^\s<>"'()\[\]]+|\]\(([^)\s]+)"#).unwrap()
+});
+
+// Host part of an absolute URL
+static HOST: Lazy<Regex> = Lazy::new(|| Regex::new(r"://(?:[^/?#@]*@)?([^/?#:]+)").unwrap());
+
+/// Normalize the input text
+///
+/// # Arguments
+///
+/// * `input` - The Universal Markdown source text
+/// * `bidi` - What to do with bidi control characters
+///
+/// # Returns
+///
+/// A tuple of (normalized text, diagnostics)
+///
+/// # Examples
+///
+/// ```
+/// use universal_markdown::normalize::{BidiPolicy, normalize};
+///
+/// let (text, diagnostics) = normalize("abc\u{202E}def", BidiPolicy::Strip);
+/// assert_eq!(text, "abcdef");
+/// assert_eq!(diagnostics[0].code, "bidi-control");
+///
+/// // Combining sequences are composed (NFC)
+/// let (text, _) = normalize("e\u{0301}", BidiPolicy::Keep);
+/// assert_eq!(text, "\u{00E9}");
+/// ```
+pub fn normalize(input: &str, bidi: BidiPolicy) -> (String, Vec<Diagnostic>) {
+    let mut diagnostics = Vec::new();
+
+    let text = if is_nfc(input) {
+        input.to_string()
+    } else {
+        input.nfc().collect()
+    };
+
+    let text = clean_urls(&text, &mut diagnostics);
+    let text = handle_bidi_controls(&text, bidi, &mut diagnostics);
+
+    (text, diagnostics)
+}
+
+/// Remove invisible characters from URLs and report confusable hosts
+fn clean_urls(text: &str, diagnostics: &mut Vec<Diagnostic>) -> String {
+    URL.replace_all(text, |caps: &regex::Captures| {
+        let url = &caps[0];
+        let cleaned: String = url.chars().filter(|&c| !is_invisible(c)).collect();
+        if cleaned.len() != url.len() {
+            diagnostics.push(Diagnostic::new(
+                "invisible-in-url",
+                format!("Invisible characters removed from URL: {}", cleaned),
+            ));
+        }
+
+        if let Some(host) = HOST.captures(&cleaned).map(|c| c[1].to_string())
+            && let Some(label) = host.split('.').find(|label| is_confusable_label(label))
+        {
+            diagnostics.push(Diagnostic::new(
+                "confusable-host",
+                format!("Host {} contains look-alike characters ({})", host, label),
+            ));
+        }
+
+        cleaned
+    })
+    .into_owned()
+}
+
+/// Check whether a host label may imitate another one
+///
+/// ASCII labels are fine. A non-ASCII label is suspicious when it mixes
+/// scripts (`pаypal` with a Cyrillic `а`) or when all of its characters
+/// have ASCII look-alikes (`раура` in Cyrillic).
+fn is_confusable_label(label: &str) -> bool {
+    if label.is_ascii() {
+        return false;
+    }
+    !label.is_single_script() || skeleton(label).all(|c| c.is_ascii())
+}
+
+/// Apply the bidi policy outside `&bdo` / `&bdi`
+fn handle_bidi_controls(
+    text: &str,
+    policy: BidiPolicy,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> String {
+    if !text.chars().any(is_bidi_control) {
+        return text.to_string();
+    }
+
+    let protected: Vec<(usize, usize)> = BIDI_DECORATION
+        .find_iter(text)
+        .map(|m| (m.start(), m.end()))
+        .collect();
+
+    let mut result = String::with_capacity(text.len());
+    for (pos, c) in text.char_indices() {
+        if !is_bidi_control(c) || protected.iter().any(|&(s, e)| s <= pos && pos < e) {
+            result.push(c);
+            continue;
+        }
+
+        let action = match policy {
+            BidiPolicy::Keep => {
+                result.push(c);
+                "found"
+            }
+            BidiPolicy::Strip => "removed",
+            BidiPolicy::Visualize => {
+                result.push_str(&format!("[U+{:04X}]", c as u32));
+                "made visible"
+            }
+        };
+        diagnostics.push(Diagnostic::new(
+            "bidi-control",
+            format!("Bidi control character U+{:04X} {}", c as u32, action),
+        ));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<&str> {
+        diagnostics.iter().map(|d| d.code).collect()
+    }
+
+    #[test]
+    fn test_nfc() {
+        let (text, diagnostics) = normalize("Cafe\u{0301} \u{30AB}\u{3099}", BidiPolicy::Keep);
+        assert_eq!(text, "Caf\u{00E9} \u{30AC}");
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_bidi_policies() {
+        let input = "if admin \u{202E}{ }\u{2066}";
+
+        let (text, diagnostics) = normalize(input, BidiPolicy::Keep);
+        assert_eq!(text, input);
+        assert_eq!(codes(&diagnostics), vec!["bidi-control", "bidi-control"]);
+
+        let (text, _) = normalize(input, BidiPolicy::Strip);
+        assert_eq!(text, "if admin { }");
+
+        let (text, _) = normalize(input, BidiPolicy::Visualize);
+        assert_eq!(text, "if admin [U+202E]{ }[U+2066]");
+    }
+
+    #[test]
+    fn test_bidi_decorations_kept() {
+        let input = "&bdo(rtl){a\u{202E}b}; &bdi(\u{2067}x); &bdi{\u{2068}y};";
+        let (text, diagnostics) = normalize(input, BidiPolicy::Strip);
+        assert_eq!(text, input);
+        assert!(diagnostics.is_empty());
+
+        // Controls right after the content form are still handled
+        let (text, diagnostics) = normalize("&bdi{y};\u{202E}z", BidiPolicy::Strip);
+        assert_eq!(text, "&bdi{y};z");
+        assert_eq!(diagnostics.len(), 1);
+    }
+
+    #[test]
+    fn test_invisible_characters_in_urls() {
+        let (text, diagnostics) = normalize(
+            "https://exa\u{200B}mple.com/a\u{200D}b [x](/pa\u{2060}th) a\u{200D}b",
+            BidiPolicy::Keep,
+        );
+        assert_eq!(text, "https://example.com/ab [x](/path) a\u{200D}b");
+        assert_eq!(
+            codes(&diagnostics),
+            vec!["invisible-in-url", "invisible-in-url"]
+        );
+    }
+
+    #[test]
+    fn test_confusable_hosts() {
+        // Cyrillic а in a Latin label
+        let (_, diagnostics) = normalize("https://p\u{0430}ypal.com/", BidiPolicy::Keep);
+        assert_eq!(codes(&diagnostics), vec!["confusable-host"]);
+
+        // All-Cyrillic label that reads as "paypa"
+        assert!(is_confusable_label(
+            "\u{0440}\u{0430}\u{0443}\u{0440}\u{0430}"
+        ));
+
+        // Genuine internationalized hosts are fine
+        let (_, diagnostics) = normalize(
+            "https://日本語.jp/ https://example.com/ https://user@müller.de:8080/",
+            BidiPolicy::Keep,
+        );
+        assert!(diagnostics.is_empty());
+    }
+}
@@ -0,0 +1,322 @@
+//! Final sanitization of rendered HTML
+//!
+//! An optional defense-in-depth pass that runs the rendered HTML through
+//! `ammonia` with a policy built from the tags, attributes and classes UMD
+//! itself emits. Anything outside that vocabulary is removed, so a bug in a
+//! post-processor cannot turn into markup the parser never meant to produce.
+//!
+//! Plugin `<template>` elements are rebuilt around their cleaned contents,
+//! since arguments and bodies come from the input. Deferred placeholders are
+//! taken out before cleaning and put back afterwards, and HTML spliced in
+//! with [`crate::hydrate`] is never passed through this module.
+
+use ammonia::{Builder, UrlRelative};
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use std::collections::{HashMap, HashSet};
+
+use crate::extensions::markers;
+use crate::extensions::styles::{is_valid_color, is_valid_length};
+use crate::link_policy::{LinkPolicy, SchemePolicy};
+
+/// Token kind for plugin output set aside during cleaning
+const TRUSTED: char = 'R';
+
+// Plugin <template> elements (name, contents) and deferred placeholders
+static PLUGIN_OUTPUT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"(?s)<template class="umd-plugin umd-plugin-(\w+)">(.*?)</template>|<!--umd-plugin:[0-9a-f]+:[\w-]+-->"#,
+    )
+    .unwrap()
+});
+
+static TRUSTED_TOKEN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(&markers::token_pattern(TRUSTED)).unwrap());
+
+// Class names UMD emits verbatim
+const CLASSES: &[&str] = &[
+    "alert",
+    "anchor",
+    "badge",
+    "blockquote",
+    "contains-task-list",
+    "csv-table",
+    "footnote-backref",
+    "footnote-ref",
+    "footnotes",
+    "me-0",
+    "ms-auto",
+    "mx-auto",
+    "rounded-pill",
+    "row",
+    "spoiler",
+    "table",
+    "task-list-item",
+    "text-truncate",
+    "umd-blockquote",
+    "umd-plugin",
+    "umd-table",
+    "w-100",
+    "w-auto",
+];
+
+// Class families UMD emits with a generated suffix (colors, sizes, languages,
+// generated style classes, ...)
+static CLASS_FAMILY: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"^(alert|text|bg|fs|align|language|umd-c|writing-mode|indent|table|col|offset)-[\w-]+$",
+    )
+    .unwrap()
+});
+
+// Tags UMD emits and the attributes allowed on each of them
+const TAG_ATTRIBUTES: &[(&str, &[&str])] = &[
+    ("a", &["href", "title", "rel", "target", "id"]),
+    ("abbr", &["title"]),
+    ("b", &[]),
+    ("bdi", &[]),
+    ("bdo", &["dir"]),
+    ("blockquote", &[]),
+    ("br", &[]),
+    ("caption", &[]),
+    ("cite", &[]),
+    ("code", &[]),
+    ("data", &["value"]),
+    ("dd", &[]),
+    ("del", &[]),
+    ("dfn", &[]),
+    ("div", &["role"]),
+    ("dl", &[]),
+    ("dt", &[]),
+    ("em", &[]),
+    ("h1", &[]),
+    ("h2", &[]),
+    ("h3", &[]),
+    ("h4", &[]),
+    ("h5", &[]),
+    ("h6", &[]),
+    ("hr", &[]),
+    ("i", &[]),
+    ("img", &["src", "alt", "title"]),
+    ("input", &["type", "checked", "disabled"]),
+    ("kbd", &[]),
+    ("li", &["id"]),
+    ("ol", &["start"]),
+    ("p", &["style"]),
+    ("pre", &["lang"]),
+    ("q", &[]),
+    ("rp", &[]),
+    ("rt", &[]),
+    ("ruby", &[]),
+    ("s", &[]),
+    ("samp", &[]),
+    ("section", &[]),
+    ("small", &[]),
+    ("span", &["style", "role", "tabindex"]),
+    ("strong", &[]),
+    ("sub", &[]),
+    ("sup", &[]),
+    ("table", &[]),
+    ("tbody", &[]),
+    ("td", &["colspan", "rowspan", "align", "headers", "style"]),
+    ("tfoot", &[]),
+    (
+        "th",
+        &[
+            "colspan", "rowspan", "align", "scope", "headers", "id", "style",
+        ],
+    ),
+    ("thead", &[]),
+    ("time", &["datetime"]),
+    ("tr", &[]),
+    ("u", &[]),
+    ("ul", &[]),
+    ("var", &[]),
+    ("wbr", &[]),
+];
+
+// Attributes allowed on every tag above
+const GENERIC_ATTRIBUTES: &[&str] = &["class", "lang"];
+
+// Values UMD emits for `role` (alerts, spoilers) and `tabindex` (spoilers)
+const ATTRIBUTE_VALUES: &[(&str, &[&str])] =
+    &[("role", &["alert", "button"]), ("tabindex", &["0"])];
+
+// Style properties set by COLOR(), SIZE() and column widths
+const STYLE_PROPERTIES: &[&str] = &["color", "background-color", "font-size", "width"];
+
+// Schemes kept when the link policy is a blocklist; ammonia needs an allowlist
+const DEFAULT_SCHEMES: &[&str] = &[
+    "http", "https", "mailto", "tel", "sms", "ftp", "ftps", "spotify", "steam", "discord", "slack",
+    "zoom", "vscode",
+];
+
+/// Clean rendered HTML with the UMD vocabulary
+///
+/// # Arguments
+///
+/// * `html` - HTML produced by the parser
+/// * `link_policy` - The link policy in effect; its schemes and external
+///   link class are carried over to the cleaning policy
+///
+/// # Returns
+///
+/// HTML containing only tags, attributes and classes UMD emits
+///
+/// # Examples
+///
+/// ```
+/// use universal_markdown::link_policy::LinkPolicy;
+/// use universal_markdown::output_sanitizer::sanitize_output;
+///
+/// let html = r#"<p class="text-danger evil" onclick="x()">Hi<script>x()</script></p>"#;
+/// let clean = sanitize_output(html, &LinkPolicy::default());
+/// assert_eq!(clean, r#"<p class="text-danger">Hi</p>"#);
+/// ```
+pub fn sanitize_output(html: &str, link_policy: &LinkPolicy) -> String {
+    let cleaner = cleaner(link_policy);
+
+    // Set plugin output aside, cleaning the contents of templates
+    let mut trusted = Vec::new();
+    let protected = PLUGIN_OUTPUT.replace_all(html, |caps: &Captures| {
+        let output = match (caps.get(1), caps.get(2)) {
+            (Some(name), Some(contents)) => format!(
+                r#"<template class="umd-plugin umd-plugin-{}">{}</template>"#,
+                name.as_str(),
+                cleaner.clean(contents.as_str())
+            ),
+            _ => caps[0].to_string(),
+        };
+        trusted.push(output);
+        markers::token(TRUSTED, trusted.len() - 1)
+    });
+    let cleaned = cleaner.clean(&protected).to_string();
+
+    // Put plugin output back
+    TRUSTED_TOKEN
+        .replace_all(&cleaned, |caps: &Captures| {
+            caps[1]
+                .parse::<usize>()
+                .ok()
+                .and_then(|i| trusted.get(i))
+                .cloned()
+                .unwrap_or_default()
+        })
+        .to_string()
+}
+
+/// Build the ammonia policy for the UMD vocabulary and `link_policy`
+fn cleaner(link_policy: &LinkPolicy) -> Builder<'_> {
+    let tag_attributes: HashMap<&str, HashSet<&str>> = TAG_ATTRIBUTES
+        .iter()
+        .map(|(tag, attrs)| (*tag, attrs.iter().copied().collect()))
+        .collect();
+    let schemes: HashSet<&str> = match &link_policy.schemes {
+        SchemePolicy::Allow(schemes) => schemes.iter().map(String::as_str).collect(),
+        SchemePolicy::Block(blocked) => DEFAULT_SCHEMES
+            .iter()
+            .copied()
+            .filter(|s| !blocked.iter().any(|b| b.eq_ignore_ascii_case(s)))
+            .collect(),
+    };
+    let extra_classes: Vec<String> = link_policy
+        .external_class
+        .as_deref()
+        .map(|c| c.split_whitespace().map(str::to_string).collect())
+        .unwrap_or_default();
+
+    let mut builder = Builder::empty();
+    builder
+        .tags(tag_attributes.keys().copied().collect())
+        .tag_attributes(tag_attributes)
+        .generic_attributes(GENERIC_ATTRIBUTES.iter().copied().collect())
+        .generic_attribute_prefixes(["data-", "aria-"].into_iter().collect())
+        .clean_content_tags(["script", "style"].into_iter().collect())
+        .url_schemes(schemes)
+        .url_relative(UrlRelative::PassThrough)
+        .link_rel(None)
+        .attribute_filter(move |_, attr, value| match attr {
+            "class" => {
+                let classes: Vec<&str> = value
+                    .split_whitespace()
+                    .filter(|c| is_umd_class(c) || extra_classes.iter().any(|e| e == c))
+                    .collect();
+                (!classes.is_empty()).then(|| classes.join(" ").into())
+            }
+            "style" => clean_style(value).map(Into::into),
+            _ => match ATTRIBUTE_VALUES.iter().find(|(name, _)| *name == attr) {
+                Some((_, values)) => values.contains(&value).then(|| value.into()),
+                None => Some(value.into()),
+            },
+        });
+    builder
+}
+
+/// Keep the declarations of `style` UMD emits: known properties with values
+/// that pass the same validation as `COLOR()` and `SIZE()`
+fn clean_style(style: &str) -> Option<String> {
+    let declarations: Vec<String> = style
+        .split(';')
+        .filter_map(|declaration| {
+            let (property, value) = declaration.split_once(':')?;
+            let (property, value) = (property.trim(), value.trim());
+            let valid = match property {
+                "font-size" | "width" => is_valid_length(value),
+                _ => STYLE_PROPERTIES.contains(&property) && is_valid_color(value),
+            };
+            valid.then(|| format!("{}: {}", property, value))
+        })
+        .collect();
+    (!declarations.is_empty()).then(|| declarations.join("; "))
+}
+
+/// Whether `class` belongs to the class vocabulary UMD emits
+fn is_umd_class(class: &str) -> bool {
+    CLASSES.contains(&class) || CLASS_FAMILY.is_match(class)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_removes_foreign_markup() {
+        let html = r#"<div onmouseover="x()"><iframe src="x"></iframe><span class="badge bg-primary">b</span></div>"#;
+        let clean = sanitize_output(html, &LinkPolicy::default());
+        assert_eq!(
+            clean,
+            r#"<div><span class="badge bg-primary">b</span></div>"#
+        );
+    }
+
+    #[test]
+    fn test_cleans_plugin_templates() {
+        let html = r#"<template class="umd-plugin umd-plugin-x"><data value="0">a</data><b onclick="y">t</b><img src="javascript:x"></template><!--umd-plugin:00ff:x-0123-->"#;
+        assert_eq!(
+            sanitize_output(html, &LinkPolicy::default()),
+            r#"<template class="umd-plugin umd-plugin-x"><data value="0">a</data><b>t</b><img></template><!--umd-plugin:00ff:x-0123-->"#
+        );
+    }
+
+    #[test]
+    fn test_attributes_limited_to_emitting_tags() {
+        let html = r#"<div id="x" style="color: red" tabindex="1" role="alert"><span role="link" tabindex="0" style="color: red; position: fixed; font-size: 2rem">s</span><p style="background-color: url(x)">p</p></div>"#;
+        assert_eq!(
+            sanitize_output(html, &LinkPolicy::default()),
+            r#"<div role="alert"><span style="color: red; font-size: 2rem" tabindex="0">s</span><p>p</p></div>"#
+        );
+    }
+
+    #[test]
+    fn test_url_schemes_follow_link_policy() {
+        let html = r#"<a href="spotify:track:1">s</a><a href="ftp://x/">f</a>"#;
+        let policy = LinkPolicy {
+            schemes: SchemePolicy::Allow(vec!["spotify".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(
+            sanitize_output(html, &policy),
+            r#"<a href="spotify:track:1">s</a><a>f</a>"#
+        );
+    }
+}
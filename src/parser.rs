@@ -3,9 +3,18 @@
 //! This module provides the core parsing functionality using comrak as the base
 //! Markdown parser, with extensions for LukiWiki-specific syntax.
 
+use comrak::html::dangerous_url;
+use comrak::nodes::{Node, NodeValue};
 use comrak::options::{ListStyleType, Plugins};
 use comrak::{Arena, Options, format_html_with_plugins, parse_document};
 
+use crate::extensions::styles::{StylePolicy, Styles};
+use crate::extensions::table;
+use crate::extensions::table::export::TableRecorder;
+use crate::limits::Limits;
+use crate::link_policy::{BLOCKED_URL, LinkPolicy};
+use crate::normalize::BidiPolicy;
+
 /// Parser configuration for LukiWiki markup
 #[derive(Debug, Clone)]
 pub struct ParserOptions {
@@ -15,6 +24,32 @@ pub struct ParserOptions {
     pub lukiwiki_extensions: bool,
     /// Maximum heading level (1-5 for LukiWiki, 1-6 for standard Markdown)
     pub max_heading_level: u8,
+    /// Emit placeholders for plugin calls instead of `<template>` elements
+    /// (see [`crate::extensions::deferred`])
+    pub deferred_plugins: bool,
+    /// Rules for link and media URLs in the output
+    pub link_policy: LinkPolicy,
+    /// Run the output through a final allowlist sanitizer
+    /// (see [`crate::output_sanitizer`])
+    pub sanitize_output: bool,
+    /// How custom `COLOR()`/`SIZE()` values are emitted: inline, dropped,
+    /// or as generated classes (see [`crate::extensions::styles`])
+    pub style_policy: StylePolicy,
+    /// Resource limits for untrusted input (see [`crate::limits`])
+    pub limits: Limits,
+    /// Handling of bidi control characters in the text
+    /// (see [`crate::normalize`])
+    pub bidi_policy: BidiPolicy,
+    /// CJK-friendly emphasis and removal of soft breaks between East Asian
+    /// wide characters (see [`crate::extensions::cjk`])
+    pub cjk: bool,
+    /// Compute `=SUM(...)`-style formula cells in UMD tables
+    /// (see [`crate::extensions::table::umd::evaluate_formulas`])
+    pub table_formulas: bool,
+    /// Prepended to the `id`s of table header cells (`umd-table-…`,
+    /// `gfm-table-…`), which are numbered per document; set a distinct
+    /// prefix for each document rendered into the same page
+    pub table_id_prefix: String,
 }
 
 impl Default for ParserOptions {
@@ -23,6 +58,15 @@ impl Default for ParserOptions {
             gfm_extensions: true,
             lukiwiki_extensions: true,
             max_heading_level: 5,
+            deferred_plugins: false,
+            link_policy: LinkPolicy::default(),
+            sanitize_output: false,
+            style_policy: StylePolicy::default(),
+            limits: Limits::default(),
+            bidi_policy: BidiPolicy::default(),
+            cjk: false,
+            table_formulas: false,
+            table_id_prefix: String::new(),
         }
     }
 }
@@ -49,6 +93,36 @@ impl Default for ParserOptions {
 /// assert!(html.contains("<strong>"));
 /// ```
 pub fn parse_to_html(input: &str, options: &ParserOptions) -> String {
+    parse_to_html_with_styles(
+        input,
+        options,
+        &mut Styles::default(),
+        None,
+        &mut Vec::new(),
+    )
+}
+
+/// Parse LukiWiki markup, validating custom table cell style values
+///
+/// # Arguments
+///
+/// * `input` - The sanitized LukiWiki markup source text
+/// * `options` - Parser configuration options
+/// * `styles` - Validator for custom `COLOR()`/`SIZE()` values in GFM table cells
+/// * `recorder` - Recorder of the rendered GFM tables, if they are exported
+/// * `blocked_urls` - Receives the link and image destinations replaced because
+///   they use a dangerous scheme
+///
+/// # Returns
+///
+/// HTML string
+pub fn parse_to_html_with_styles(
+    input: &str,
+    options: &ParserOptions,
+    styles: &mut Styles,
+    recorder: Option<&mut TableRecorder>,
+    blocked_urls: &mut Vec<(&'static str, String)>,
+) -> String {
     // Configure comrak options
     let mut comrak_options = Options::default();
 
@@ -62,6 +136,10 @@ pub fn parse_to_html(input: &str, options: &ParserOptions) -> String {
         comrak_options.extension.footnotes = true; // Enable footnotes
         comrak_options.extension.header_ids = None; // Disable automatic IDs, we'll add them ourselves
     }
+    if options.lukiwiki_extensions {
+        comrak_options.extension.table = true; // UMD table cells are parsed as a GFM table
+    }
+    comrak_options.extension.cjk_friendly_emphasis = options.cjk;
 
     // Render options
     comrak_options.render.hardbreaks = false;
@@ -78,8 +156,20 @@ pub fn parse_to_html(input: &str, options: &ParserOptions) -> String {
     // Parse markdown to AST
     let root = parse_document(&arena, input, &comrak_options);
 
-    // TODO: Apply LukiWiki-specific transformations to AST here
-    // This is where we'll add custom syntax handling in later steps
+    // Render GFM tables with the shared table model
+    table::gfm::replace_tables(
+        &arena,
+        root,
+        input,
+        &comrak_options,
+        &options.table_id_prefix,
+        styles,
+        recorder,
+    );
+
+    // Replace dangerous destinations the way the link policy does, instead of
+    // letting comrak render them as empty attributes
+    block_dangerous_urls(root, blocked_urls);
 
     // Render to HTML
     let mut html = String::new();
@@ -89,6 +179,27 @@ pub fn parse_to_html(input: &str, options: &ParserOptions) -> String {
     html
 }
 
+/// Replace `javascript:`-like link and image destinations with the blocked URL
+///
+/// # Arguments
+///
+/// * `root` - Root of the parsed document
+/// * `blocked_urls` - Receives the rule name and original value of each replacement
+fn block_dangerous_urls<'a>(root: Node<'a>, blocked_urls: &mut Vec<(&'static str, String)>) {
+    for node in root.descendants() {
+        let mut data = node.data.borrow_mut();
+        let (rule, link) = match &mut data.value {
+            NodeValue::Link(link) => ("link-policy", link),
+            NodeValue::Image(link) => ("media-policy", link),
+            _ => continue,
+        };
+        if dangerous_url(&link.url) {
+            let url = std::mem::replace(&mut link.url, BLOCKED_URL.to_string());
+            blocked_urls.push((rule, url));
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -182,8 +293,8 @@ mod tests {
     fn test_gfm_table() {
         let input = "| Header 1 | Header 2 |\n|----------|----------|\n| Cell 1   | Cell 2   |";
         let html = parse_to_html(input, &ParserOptions::default());
-        assert!(html.contains("<table>"));
-        assert!(html.contains("<th>Header 1</th>"));
+        assert!(html.contains(r#"<table class="table">"#));
+        assert!(html.contains(r#"<th scope="col">Header 1</th>"#));
         assert!(html.contains("<td>Cell 1</td>"));
     }
 
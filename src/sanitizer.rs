@@ -4,10 +4,18 @@
 //! It escapes all HTML tags in user input while preserving standard HTML entities.
 //! It also blocks dangerous URL schemes.
 
+use once_cell::sync::Lazy;
+use regex::Regex;
 use std::borrow::Cow;
 
+use crate::entities;
+use crate::link_policy::LinkPolicy;
+
 /// Sanitizes a URL by blocking dangerous schemes
 ///
+/// Uses the default [`LinkPolicy`](crate::link_policy::LinkPolicy), so
+/// entity and whitespace obfuscation such as `java&#09;script:` is detected.
+///
 /// # Arguments
 ///
 /// * `url` - The URL to sanitize
@@ -30,22 +38,13 @@ use std::borrow::Cow;
 ///
 /// assert_eq!(sanitize_url("https://example.com"), "https://example.com");
 /// assert_eq!(sanitize_url("javascript:alert(1)"), "#blocked-url");
+/// assert_eq!(sanitize_url("java&#09;script:alert(1)"), "#blocked-url");
 /// assert_eq!(sanitize_url("data:text/html,<script>alert(1)</script>"), "#blocked-url");
 /// assert_eq!(sanitize_url("spotify:track:123"), "spotify:track:123"); // Custom app schemes allowed
 /// ```
 pub fn sanitize_url(url: &str) -> Cow<'_, str> {
-    let url_lower = url.trim().to_lowercase();
-
-    // Check for dangerous schemes (case-insensitive)
-    if url_lower.starts_with("javascript:")
-        || url_lower.starts_with("data:")
-        || url_lower.starts_with("vbscript:")
-        || url_lower.starts_with("file:")
-    {
-        return Cow::Borrowed("#blocked-url");
-    }
-
-    Cow::Borrowed(url)
+    static DEFAULT_POLICY: Lazy<LinkPolicy> = Lazy::new(LinkPolicy::default);
+    DEFAULT_POLICY.check_link(url)
 }
 
 /// Sanitizes input text by escaping HTML tags while preserving HTML entities
@@ -102,10 +101,32 @@ pub fn sanitize(input: &str) -> Cow<'_, str> {
     Cow::Owned(result)
 }
 
+// Raw HTML tags, reported by `sanitize_recording`
+pub(crate) static TAG: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"</?[A-Za-z][A-Za-z0-9-]*(?:\s[^<>]*)?/?>").unwrap());
+
+/// Sanitize input text, recording each raw HTML tag it escapes
+///
+/// # Arguments
+///
+/// * `input` - The text to sanitize
+/// * `escaped_tags` - Receives the escaped tags, in order
+///
+/// # Returns
+///
+/// The same output as [`sanitize`]
+pub(crate) fn sanitize_recording<'a>(
+    input: &'a str,
+    escaped_tags: &mut Vec<String>,
+) -> Cow<'a, str> {
+    escaped_tags.extend(TAG.find_iter(input).map(|m| m.as_str().to_string()));
+    sanitize(input)
+}
+
 /// Checks if the character sequence starting with '&' is a valid HTML entity
 ///
 /// Valid entities are:
-/// - Named entities: &name; (e.g., &nbsp;, &lt;, &gt;, &amp;, &quot;, &apos;)
+/// - Named entities: &name; (e.g., &nbsp;, &hearts;, &rarr;)
 /// - Decimal entities: &#123;
 /// - Hexadecimal entities: &#x7B;
 fn is_html_entity(chars: &mut std::iter::Peekable<std::str::Chars>) -> bool {
@@ -118,7 +139,7 @@ fn is_html_entity(chars: &mut std::iter::Peekable<std::str::Chars>) -> bool {
             // Found the end of entity
             return is_valid_entity(&entity);
         }
-        if entity.len() > 10 {
+        if entity.len() > entities::MAX_NAME_LEN {
             // Entity too long, not valid
             return false;
         }
@@ -134,69 +155,11 @@ fn is_html_entity(chars: &mut std::iter::Peekable<std::str::Chars>) -> bool {
 }
 
 /// Validates if the entity name (without & and ;) is a valid HTML entity
+///
+/// Named entities are checked against the full WHATWG table in
+/// [`crate::entities`].
 fn is_valid_entity(entity: &str) -> bool {
-    if entity.is_empty() {
-        return false;
-    }
-
-    // Numeric entities
-    if entity.starts_with('#') {
-        if entity.len() < 2 {
-            return false;
-        }
-        if entity[1..].starts_with('x') || entity[1..].starts_with('X') {
-            // Hexadecimal: &#xHH;
-            if entity.len() < 3 {
-                return false;
-            }
-            return entity[2..].chars().all(|c| c.is_ascii_hexdigit());
-        } else {
-            // Decimal: &#123;
-            return entity[1..].chars().all(|c| c.is_ascii_digit());
-        }
-    }
-
-    // Named entities - common ones
-    // Full list: https://html.spec.whatwg.org/multipage/named-characters.html
-    matches!(
-        entity,
-        "nbsp"
-            | "lt"
-            | "gt"
-            | "amp"
-            | "quot"
-            | "apos"
-            | "copy"
-            | "reg"
-            | "trade"
-            | "ndash"
-            | "mdash"
-            | "lsquo"
-            | "rsquo"
-            | "ldquo"
-            | "rdquo"
-            | "hellip"
-            | "prime"
-            | "Prime"
-            | "euro"
-            | "yen"
-            | "pound"
-            | "cent"
-            | "times"
-            | "divide"
-            | "plusmn"
-            | "minus"
-            | "alpha"
-            | "beta"
-            | "gamma"
-            | "delta"
-            | "epsilon"
-            | "Alpha"
-            | "Beta"
-            | "Gamma"
-            | "Delta"
-            | "Epsilon" // Add more as needed
-    )
+    entities::is_character_reference(entity)
 }
 
 #[cfg(test)]
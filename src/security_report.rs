@@ -0,0 +1,517 @@
+//! Audit of what the parser neutralized
+//!
+//! Escaping and URL blocking happen silently in the output, so this module
+//! reports each occurrence with its location in the source for moderation:
+//!
+//! - Raw HTML tags, which are escaped and shown as text
+//! - Dangerous attributes on those tags (event handlers, `style`, script
+//!   URLs)
+//! - Link and media URLs rejected by the [`LinkPolicy`]
+//! - Custom `COLOR()`/`SIZE()` values and column widths dropped by style
+//!   validation
+//!
+//! The events are recorded where they happen, as [`Findings`]: the
+//! sanitizer records each tag it escapes, [`LinkPolicy::apply`] each URL it
+//! replaces and [`crate::extensions::styles::Styles`] each value it drops.
+//! [`audit`] then locates them in the source, indexing the source once and
+//! matching each finding to the next unreported occurrence of its text, so
+//! the report lists exactly what the output neutralized.
+//!
+//! Spans are byte ranges into the text passed to the parser, frontmatter
+//! included. Code (fenced and indented blocks and code spans) is shown
+//! verbatim and is not reported.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+use comrak::nodes::NodeValue;
+use comrak::{Arena, Options, parse_document};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::frontmatter::extract_frontmatter;
+use crate::link_policy::{BLOCKED_URL, LinkPolicy};
+use crate::sanitizer::TAG;
+
+/// What happened to the reported input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecurityEventKind {
+    /// A link or media URL was replaced with [`BLOCKED_URL`]
+    BlockedUrl,
+    /// A raw HTML tag was escaped to text
+    EscapedTag,
+    /// A dangerous attribute of an escaped tag
+    RejectedAttribute,
+    /// A custom style value was dropped
+    InvalidStyleValue,
+}
+
+/// One entry of the security report
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecurityEvent {
+    pub kind: SecurityEventKind,
+    /// Identifier of the rule that triggered (e.g. `event-handler`)
+    pub rule: &'static str,
+    /// Byte range in the source text
+    pub span: Range<usize>,
+    /// The offending source text
+    pub text: String,
+}
+
+/// What the rendering pipeline neutralized, in the order it happened
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Findings {
+    /// Raw tags escaped by the sanitizer
+    pub escaped_tags: Vec<String>,
+    /// URLs replaced by the link policy: rule (`link-policy` or
+    /// `media-policy`) and the attribute value
+    pub blocked_urls: Vec<(&'static str, String)>,
+    /// `(property, value)` pairs dropped by style validation
+    /// (see [`crate::extensions::styles::Styles::invalid_values`])
+    pub invalid_styles: Vec<(String, String)>,
+}
+
+impl Findings {
+    fn is_empty(&self) -> bool {
+        self.escaped_tags.is_empty()
+            && self.blocked_urls.is_empty()
+            && self.invalid_styles.is_empty()
+    }
+}
+
+// Attributes of a raw tag: name and optional value
+static ATTRIBUTE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"\s([A-Za-z_:][-A-Za-z0-9_:.]*)(?:\s*=\s*("[^"]*"|'[^']*'|[^\s"'=<>`]+))?"#)
+        .unwrap()
+});
+
+// Markdown link and image destinations
+static LINK: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"!?\[[^\]]*\]\(\s*<?((?:[^()\s<>]|\([^()\s]*\))+)").unwrap());
+
+// Link reference definitions
+static REFERENCE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?m)^ {0,3}\[[^\]]+\]:\s*<?([^\s>]+)").unwrap());
+
+/// Build the security report for a source text
+///
+/// # Arguments
+///
+/// * `input` - The Universal Markdown source text
+/// * `link_policy` - The policy the links were checked with
+/// * `findings` - What the pipeline neutralized while rendering `input`
+///
+/// # Returns
+///
+/// The events, ordered by position
+///
+/// # Examples
+///
+/// ```
+/// use universal_markdown::link_policy::LinkPolicy;
+/// use universal_markdown::security_report::{Findings, SecurityEventKind, audit};
+///
+/// let findings = Findings {
+///     escaped_tags: vec!["<b onclick=\"x()\">".into(), "</b>".into()],
+///     ..Default::default()
+/// };
+/// let report = audit("Hi <b onclick=\"x()\">!</b>", &LinkPolicy::default(), &findings);
+/// assert_eq!(report[0].kind, SecurityEventKind::EscapedTag);
+/// assert_eq!(report[0].span, 3..20);
+/// assert_eq!(report[1].rule, "event-handler");
+/// assert_eq!(report[1].text, "onclick=\"x()\"");
+/// ```
+pub fn audit(input: &str, link_policy: &LinkPolicy, findings: &Findings) -> Vec<SecurityEvent> {
+    if findings.is_empty() {
+        return Vec::new();
+    }
+
+    // Frontmatter is removed before rendering
+    let (_, body) = extract_frontmatter(input);
+    let body_start = input.len() - body.len();
+
+    let mut skipped: Vec<Range<usize>> = std::iter::once(0..body_start)
+        .chain(code_ranges(&input[body_start..]).map(|r| body_start + r.start..body_start + r.end))
+        .collect();
+    skipped.sort_by_key(|r| r.start);
+    let is_skipped = |position: usize| {
+        let i = skipped.partition_point(|r| r.end <= position);
+        skipped.get(i).is_some_and(|r| r.start <= position)
+    };
+
+    let mut events = Vec::new();
+    let mut push = |kind, rule, span: Range<usize>| {
+        events.push(SecurityEvent {
+            kind,
+            rule,
+            text: input[span.clone()].to_string(),
+            span,
+        });
+    };
+
+    if !findings.escaped_tags.is_empty() {
+        // The sanitizer escapes what its tag pattern matches
+        let mut index = Index::default();
+        for m in TAG.find_iter(input) {
+            if !is_skipped(m.start()) {
+                index.insert(Cow::Borrowed(m.as_str()), m.range());
+            }
+        }
+
+        for tag in &findings.escaped_tags {
+            let Some(span) = index.take(&[tag.as_str()]) else {
+                continue;
+            };
+            push(SecurityEventKind::EscapedTag, "raw-html", span.clone());
+
+            for caps in ATTRIBUTE.captures_iter(tag) {
+                let name = caps[1].to_ascii_lowercase();
+                let value = caps
+                    .get(2)
+                    .map_or("", |m| m.as_str().trim_matches(['"', '\'']));
+                let rule = if name.starts_with("on") {
+                    "event-handler"
+                } else if name == "style" {
+                    "style-attribute"
+                } else if matches!(
+                    name.as_str(),
+                    "href" | "src" | "action" | "formaction" | "xlink:href" | "srcdoc"
+                ) && (name == "srcdoc" || link_policy.check_link(value) == BLOCKED_URL)
+                {
+                    "unsafe-url"
+                } else {
+                    continue;
+                };
+                let attribute = caps.get(0).unwrap();
+                let start =
+                    span.start + attribute.start() + (caps[0].len() - caps[0].trim_start().len());
+                push(
+                    SecurityEventKind::RejectedAttribute,
+                    rule,
+                    start..span.start + attribute.end(),
+                );
+            }
+        }
+    }
+
+    if !findings.blocked_urls.is_empty() {
+        // Blocked URLs are looked up among link destinations, which may be
+        // written with character references
+        let mut destinations: Vec<_> = LINK
+            .captures_iter(input)
+            .chain(REFERENCE.captures_iter(input))
+            .map(|caps| caps.get(1).unwrap())
+            .filter(|destination| !is_skipped(destination.start()))
+            .collect();
+        destinations.sort_by_key(|destination| destination.start());
+
+        let mut index = Index::default();
+        for destination in destinations {
+            let decoded = html_escape::decode_html_entities(destination.as_str());
+            if decoded != destination.as_str() {
+                index.insert(Cow::Owned(decoded.into_owned()), destination.range());
+            }
+            index.insert(Cow::Borrowed(destination.as_str()), destination.range());
+        }
+
+        for (rule, url) in &findings.blocked_urls {
+            // The attribute value is HTML-escaped and may be percent-encoded
+            let decoded = html_escape::decode_html_entities(url).into_owned();
+            let unencoded = percent_decode(&decoded);
+            if let Some(span) = index.take(&[&decoded, &unencoded]) {
+                push(SecurityEventKind::BlockedUrl, rule, span);
+            }
+        }
+    }
+
+    if !findings.invalid_styles.is_empty() {
+        let mut index = Index::default();
+        for span in style_arguments(input) {
+            if !is_skipped(span.start) {
+                index.insert(Cow::Borrowed(&input[span.clone()]), span);
+            }
+        }
+
+        for (property, value) in &findings.invalid_styles {
+            let rule = if matches!(property.as_str(), "font-size" | "width") {
+                "invalid-length"
+            } else {
+                "invalid-color"
+            };
+            // Unitless sizes get `rem` appended before validation
+            let candidates = [value.as_str(), value.strip_suffix("rem").unwrap_or(value)];
+            if let Some(span) = index.take(&candidates) {
+                push(SecurityEventKind::InvalidStyleValue, rule, span);
+            }
+        }
+    }
+
+    events.sort_by_key(|e| (e.span.start, e.span.end));
+    events
+}
+
+/// Occurrences of source tokens by their text
+///
+/// Equal findings are matched to equal source text in source order, so the
+/// order in which the pipeline recorded them does not matter.
+#[derive(Default)]
+struct Index<'a> {
+    /// Spans in source order and the position of the next one to hand out
+    occurrences: HashMap<Cow<'a, str>, (usize, Vec<Range<usize>>)>,
+    /// Starts of the spans already handed out
+    found: HashSet<usize>,
+}
+
+impl<'a> Index<'a> {
+    /// Add an occurrence; occurrences must be inserted in source order
+    fn insert(&mut self, text: Cow<'a, str>, span: Range<usize>) {
+        self.occurrences.entry(text).or_default().1.push(span);
+    }
+
+    /// Next unreported occurrence of the first candidate that has one left
+    fn take(&mut self, candidates: &[&str]) -> Option<Range<usize>> {
+        for candidate in candidates {
+            let Some((next, spans)) = self.occurrences.get_mut(*candidate) else {
+                continue;
+            };
+            while let Some(span) = spans.get(*next) {
+                *next += 1;
+                if self.found.insert(span.start) {
+                    return Some(span.clone());
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Spans of the places style values are written, in source order
+///
+/// These are call arguments, as in `COLOR(value)` or `&color(fg,value)`,
+/// and table cells, where a column width follows the last `:` prefix
+/// (`|CENTER:value|`).
+fn style_arguments(input: &str) -> Vec<Range<usize>> {
+    let trimmed = |range: Range<usize>| {
+        let text = &input[range.clone()];
+        let start = range.start + (text.len() - text.trim_start().len());
+        start..start + text.trim().len()
+    };
+
+    let mut spans = Vec::new();
+    let mut previous: Option<(usize, u8)> = None;
+    for (i, byte) in input.bytes().enumerate() {
+        if !matches!(byte, b'(' | b')' | b',') {
+            continue;
+        }
+        if let Some((start, b'(' | b',')) = previous
+            && matches!(byte, b')' | b',')
+        {
+            spans.push(trimmed(start + 1..i));
+        }
+        previous = Some((i, byte));
+    }
+
+    let mut line_start = 0;
+    for line in input.split_inclusive('\n') {
+        let row = line.trim_end();
+        if row.trim_start().starts_with('|') {
+            let mut cell_start = line_start + (line.len() - line.trim_start().len()) + 1;
+            for cell in row.trim_start()[1..].split('|') {
+                let width = cell.rfind(':').map_or(0, |i| i + 1);
+                spans.push(trimmed(cell_start + width..cell_start + cell.len()));
+                cell_start += cell.len() + 1;
+            }
+        }
+        line_start += line.len();
+    }
+
+    spans.retain(|span| !span.is_empty());
+    spans.sort_by_key(|span| span.start);
+    spans
+}
+
+/// Byte ranges of code blocks (fenced and indented) and code spans
+fn code_ranges(text: &str) -> impl Iterator<Item = Range<usize>> {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let offset = |line: usize, column: usize| {
+        line_starts
+            .get(line.saturating_sub(1))
+            .map_or(text.len(), |start| (start + column).min(text.len()))
+    };
+
+    let mut options = Options::default();
+    options.extension.table = true;
+    options.extension.footnotes = true;
+    let arena = Arena::new();
+    let root = parse_document(&arena, text, &options);
+
+    let ranges: Vec<Range<usize>> = root
+        .descendants()
+        .filter(|node| {
+            matches!(
+                node.data().value,
+                NodeValue::Code(_) | NodeValue::CodeBlock(_)
+            )
+        })
+        .map(|node| {
+            // Columns are 1-based and the end is inclusive
+            let position = node.data().sourcepos;
+            offset(position.start.line, position.start.column.saturating_sub(1))
+                ..offset(position.end.line, position.end.column)
+        })
+        .collect();
+    ranges.into_iter()
+}
+
+/// Decode `%XX` escapes, keeping invalid ones as they are
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(report: &[SecurityEvent]) -> Vec<&str> {
+        report.iter().map(|e| e.rule).collect()
+    }
+
+    fn tags(tags: &[&str]) -> Findings {
+        Findings {
+            escaped_tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_escaped_tags_and_attributes() {
+        let input = "<img src=x onerror=alert(1) style='a'> <a href=\"javascript:x\">y</a>";
+        let findings = tags(&[
+            "<img src=x onerror=alert(1) style='a'>",
+            "<a href=\"javascript:x\">",
+            "</a>",
+        ]);
+        let report = audit(input, &LinkPolicy::default(), &findings);
+        assert_eq!(
+            rules(&report),
+            vec![
+                "raw-html",
+                "event-handler",
+                "style-attribute",
+                "raw-html",
+                "unsafe-url",
+                "raw-html"
+            ]
+        );
+        assert_eq!(report[1].text, "onerror=alert(1)");
+        assert_eq!(report[4].text, "href=\"javascript:x\"");
+    }
+
+    #[test]
+    fn test_blocked_urls() {
+        let input = "javascript:x [a](javascript:x) ![b](data:image/svg+xml,x) [c](https://ok)\n\n[r]: vbscript:%22x%22";
+        let findings = Findings {
+            blocked_urls: vec![
+                ("link-policy", "javascript:x".to_string()),
+                ("media-policy", "data:image/svg+xml,x".to_string()),
+                ("link-policy", "vbscript:%22x%22".to_string()),
+            ],
+            ..Default::default()
+        };
+        let report = audit(input, &LinkPolicy::default(), &findings);
+        assert_eq!(
+            rules(&report),
+            vec!["link-policy", "media-policy", "link-policy"]
+        );
+        assert!(
+            report
+                .iter()
+                .all(|e| e.kind == SecurityEventKind::BlockedUrl)
+        );
+        // The destination, not the same text in the paragraph
+        assert_eq!(report[0].span, 17..29);
+        assert_eq!(report[2].text, "vbscript:%22x%22");
+    }
+
+    #[test]
+    fn test_code_and_frontmatter_skipped() {
+        let input = "---\nx: <b>\n---\n`<i>` and\n\n```\n<script>\n```\n\n    <b>\n\n<u> <i>";
+        let findings = tags(&["<i>", "<script>", "<b>", "<u>", "<i>"]);
+        let report = audit(input, &LinkPolicy::default(), &findings);
+        let text: Vec<&str> = report.iter().map(|e| e.text.as_str()).collect();
+        assert_eq!(text, vec!["<u>", "<i>"]);
+    }
+
+    #[test]
+    fn test_invalid_style_values() {
+        let input = "red;top:0 &color(red;top:0){a}; SIZE(foo): b";
+        let findings = Findings {
+            invalid_styles: vec![
+                ("color".to_string(), "red;top:0".to_string()),
+                ("font-size".to_string(), "foorem".to_string()),
+            ],
+            ..Default::default()
+        };
+        let report = audit(input, &LinkPolicy::default(), &findings);
+        assert_eq!(rules(&report), vec!["invalid-color", "invalid-length"]);
+        assert_eq!(report[0].span, 17..26);
+        assert_eq!(report[1].text, "foo");
+    }
+
+    #[test]
+    fn test_column_width_values() {
+        let input = "|CENTER:zz|c\n|a|b|";
+        let findings = Findings {
+            invalid_styles: vec![("width".to_string(), "zz".to_string())],
+            ..Default::default()
+        };
+        let report = audit(input, &LinkPolicy::default(), &findings);
+        assert_eq!(rules(&report), vec!["invalid-length"]);
+        assert_eq!(report[0].span, 8..10);
+    }
+
+    #[test]
+    fn test_large_input() {
+        // Locating used to rescan the input for every event
+        let lines = 100_000;
+        let input = "<b>\n|CENTER:zz|c\n".repeat(lines);
+        let findings = Findings {
+            escaped_tags: vec!["<b>".to_string(); lines],
+            invalid_styles: vec![("width".to_string(), "zz".to_string()); lines + 1],
+            ..Default::default()
+        };
+        let start = std::time::Instant::now();
+        let report = audit(&input, &LinkPolicy::default(), &findings);
+        assert_eq!(report.len(), 2 * lines);
+        assert!(start.elapsed() < std::time::Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("a%22b%2"), "a\"b%2");
+        assert_eq!(percent_decode("%+1%zz"), "%+1%zz");
+    }
+}
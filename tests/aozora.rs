@@ -0,0 +1,55 @@
+//! Aozora Bunko import tests
+
+use universal_markdown::aozora::convert;
+use universal_markdown::parse;
+
+const SAMPLE: &str = "羅生門
+芥川龍之介
+
+-------------------------------------------------------
+【テキスト中に現れる記号について】
+
+《》：ルビ
+（例）下人《げにん》
+-------------------------------------------------------
+
+［＃３字下げ］一［＃「一」は中見出し］
+
+　ある日の暮方の事である。一人の下人《げにん》が、｜羅生門《らしょうもん》の下で雨やみを待っていた。
+［＃ここから２字下げ］
+広い門の下には、［＃傍点］この男のほかに［＃傍点終わり］誰もいない。
+［＃ここで字下げ終わり］
+［＃地付き］（大正四年九月）
+［＃改ページ］
+底本：「羅生門」※［＃「てへん＋丑」、第4水準2-12-93］
+";
+
+#[test]
+fn test_convert_sample() {
+    let (umd, diagnostics) = convert(SAMPLE);
+
+    assert!(!umd.contains("【テキスト中に現れる記号について】"));
+    assert!(umd.contains("## 一\n"));
+    assert!(umd.contains("一人の&ruby(げにん){下人};が、&ruby(らしょうもん){羅生門};の下"));
+    assert!(umd.contains("INDENT(2): 広い門の下には、&em(sesame){この男のほかに};誰もいない。"));
+    assert!(umd.contains("RIGHT: （大正四年九月）"));
+    assert!(umd.contains("\n\n---\n\n"));
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].code, "aozora-unsupported");
+    assert!(diagnostics[0].message.contains("てへん＋丑"));
+}
+
+#[test]
+fn test_render_converted_text() {
+    let (umd, _) = convert(SAMPLE);
+    let html = parse(&umd);
+
+    assert!(html.contains("<h2"));
+    assert!(html.contains("<ruby>下人<rp>(</rp><rt>げにん</rt><rp>)</rp></ruby>"));
+    assert!(html.contains(
+        r#"<p class="indent-2">広い門の下には、<span class="text-emphasis-sesame">この男のほかに</span>誰もいない。</p>"#
+    ));
+    assert!(html.contains(r#"<p class="text-end">（大正四年九月）</p>"#));
+    assert!(html.contains("<hr />"));
+}
@@ -2,7 +2,7 @@
 //!
 //! Tests for Bootstrap class generation and styling features
 
-use universal_markdown::parse;
+use universal_markdown::{extract_tables, parse, parse_with_options};
 
 #[test]
 fn test_bootstrap_table_default_class() {
@@ -150,6 +150,30 @@ fn test_definition_list_single_item() {
     assert!(output.contains("</dl>"));
 }
 
+#[test]
+fn test_definition_list_blocks() {
+    let input = ":**HTML**|HyperText Markup Language\n:|The markup &color(red){language}; of the web\n:CSS|Style sheets:\n\n  - Selectors\n  - Properties\n\n  ```css\n  a { color: red }\n  ```\n\nAfter";
+    let output = parse(input);
+    assert!(output.starts_with(
+        r#"<dl><dt><strong>HTML</strong></dt><dd>HyperText Markup Language</dd><dd>The markup <span style="color: red">language</span> of the web</dd><dt>CSS</dt><dd><p>Style sheets:</p>"#
+    ));
+    assert!(output.contains("<li>Properties</li>\n</ul>\n<pre lang=\"css\"><code>a { color: red }\n</code></pre></dd></dl>"));
+    assert!(output.contains("<p>After</p>"));
+}
+
+#[test]
+fn test_definition_list_horizontal_layout() {
+    let options = universal_markdown::parser::ParserOptions {
+        sanitize_output: true,
+        ..Default::default()
+    };
+    let output = parse_with_options("HORIZONTAL(4):\n:Term|One\n:Term|Two", &options).html;
+    assert_eq!(
+        output.trim(),
+        r#"<dl class="row"><dt class="col-sm-4">Term</dt><dd class="col-sm-8">One</dd><dd class="col-sm-8 offset-sm-4">Two</dd></dl>"#
+    );
+}
+
 #[test]
 fn test_mixed_bootstrap_features() {
     let input = r#"
@@ -191,3 +215,172 @@ fn test_strikethrough_compatibility() {
     assert!(output.contains("<s>UMD strikethrough</s>"));
     assert!(output.contains("<del>GFM strikethrough</del>"));
 }
+
+#[test]
+fn test_gfm_and_umd_tables_render_alike() {
+    let gfm = parse("| ~A | ~B |\n|---|---|\n| ~Row | BASELINE: Cell |");
+    let umd = parse("| ~A | ~B |h\n| ~Row | BASELINE: Cell |");
+
+    for output in [&gfm, &umd] {
+        assert!(output.contains(r#"<thead><tr><th scope="col">A</th><th scope="col">B</th>"#));
+        assert!(output.contains(
+            r#"<tbody><tr><th scope="row">Row</th><td class="align-baseline">Cell</td>"#
+        ));
+    }
+    assert!(gfm.contains(r#"<table class="table">"#));
+    assert!(umd.contains(r#"<table class="table umd-table">"#));
+}
+
+#[test]
+fn test_umd_table_cells_use_inline_syntax() {
+    let output = parse_with_options(
+        "| [link](/x) |> | **b** `c` |\n| COLOR(red): *r* | &color(blue){x}; | note[^1] |\n\n[^1]: foot",
+        &Default::default(),
+    );
+    let html = output.html;
+    assert!(html.contains(
+        r#"<tr><td><a href="/x">link</a></td><td colspan="2"><strong>b</strong> <code>c</code></td>"#
+    ));
+    assert!(html.contains(r#"<td class="text-red"><em>r</em></td>"#));
+    assert!(html.contains(r#"<span style="color: blue">x</span>"#));
+    assert!(html.contains(r##"<a href="#fn-1" id="fnref-1" data-footnote-ref>1</a>"##));
+    assert!(output.footnotes.is_some_and(|f| f.contains("foot")));
+}
+
+#[test]
+fn test_umd_table_in_code_block_untouched() {
+    let output = parse("```\n| a | b |\n```");
+    assert!(output.contains("<pre><code>| a | b |\n</code></pre>"));
+    assert!(!output.contains("<table"));
+}
+
+#[test]
+fn test_table_plugin_variants() {
+    let output =
+        parse("@table(striped,hover,sm,caption=Sales){{\n| a | b |\n|---|---|\n| 1 | 2 |\n}}");
+    assert!(output.contains(
+        r#"<table class="table table-striped table-hover table-sm"><caption>Sales</caption><thead>"#
+    ));
+    assert!(!output.contains("umd-plugin"));
+
+    let output = parse("@table(dark,responsive){{\n|> | a |\n| b | c |\n}}");
+    assert!(
+        output.contains(
+            r#"<div class="table-responsive"><table class="table umd-table table-dark">"#
+        )
+    );
+}
+
+#[test]
+fn test_table_placement() {
+    let output = parse("CENTER:\n@table(bordered){{\n| a | b |\n}}");
+    assert!(
+        output.contains(
+            r#"<div class="w-auto mx-auto"><table class="table umd-table table-bordered">"#
+        )
+    );
+
+    let output = parse("JUSTIFY:\n| a |\n|---|\n| b |");
+    assert!(output.contains(r#"<div class="w-100"><table class="table">"#));
+    assert!(!output.contains("JUSTIFY:"));
+}
+
+#[test]
+fn test_pukiwiki_row_modifiers() {
+    let output = parse(
+        "|RIGHT:|CENTER:|c\n|~Item|~Qty|h\n|Apple|3|\n|~Total|LEFT: 3|f\n\n@table(striped){{\n|LEFT:60|c\n|x|\n}}",
+    );
+    assert!(
+        output.contains(
+            r#"<thead><tr><th class="text-end" scope="col">Item</th><th class="text-center" scope="col">Qty</th>"#
+        )
+    );
+    assert!(
+        output.contains(
+            r#"<tbody><tr><td class="text-end">Apple</td><td class="text-center">3</td>"#
+        )
+    );
+    assert!(output.contains(
+        r#"<tfoot><tr><th class="text-end" scope="row">Total</th><td class="text-start">3</td>"#
+    ));
+    assert!(output.contains(r#"<td class="text-start" style="width: 60px">x</td>"#));
+}
+
+#[test]
+fn test_csv_blocks() {
+    let output = parse(
+        "```csv header\nName,Price\n\"Apple, red\",\"1,200\"\n<b>,80\n```\n\n```tsv\na\t\"b\nc\"\n```\n\n@csv(delimiter=;){{\nx;1.5\n}}",
+    );
+    assert!(output.contains(
+        r#"<table class="table csv-table"><thead><tr><th scope="col">Name</th><th class="text-end" scope="col">Price</th></tr></thead>"#
+    ));
+    assert!(output.contains(r#"<td>Apple, red</td><td class="text-end">1,200</td>"#));
+    assert!(output.contains("<td>&lt;b&gt;</td>"));
+    assert!(output.contains("<td>a</td><td>b<br />c</td>"));
+    assert!(output.contains(r#"<td>x</td><td class="text-end">1.5</td>"#));
+    assert!(!output.contains("<pre>"));
+}
+
+#[test]
+fn test_extract_tables() {
+    let input = "| Item | Qty |\n|---|--:|\n| **Pen** | 3 |\n\nText\n\n@table(caption=Stock){{\n|~Shop|~Open|h\n|Main &amp; Co|COLOR(red): 9-5|\n}}\n\n```csv\nx,\"y\nz\"\n```";
+    let tables = extract_tables(input);
+    assert_eq!(tables.len(), 3);
+
+    assert_eq!(tables[0].rows[1].cells[0].text, "Pen");
+    assert_eq!(tables[0].rows[1].cells[1].classes, vec!["text-end"]);
+    assert_eq!(tables[0].to_csv(), "Item,Qty\r\nPen,3\r\n");
+
+    assert_eq!(tables[1].caption.as_deref(), Some("Stock"));
+    assert_eq!(tables[1].rows[1].cells[0].text, "Main & Co");
+    assert_eq!(tables[1].rows[1].cells[1].classes, vec!["text-red"]);
+
+    assert!(tables[2].to_csv().starts_with("x,\"y\nz\""));
+    let json = serde_json::to_string(&tables).unwrap();
+    assert!(json.contains(r#""caption":"Stock""#));
+}
+
+#[test]
+fn test_table_formulas() {
+    let input = "|~Item|~Qty|~Price|h\n|Pen|2|120|\n|Ink|1|1,080|\n|~Total|=SUM(col)|RIGHT: =SUM(C2:C3)|f\n|~Average||=AVG(C2:C3)|f\n\n|a|=SUM(B2)|\n|b|=SUM(B1)|";
+    let options = universal_markdown::parser::ParserOptions {
+        table_formulas: true,
+        ..Default::default()
+    };
+    let result = parse_with_options(input, &options);
+    let html = &result.html;
+    assert!(html.contains(r#"<td data-formula="=SUM(col)">3</td>"#));
+    assert!(html.contains(r#"<td class="text-end" data-formula="=SUM(C2:C3)">1200</td>"#));
+    assert!(html.contains(r#"<td data-formula="=AVG(C2:C3)">600</td>"#));
+    assert!(html.contains(r#"<td data-formula="=SUM(B2)">#CYCLE!</td>"#));
+    let cycles: Vec<_> = result
+        .diagnostics
+        .iter()
+        .filter(|d| d.code == "table-formula-cycle")
+        .collect();
+    assert_eq!(cycles.len(), 1);
+
+    // Formulas are opt-in
+    assert!(parse(input).contains("<td>=SUM(col)</td>"));
+}
+
+#[test]
+fn test_accessible_tables() {
+    let output =
+        parse("|~Region|>|~Q1|h\n|^|~Jan|~Feb|h\n|~North|1|2|\n|^|3|4|\nTable: Sales by region");
+    assert!(output.contains(r#"<caption>Sales by region</caption>"#));
+    assert!(output.contains(r#"<th id="umd-table-0-0-0" scope="col" rowspan="2">Region</th>"#));
+    assert!(output.contains(
+        r#"<th id="umd-table-0-2-0" scope="rowgroup" rowspan="2" headers="umd-table-0-0-0">North</th>"#
+    ));
+    assert!(
+        output.contains(r#"<td headers="umd-table-0-0-1 umd-table-0-1-1 umd-table-0-2-0">3</td>"#)
+    );
+
+    // Simple tables rely on scope alone
+    let output = parse("Table: Stock\n| Item | Qty |\n|---|---|\n| Pen | 2 |");
+    assert!(output.contains(
+        r#"<table class="table"><caption>Stock</caption><thead><tr><th scope="col">Item</th>"#
+    ));
+    assert!(!output.contains("headers="));
+}
@@ -0,0 +1,51 @@
+//! CJK mode tests
+//!
+//! Emphasis next to CJK punctuation and soft breaks between Japanese
+//! lines must render naturally with `ParserOptions::cjk`.
+
+use universal_markdown::parse_with_options;
+use universal_markdown::parser::ParserOptions;
+
+fn render(input: &str, cjk: bool) -> String {
+    let options = ParserOptions {
+        cjk,
+        ..Default::default()
+    };
+    parse_with_options(input, &options).html
+}
+
+#[test]
+fn test_markdown_emphasis_around_cjk_punctuation() {
+    let input = "これは**「強調」**です。";
+    assert!(!render(input, false).contains("<strong>"));
+    assert!(render(input, true).contains("<strong>「強調」</strong>です。"));
+
+    assert!(render("*「斜体」*です", true).contains("<em>「斜体」</em>です"));
+}
+
+#[test]
+fn test_soft_breaks_between_cjk_lines() {
+    let input = "一行目の文章\n二行目の文章\nEnglish line";
+    assert!(render(input, false).contains("一行目の文章\n二行目の文章"));
+
+    let html = render(input, true);
+    assert!(html.contains("一行目の文章二行目の文章\nEnglish line"));
+}
+
+#[test]
+fn test_soft_breaks_around_emphasis() {
+    let html = render("**太字**\nの後に続く文", true);
+    assert!(html.contains("<strong>太字</strong>の後に続く文"));
+}
+
+#[test]
+fn test_code_blocks_untouched() {
+    let html = render("```\n一行目\n二行目\n```", true);
+    assert!(html.contains("一行目\n二行目"));
+}
+
+#[test]
+fn test_umd_emphasis() {
+    let html = render("''強''調、''太字''。'''斜体'''、__下線__した", true);
+    assert!(html.contains("<b>強</b>調、<b>太字</b>。<i>斜体</i>、<u>下線</u>した"));
+}
@@ -183,8 +183,8 @@ fn test_commonmark_autolinks() {
 fn test_gfm_tables() {
     let input = "| foo | bar |\n| --- | --- |\n| baz | bim |";
     let html = parse_to_html(input, &ParserOptions::default());
-    assert!(html.contains("<table>"));
-    assert!(html.contains("<th>foo</th>"));
+    assert!(html.contains(r#"<table class="table">"#));
+    assert!(html.contains(r#"<th scope="col">foo</th>"#));
     assert!(html.contains("<td>baz</td>"));
 }
 
@@ -105,3 +105,42 @@ fn test_multiline_content() {
     assert!(output.contains(r#"<blockquote class="umd-blockquote">"#));
     assert!(output.contains("color: blue"));
 }
+
+#[test]
+fn test_typed_markers_are_not_interpreted() {
+    let input = "{{DEFINITION_LIST:[[\"<img onerror=alert(1)>\",\"x\"]]:DEFINITION_LIST}}\n\n\
+                 {{BLOCK_DECORATION:COLOR(red): x:BLOCK_DECORATION}}\n\n\
+                 {{UMD_BLOCKQUOTE:x:UMD_BLOCKQUOTE}} <!--CODE_BLOCK_0-->";
+    let output = parse(input);
+    assert!(!output.contains("<img"));
+    assert!(!output.contains("<dl>"));
+    assert!(!output.contains("<blockquote"));
+    assert!(!output.contains("style="));
+}
+
+#[test]
+fn test_forged_tokens_are_scrubbed() {
+    let output = parse("a \u{FDD0}P0\u{FDD1} b \u{FDD0}Q\u{FDD1}");
+    assert_eq!(output.trim(), "<p>a P0 b Q</p>");
+}
+
+#[test]
+fn test_plugin_syntax_in_code_kept_verbatim() {
+    let output = parse("`&color(red){<b>};`\n\n```\n:term|definition\n```");
+    assert!(output.contains("<code>&amp;color(red){&lt;b&gt;};</code>"));
+    assert!(output.contains(":term|definition"));
+    assert!(!output.contains("<dl>"));
+}
+
+#[test]
+fn test_entity_or_plugin_resolution() {
+    // Known names are entities, unknown names are plugins, malformed
+    // references are literal text
+    let output = parse("&hearts; &rarr; &real; &counter; &Hearts; &#xZZ;");
+    assert!(output.contains("♥ → ℜ"));
+    assert!(output.contains(r#"<template class="umd-plugin umd-plugin-counter">"#));
+    assert!(output.contains(r#"<template class="umd-plugin umd-plugin-Hearts">"#));
+    assert!(output.contains("&amp;#xZZ;"));
+    assert!(!output.contains("umd-plugin-hearts"));
+    assert!(!output.contains("umd-plugin-rarr"));
+}
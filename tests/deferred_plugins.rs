@@ -0,0 +1,85 @@
+//! Integration tests for deferred plugin resolution
+
+use std::collections::HashMap;
+use universal_markdown::parser::ParserOptions;
+use universal_markdown::{PluginKind, hydrate, parse_with_options};
+
+fn deferred_options() -> ParserOptions {
+    ParserOptions {
+        deferred_plugins: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_deferred_mode_emits_placeholders() {
+    let input = "@rss(https://example.com/feed, 5)\n\nVisits: &counter;";
+    let result = parse_with_options(input, &deferred_options());
+
+    assert!(!result.html.contains("<template"));
+    assert_eq!(result.pending_plugins.len(), 2);
+
+    let names: Vec<&str> = result
+        .pending_plugins
+        .iter()
+        .map(|p| p.name.as_str())
+        .collect();
+    assert!(names.contains(&"rss"));
+    assert!(names.contains(&"counter"));
+
+    let rss = result
+        .pending_plugins
+        .iter()
+        .find(|p| p.name == "rss")
+        .unwrap();
+    assert_eq!(rss.kind, PluginKind::Block);
+    assert_eq!(rss.args, vec!["https://example.com/feed", "5"]);
+}
+
+#[test]
+fn test_default_mode_unchanged() {
+    let result = parse_with_options("&counter;", &ParserOptions::default());
+    assert!(
+        result
+            .html
+            .contains(r#"<template class="umd-plugin umd-plugin-counter">"#)
+    );
+    assert!(result.pending_plugins.is_empty());
+    assert!(result.plugin_nonce.is_none());
+}
+
+#[test]
+fn test_hydrate_block_plugin() {
+    let result = parse_with_options("@include(Page){{ fallback }}", &deferred_options());
+    let pending = &result.pending_plugins[0];
+    assert_eq!(pending.body.as_deref(), Some(" fallback "));
+
+    let results = HashMap::from([(pending.id.clone(), "<div>Included</div>".to_string())]);
+    let hydrated = hydrate(&result, &results);
+
+    assert_eq!(hydrated.html.trim(), "<div>Included</div>");
+    assert!(hydrated.pending_plugins.is_empty());
+}
+
+#[test]
+fn test_partial_hydration_keeps_pending() {
+    let result = parse_with_options("&a; and &b;", &deferred_options());
+    let first = result.pending_plugins[0].id.clone();
+
+    let hydrated = hydrate(&result, &HashMap::from([(first, "A".to_string())]));
+    assert_eq!(hydrated.pending_plugins.len(), 1);
+    assert!(hydrated.html.contains("<!--umd-plugin:"));
+}
+
+#[test]
+fn test_placeholder_cannot_be_forged() {
+    let result = parse_with_options("&counter;", &deferred_options());
+    let id = result.pending_plugins[0].id.clone();
+    let nonce = result.plugin_nonce.clone().unwrap();
+
+    // Typing a placeholder only produces escaped text
+    let forged_input = format!("<!--umd-plugin:{}:{}-->", nonce, id);
+    let forged = parse_with_options(&forged_input, &deferred_options());
+    let hydrated = hydrate(&forged, &HashMap::from([(id, "<b>x</b>".to_string())]));
+    assert!(!hydrated.html.contains("<b>x</b>"));
+}
@@ -0,0 +1,141 @@
+//! Resource limit tests
+//!
+//! Oversized or deeply nested input must be rejected by
+//! `try_parse_with_options` and degrade to escaped text in
+//! `parse_with_options`.
+
+use std::time::{Duration, Instant};
+use universal_markdown::extract_plugin_calls;
+use universal_markdown::limits::NestingKind;
+use universal_markdown::parser::ParserOptions;
+use universal_markdown::{LimitError, Limits, parse_with_options, try_parse_with_options};
+
+fn options(limits: Limits) -> ParserOptions {
+    ParserOptions {
+        limits,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_default_limits_allow_ordinary_documents() {
+    let input = "# Title\n\n> quote\n\n- a\n  - b\n\n|a|b|\n|c|d|\n\n&color(red){x}; @toc()";
+    let result = try_parse_with_options(input, &ParserOptions::default()).unwrap();
+    assert!(result.html.contains("<h1"));
+    assert!(result.diagnostics.is_empty());
+}
+
+#[test]
+fn test_deep_blockquote_degrades_to_text() {
+    let input = format!("{}<b>x</b>", "> ".repeat(40));
+    let result = parse_with_options(&input, &ParserOptions::default());
+    assert!(
+        result
+            .html
+            .starts_with("<pre class=\"umd-source\">&gt; &gt;")
+    );
+    assert!(result.html.contains("&lt;b&gt;x&lt;/b&gt;"));
+    assert_eq!(result.diagnostics.len(), 1);
+    assert_eq!(result.diagnostics[0].code, "nesting-too-deep");
+
+    assert_eq!(
+        try_parse_with_options(&input, &ParserOptions::default()).unwrap_err(),
+        LimitError::NestingTooDeep {
+            kind: NestingKind::Blockquote,
+            depth: 40,
+            limit: 32,
+        }
+    );
+}
+
+#[test]
+fn test_table_cells() {
+    let input = "|a|b|c|\n|d|e|f|";
+    let limits = Limits {
+        max_table_cells: 5,
+        ..Default::default()
+    };
+    let result = parse_with_options(input, &options(limits));
+    assert!(!result.html.contains("<table"));
+    assert_eq!(result.diagnostics[0].code, "too-many-table-cells");
+}
+
+#[test]
+fn test_csv_table_cells() {
+    let limits = Limits {
+        max_table_cells: 5,
+        ..Default::default()
+    };
+    for input in [
+        "```csv\na,b,c\nd,e,f\n```",
+        "```tsv\na\tb\tc\nd\te\tf\n```",
+        "@csv(){{\na,b,c\nd,e,f\n}}",
+    ] {
+        let result = parse_with_options(input, &options(limits.clone()));
+        assert!(!result.html.contains("<table"), "{}", input);
+        assert_eq!(result.diagnostics[0].code, "too-many-table-cells");
+        assert_eq!(
+            try_parse_with_options(input, &options(limits.clone())).unwrap_err(),
+            LimitError::TooManyTableCells { count: 6, limit: 5 }
+        );
+    }
+
+    // Within the limit, and code blocks are not tables
+    let input = "```csv\na,b\n```\n\n````\n```csv\na,b,c\nd,e,f\n```\n````";
+    assert!(try_parse_with_options(input, &options(limits)).is_ok());
+}
+
+#[test]
+fn test_plugin_calls() {
+    let input = "&a; &b; &c;";
+    let limits = Limits {
+        max_plugin_calls: 2,
+        ..Default::default()
+    };
+    assert!(matches!(
+        try_parse_with_options(input, &options(limits)),
+        Err(LimitError::TooManyPluginCalls { count: 3, limit: 2 })
+    ));
+}
+
+#[test]
+fn test_output_size() {
+    let limits = Limits {
+        max_output_bytes: 32,
+        ..Default::default()
+    };
+    let input = "**bold** text that renders to more than thirty-two bytes";
+    assert!(matches!(
+        try_parse_with_options(input, &options(limits.clone())),
+        Err(LimitError::OutputTooLarge { limit: 32, .. })
+    ));
+
+    // The fallback itself stays within the limit
+    let result = parse_with_options(input, &options(limits));
+    assert!(result.html.len() <= 32);
+    assert!(result.html.ends_with("</pre>"));
+    assert_eq!(result.diagnostics[0].code, "output-too-large");
+}
+
+#[test]
+fn test_unlimited() {
+    let input = "- ".repeat(40) + "x";
+    let result = try_parse_with_options(&input, &options(Limits::unlimited())).unwrap();
+    assert_eq!(result.html.matches("<ul>").count(), 40);
+}
+
+#[test]
+fn test_many_plugin_calls_rejected_quickly() {
+    // Detecting the calls used to be quadratic, taking minutes on this input
+    let input = "&a; ".repeat(128_000);
+    let start = Instant::now();
+    assert!(matches!(
+        try_parse_with_options(&input, &options(Limits::default())),
+        Err(LimitError::TooManyPluginCalls {
+            count: 1001,
+            limit: 1000
+        })
+    ));
+    assert_eq!(extract_plugin_calls(&input).len(), 128_000);
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
@@ -0,0 +1,67 @@
+//! Integration tests for the link policy
+
+use universal_markdown::link_policy::{LinkPolicy, SchemePolicy};
+use universal_markdown::parse_with_options;
+use universal_markdown::parser::ParserOptions;
+
+fn render(input: &str, policy: LinkPolicy) -> String {
+    let options = ParserOptions {
+        link_policy: policy,
+        ..Default::default()
+    };
+    parse_with_options(input, &options).html
+}
+
+#[test]
+fn test_external_links_get_rel() {
+    let html = render(
+        "[a](https://example.org/) [b](/local)",
+        LinkPolicy::default(),
+    );
+    assert!(html.contains(r#"<a href="https://example.org/" rel="nofollow ugc noopener">a</a>"#));
+    assert!(html.contains(r#"<a href="/local">b</a>"#));
+}
+
+#[test]
+fn test_internal_hosts_and_target() {
+    let policy = LinkPolicy {
+        internal_hosts: vec!["example.com".to_string()],
+        external_target: Some("_blank".to_string()),
+        external_class: Some("link-external".to_string()),
+        ..Default::default()
+    };
+    let html = render(
+        "[in](https://www.example.com/) [out](https://example.org/)",
+        policy,
+    );
+    assert!(html.contains(r#"<a href="https://www.example.com/">in</a>"#));
+    assert!(html.contains(
+        r#"<a href="https://example.org/" rel="nofollow ugc noopener" target="_blank" class="link-external">out</a>"#
+    ));
+}
+
+#[test]
+fn test_policy_applies_to_badges_and_images() {
+    let policy = LinkPolicy {
+        schemes: SchemePolicy::Allow(vec!["https".to_string()]),
+        ..Default::default()
+    };
+    let html = render(
+        "&badge(info){[x](http://example.org/)}; ![i](http://example.org/i.png)",
+        policy,
+    );
+    assert!(html.contains(r##"<a href="#blocked-url" class="badge bg-info">x</a>"##));
+    assert!(html.contains(r##"<img src="#blocked-url" alt="i" />"##));
+}
+
+#[test]
+fn test_data_images_are_optional() {
+    let input = "![i](data:image/png;base64,AAAA)";
+    assert!(render(input, LinkPolicy::default()).contains(r##"src="#blocked-url""##));
+
+    let policy = LinkPolicy {
+        allow_data_images: true,
+        ..Default::default()
+    };
+    assert!(render(input, policy).contains(r#"src="data:image/png;base64,AAAA""#));
+}
@@ -0,0 +1,103 @@
+//! Security report tests
+//!
+//! Everything the parser escapes, blocks or drops must be listed in
+//! `ParseResult.security_report` with a span pointing at the source.
+
+use universal_markdown::{ParseResult, parse_with_frontmatter};
+use universal_markdown::{SecurityEventKind, parse};
+
+fn spans(result: &ParseResult, input: &str) -> Vec<(SecurityEventKind, &'static str, String)> {
+    result
+        .security_report
+        .iter()
+        .map(|e| (e.kind, e.rule, input[e.span.clone()].to_string()))
+        .collect()
+}
+
+#[test]
+fn test_clean_document_has_empty_report() {
+    let result = parse_with_frontmatter("# Title\n\n[link](https://example.com) `<b>`");
+    assert!(result.security_report.is_empty());
+}
+
+#[test]
+fn test_report_matches_output() {
+    let input = "---\ntitle: t\n---\n[x](javascript:alert(1)) <img src=x onerror=alert(1)>\n\n&color(red;top:0){y};";
+    let result = parse_with_frontmatter(input);
+
+    assert!(!result.html.contains("javascript:"));
+    assert!(result.html.contains("&lt;img"));
+    assert!(!result.html.contains("top:0"));
+
+    assert_eq!(
+        spans(&result, input),
+        vec![
+            (
+                SecurityEventKind::BlockedUrl,
+                "link-policy",
+                "javascript:alert(1)".to_string()
+            ),
+            (
+                SecurityEventKind::EscapedTag,
+                "raw-html",
+                "<img src=x onerror=alert(1)>".to_string()
+            ),
+            (
+                SecurityEventKind::RejectedAttribute,
+                "event-handler",
+                "onerror=alert(1)".to_string()
+            ),
+            (
+                SecurityEventKind::InvalidStyleValue,
+                "invalid-color",
+                "red;top:0".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_report_agrees_with_html() {
+    let input = "Note: red;top:0 is bad. &color(red;top:0){a};\n\n    <b onclick=x>\n\n[x](java&#09;script:alert(1)) [y](<javascript:alert(2)>) [z](javascript:alert(3))";
+    let result = parse_with_frontmatter(input);
+
+    let blocked = result
+        .security_report
+        .iter()
+        .filter(|e| e.kind == SecurityEventKind::BlockedUrl)
+        .count();
+    assert_eq!(blocked, result.html.matches("#blocked-url").count());
+
+    let decoration = input.find("&color(").unwrap() + "&color(".len();
+    assert_eq!(
+        result
+            .security_report
+            .iter()
+            .map(|e| (e.kind, e.span.clone()))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                SecurityEventKind::InvalidStyleValue,
+                decoration..decoration + "red;top:0".len()
+            ),
+            (
+                SecurityEventKind::BlockedUrl,
+                input.find("javascript:alert(3)").unwrap()..input.len() - 1
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_table_cell_style_value_located() {
+    let input = "|a|SIZE(1em;top:0):b|";
+    let result = parse_with_frontmatter(input);
+    assert_eq!(result.security_report.len(), 1);
+    assert_eq!(&input[result.security_report[0].span.clone()], "1em;top:0");
+    assert_eq!(result.security_report[0].rule, "invalid-length");
+}
+
+#[test]
+fn test_parse_output_unchanged() {
+    assert!(parse("<script>x</script>").contains("&lt;script&gt;"));
+}
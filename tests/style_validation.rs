@@ -0,0 +1,149 @@
+//! Custom style value validation tests
+//!
+//! COLOR()/SIZE() values that are not plain colors or lengths must never
+//! reach a `style` attribute.
+
+use universal_markdown::extensions::styles::StylePolicy;
+use universal_markdown::parse_with_options;
+use universal_markdown::parser::ParserOptions;
+
+fn render(input: &str) -> universal_markdown::ParseResult {
+    parse_with_options(input, &ParserOptions::default())
+}
+
+#[test]
+fn test_inline_color_injection_dropped() {
+    let result = render("&color(red;position:fixed;inset:0){x};");
+    assert!(!result.html.contains("style="));
+    assert!(!result.html.contains("position"));
+    assert!(result.html.contains('x'));
+    assert_eq!(result.diagnostics.len(), 1);
+    assert_eq!(result.diagnostics[0].code, "invalid-style-value");
+}
+
+#[test]
+fn test_inline_size_injection_dropped() {
+    let result = render("&size(1px;top:0){x};");
+    assert!(!result.html.contains("style="));
+    assert!(!result.html.contains("top"));
+    assert_eq!(result.diagnostics[0].code, "invalid-style-value");
+}
+
+#[test]
+fn test_block_decoration_injection_dropped() {
+    let result = render("COLOR(red;position:fixed): text\n\nSIZE(1px;top:0): text");
+    assert!(!result.html.contains("style="));
+    assert_eq!(result.diagnostics.len(), 2);
+}
+
+#[test]
+fn test_table_cell_injection_dropped() {
+    let result = render("|COLOR(red;position:fixed):a|SIZE(1em;top:0):b|");
+    assert!(!result.html.contains("style="));
+    assert!(result.html.contains("<td>a</td>"));
+    assert_eq!(result.diagnostics.len(), 2);
+}
+
+#[test]
+fn test_valid_custom_values_kept() {
+    let result = render("&color(#abc,Transparent){x}; &size(1.3rem){y};\n\n|COLOR(#f00):a|");
+    assert!(
+        result
+            .html
+            .contains(r#"style="color: #abc; background-color: Transparent""#)
+    );
+    assert!(result.html.contains(r#"style="font-size: 1.3rem""#));
+    assert!(result.html.contains(r#"style="color: #f00""#));
+    assert!(result.diagnostics.is_empty());
+}
+
+#[test]
+fn test_disallow_inline_styles() {
+    let options = ParserOptions {
+        style_policy: StylePolicy::Disallow,
+        ..Default::default()
+    };
+    let result = parse_with_options(
+        "&color(primary,#abc){x}; &size(2){y}; &size(1.3rem){z};\n\n|COLOR(#f00):a|",
+        &options,
+    );
+    assert!(!result.html.contains("style="));
+    assert!(
+        result
+            .html
+            .contains(r#"<span class="text-primary">x</span>"#)
+    );
+    assert!(result.html.contains(r#"<span class="fs-2">y</span>"#));
+    assert_eq!(result.diagnostics.len(), 3);
+    assert!(
+        result
+            .diagnostics
+            .iter()
+            .all(|d| d.code == "inline-style-disallowed")
+    );
+}
+
+#[test]
+fn test_class_policy_collects_stylesheet() {
+    let options = ParserOptions {
+        style_policy: StylePolicy::Classes,
+        sanitize_output: true,
+        ..Default::default()
+    };
+    let result = parse_with_options(
+        "&color(#abc){x}; &size(1.3rem){y};\n\nCOLOR(#abc): block\n\n|COLOR(#abc,primary):a|",
+        &options,
+    );
+    assert!(!result.html.contains("style="));
+
+    let stylesheet = result.stylesheet.expect("stylesheet");
+    let rules: Vec<&str> = stylesheet.lines().collect();
+    assert_eq!(rules.len(), 2);
+    assert!(rules[0].ends_with("{color: #abc}"));
+    assert!(rules[1].ends_with("{font-size: 1.3rem}"));
+
+    // Every use of the same value shares one class
+    let class = rules[0][1..].split(' ').next().unwrap();
+    assert!(class.starts_with("umd-c-"));
+    assert_eq!(result.html.matches(class).count(), 3);
+    assert!(result.html.contains("bg-primary"));
+    assert!(result.diagnostics.is_empty());
+}
+
+#[test]
+fn test_class_policy_cell_overrides_column() {
+    let options = ParserOptions {
+        style_policy: StylePolicy::Classes,
+        ..Default::default()
+    };
+    // The cell's class is registered before the column's, so both in one
+    // cell would leave the column color in effect
+    let result = parse_with_options(
+        "&color(#def){y};\n\n|COLOR(#abc):|c\n|COLOR(#def):y|\n|z|",
+        &options,
+    );
+    let stylesheet = result.stylesheet.expect("stylesheet");
+    let class = |declaration: &str| {
+        let rule = stylesheet
+            .lines()
+            .find(|rule| rule.ends_with(declaration))
+            .unwrap();
+        rule[1..].split(' ').next().unwrap().to_string()
+    };
+    let (own, column) = (class("{color: #def}"), class("{color: #abc}"));
+    assert!(
+        result
+            .html
+            .contains(&format!(r#"<td class="{}">y</td>"#, own))
+    );
+    assert!(
+        result
+            .html
+            .contains(&format!(r#"<td class="{}">z</td>"#, column))
+    );
+}
+
+#[test]
+fn test_no_stylesheet_without_class_policy() {
+    assert_eq!(render("&color(#abc){x};").stylesheet, None);
+}
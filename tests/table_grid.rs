@@ -0,0 +1,196 @@
+//! UMD table grid normalization tests
+//!
+//! Whatever the mix of ragged rows and `|>`/`|^` markers, a UMD table must
+//! render as a valid HTML table: every row of a section covers the same
+//! number of columns, cells never overlap and rowspans stay within their
+//! section. Repairs are reported as diagnostics.
+
+use once_cell::sync::Lazy;
+use proptest::prelude::*;
+use regex::Regex;
+use universal_markdown::parser::ParserOptions;
+use universal_markdown::{parse, parse_with_options};
+
+static ROW: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<tr>(.*?)</tr>").unwrap());
+static CELL: Lazy<Regex> = Lazy::new(|| Regex::new(r"<t[hd]( [^>]*)?>").unwrap());
+static SPAN: Lazy<Regex> = Lazy::new(|| Regex::new(r#"(colspan|rowspan)="(\d+)""#).unwrap());
+
+/// Cells of each `<tr>` of a section as (colspan, rowspan)
+fn section(html: &str, tag: &str) -> Vec<Vec<(usize, usize)>> {
+    let Some(start) = html.find(&format!("<{}>", tag)) else {
+        return Vec::new();
+    };
+    let end = html.find(&format!("</{}>", tag)).expect("unclosed section");
+    ROW.captures_iter(&html[start..end])
+        .map(|row| {
+            CELL.captures_iter(&row[1])
+                .map(|cell| {
+                    let attributes = cell.get(1).map_or("", |a| a.as_str());
+                    let mut span = (1, 1);
+                    for attribute in SPAN.captures_iter(attributes) {
+                        let value = attribute[2].parse().unwrap();
+                        match &attribute[1] {
+                            "colspan" => span.0 = value,
+                            _ => span.1 = value,
+                        }
+                    }
+                    span
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Check the occupancy of a section, returning its width
+fn check_section(rows: &[Vec<(usize, usize)>]) -> Result<Option<usize>, String> {
+    let mut grid: Vec<Vec<bool>> = vec![Vec::new(); rows.len()];
+    for (r, row) in rows.iter().enumerate() {
+        let mut column = 0;
+        for &(colspan, rowspan) in row {
+            while grid[r].get(column).copied().unwrap_or(false) {
+                column += 1;
+            }
+            if r + rowspan > rows.len() {
+                return Err(format!("rowspan leaves the section at row {}", r));
+            }
+            for covered in &mut grid[r..r + rowspan] {
+                if covered.len() < column + colspan {
+                    covered.resize(column + colspan, false);
+                }
+                for slot in &mut covered[column..column + colspan] {
+                    if *slot {
+                        return Err(format!("cells overlap in row {}", r));
+                    }
+                    *slot = true;
+                }
+            }
+            column += colspan;
+        }
+    }
+    let width = grid.first().map(Vec::len);
+    for (r, covered) in grid.iter().enumerate() {
+        if Some(covered.len()) != width || !covered.iter().all(|&slot| slot) {
+            return Err(format!("row {} does not fill the grid", r));
+        }
+    }
+    Ok(width)
+}
+
+fn check_table(html: &str) -> Result<(), String> {
+    let mut width = None;
+    for tag in ["thead", "tbody", "tfoot"] {
+        if let Some(section_width) = check_section(&section(html, tag))? {
+            if width.is_some_and(|width| width != section_width) {
+                return Err(format!("{} is {} columns wide", tag, section_width));
+            }
+            width = Some(section_width);
+        }
+    }
+    Ok(())
+}
+
+fn table_source() -> impl Strategy<Value = String> {
+    let cell = prop_oneof![Just("x"), Just("~h"), Just(""), Just(">"), Just("^")];
+    let row = (
+        prop::collection::vec(cell, 1..5),
+        prop_oneof![Just(""), Just("h"), Just("f")],
+    )
+        .prop_map(|(cells, modifier)| format!("|{}|{}", cells.join("|"), modifier));
+    prop::collection::vec(row, 1..6).prop_map(|rows| rows.join("\n"))
+}
+
+proptest! {
+    #[test]
+    fn prop_umd_tables_render_valid_grids(source in table_source()) {
+        let html = parse(&source);
+        prop_assert_eq!(html.matches("<table").count(), html.matches("</table>").count());
+        for table in html.split("<table").skip(1) {
+            let table = &table[..table.find("</table>").unwrap()];
+            if let Err(problem) = check_table(table) {
+                return Err(TestCaseError::fail(format!("{}: {}", problem, html)));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_repairs_are_reported() {
+    let result = parse_with_options("|^|a|b|\n|>|c|\n|d|^|e|\n|f|", &ParserOptions::default());
+    let codes: Vec<_> = result.diagnostics.iter().map(|d| d.code).collect();
+    assert_eq!(
+        codes,
+        vec![
+            "table-ragged-row",
+            "table-ragged-row",
+            "table-span-invalid",
+            "table-span-overlap"
+        ]
+    );
+    assert!(result.html.contains(
+        "<tbody><tr><td></td><td>a</td><td>b</td></tr><tr><td colspan=\"2\">c</td><td></td></tr><tr><td>d</td><td></td><td>e</td></tr><tr><td>f</td><td></td><td></td></tr></tbody>"
+    ));
+    check_table(&result.html).unwrap();
+}
+
+#[test]
+fn test_documented_spans() {
+    let html = parse("|> | ~Header2 | ~Header3 |h\n|> | Cell2 | Cell3 |\n|^ |^ | Cell4 |");
+    assert!(html.contains(r#"<th id="umd-table-0-0-0" scope="colgroup" colspan="2">Header2</th>"#));
+    assert!(html.contains(r#"<td colspan="2" rowspan="2" headers="umd-table-0-0-0">Cell2</td>"#));
+    assert!(html.contains(r#"<tr><td headers="umd-table-0-0-2">Cell4</td></tr>"#));
+    check_table(&html).unwrap();
+}
+
+#[test]
+fn test_large_table_renders_quickly() {
+    // Normalizing the grid used to rescan the section for every cell
+    let rows = 8_000;
+    let source = "|a|b|c|\n".repeat(rows);
+    let start = std::time::Instant::now();
+    let result = parse_with_options(
+        &source,
+        &ParserOptions {
+            limits: universal_markdown::Limits::unlimited(),
+            ..Default::default()
+        },
+    );
+    assert!(start.elapsed() < std::time::Duration::from_secs(10));
+    assert_eq!(section(&result.html, "tbody").len(), rows);
+}
+
+#[test]
+fn test_header_id_prefix() {
+    let source = "|> | ~Header2 | ~Header3 |h\n| a | b | c |\n\n| > | b |\n|---|---|\n| c | d |";
+    let html = parse_with_options(
+        source,
+        &ParserOptions {
+            table_id_prefix: "doc1-".to_string(),
+            ..Default::default()
+        },
+    )
+    .html;
+    assert!(html.contains(r#"<th id="doc1-umd-table-0-0-0""#));
+    assert!(html.contains(r#"headers="doc1-umd-table-0-0-0""#));
+    assert!(html.contains(r#"<th id="doc1-gfm-table-1-0-0""#));
+    assert!(!html.contains(r#"id="umd-table"#));
+}
+
+#[test]
+fn test_large_irregular_table_renders_quickly() {
+    // Header associations used to scan every cell of the table per cell
+    let rows = 4_000;
+    let source = format!("|>|~Group|~Other|h\n{}", "|~Row|a|b|\n".repeat(rows));
+    let start = std::time::Instant::now();
+    let result = parse_with_options(
+        &source,
+        &ParserOptions {
+            limits: universal_markdown::Limits::unlimited(),
+            ..Default::default()
+        },
+    );
+    assert!(start.elapsed() < std::time::Duration::from_secs(10));
+    assert_eq!(section(&result.html, "tbody").len(), rows);
+    assert!(result.html.contains(
+        r#"<th id="umd-table-0-4000-0" scope="row" headers="umd-table-0-0-0">Row</th><td headers="umd-table-0-0-0 umd-table-0-4000-0">a</td>"#
+    ));
+}
@@ -17,3 +17,68 @@ fn test_semantic_html_integration() {
     println!("COLOR output: {}", html);
     assert!(html.contains("color: red"), "Expected color style, got: {}", html);
 }
+
+#[test]
+fn test_japanese_typography() {
+    // Tate-chu-yoko
+    let html = parse("第&tcy(12);回");
+    assert!(
+        html.contains(r#"第<span class="text-combine-upright">12</span>回"#),
+        "Expected tcy span, got: {}",
+        html
+    );
+
+    // Emphasis marks (傍点)
+    let html = parse("&em(sesame){強調};と&em(circle){丸};と&em(){既定};");
+    assert!(html.contains(r#"<span class="text-emphasis-sesame">強調</span>"#));
+    assert!(html.contains(r#"<span class="text-emphasis-circle">丸</span>"#));
+    assert!(html.contains(r#"<span class="text-emphasis-sesame">既定</span>"#));
+
+    // Unknown shapes fall back to sesame with a diagnostic
+    let result = universal_markdown::parse_with_options("&em(star){x};", &Default::default());
+    assert!(result.html.contains(r#"<span class="text-emphasis-sesame">x</span>"#));
+    assert_eq!(result.diagnostics.len(), 1);
+    assert_eq!(result.diagnostics[0].code, "unknown-emphasis-shape");
+
+    // Vertical writing block
+    let html = parse("VERTICAL: 縦書き&tcy(20);世紀");
+    assert!(
+        html.contains(
+            r#"<p class="writing-mode-vertical-rl">縦書き<span class="text-combine-upright">20</span>世紀</p>"#
+        ),
+        "Expected vertical block, got: {}",
+        html
+    );
+
+    // Not reported as plugin calls
+    assert!(universal_markdown::extract_plugin_calls("&tcy(12); &em(dot){x};").is_empty());
+}
+
+#[test]
+fn test_ruby_forms() {
+    // Group ruby
+    let html = parse("&ruby(あした){明日};");
+    assert!(html.contains("<ruby>明日<rp>(</rp><rt>あした</rt><rp>)</rp></ruby>"));
+
+    // Mono ruby
+    let html = parse("&ruby(かん.じ){漢字};");
+    assert!(
+        html.contains(
+            "<ruby>漢<rp>(</rp><rt>かん</rt><rp>)</rp>字<rp>(</rp><rt>じ</rt><rp>)</rp></ruby>"
+        ),
+        "Expected mono ruby, got: {}",
+        html
+    );
+
+    // Double-sided ruby survives the output sanitizer
+    let options = universal_markdown::parser::ParserOptions {
+        sanitize_output: true,
+        ..Default::default()
+    };
+    let html = universal_markdown::parse_with_options("&ruby(かん.じ,kanji){漢字};", &options).html;
+    assert!(
+        html.contains("</ruby><rp>(</rp><rt>kanji</rt><rp>)</rp></ruby>"),
+        "Expected nested ruby, got: {}",
+        html
+    );
+}
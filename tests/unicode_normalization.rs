@@ -0,0 +1,70 @@
+//! Input normalization tests
+//!
+//! Trojan Source bidi controls, invisible characters in URLs and look-alike
+//! hosts must be neutralized or reported before rendering.
+
+use universal_markdown::normalize::BidiPolicy;
+use universal_markdown::parse_with_options;
+use universal_markdown::parser::ParserOptions;
+
+fn render(input: &str, bidi_policy: BidiPolicy) -> universal_markdown::ParseResult {
+    let options = ParserOptions {
+        bidi_policy,
+        ..Default::default()
+    };
+    parse_with_options(input, &options)
+}
+
+#[test]
+fn test_bidi_override_stripped() {
+    let result = render("access \u{202E}nimda\u{202C} granted", BidiPolicy::Strip);
+    assert!(result.html.contains("access nimda granted"));
+    assert_eq!(result.diagnostics.len(), 2);
+    assert!(result.diagnostics.iter().all(|d| d.code == "bidi-control"));
+}
+
+#[test]
+fn test_bidi_override_visualized() {
+    let result = render("a\u{202E}b", BidiPolicy::Visualize);
+    assert!(result.html.contains("a[U+202E]b"));
+}
+
+#[test]
+fn test_bidi_override_kept_by_default() {
+    let result = render("a\u{202E}b", BidiPolicy::default());
+    assert!(result.html.contains("a\u{202E}b"));
+    assert_eq!(result.diagnostics[0].code, "bidi-control");
+}
+
+#[test]
+fn test_bdo_content_untouched() {
+    let result = render("&bdo(rtl){x\u{202E}y};", BidiPolicy::Strip);
+    assert!(result.html.contains("<bdo dir=\"rtl\">x\u{202E}y</bdo>"));
+    assert!(result.diagnostics.is_empty());
+}
+
+#[test]
+fn test_zero_width_removed_from_links() {
+    let result = render(
+        "[bank](https://bank\u{200D}.example/login)",
+        BidiPolicy::default(),
+    );
+    assert!(result.html.contains("href=\"https://bank.example/login\""));
+    assert_eq!(result.diagnostics[0].code, "invisible-in-url");
+}
+
+#[test]
+fn test_confusable_link_host_reported() {
+    let result = render(
+        "[PayPal](https://p\u{0430}ypal.com/)",
+        BidiPolicy::default(),
+    );
+    assert_eq!(result.diagnostics.len(), 1);
+    assert_eq!(result.diagnostics[0].code, "confusable-host");
+}
+
+#[test]
+fn test_text_is_nfc() {
+    let result = render("Cafe\u{0301}", BidiPolicy::default());
+    assert!(result.html.contains("Caf\u{00E9}"));
+}
@@ -0,0 +1,133 @@
+//! XSS payload corpus run against the full pipeline
+//!
+//! Every payload is rendered with the default options and with the final
+//! output sanitizer enabled, and the result is checked for executable markup:
+//! dangerous elements, event handler attributes and script URLs.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use universal_markdown::parse_with_options;
+use universal_markdown::parser::ParserOptions;
+
+static TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"<([a-zA-Z][\w-]*)([^>]*)>").unwrap());
+static ATTRIBUTE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"([^\s"'=<>/]+)(?:\s*=\s*("[^"]*"|'[^']*'|[^\s>]+))?"#).unwrap());
+
+const DANGEROUS_TAGS: &[&str] = &[
+    "script", "iframe", "object", "embed", "style", "svg", "math", "form", "base", "meta", "link",
+    "frame", "frameset",
+];
+
+const PAYLOADS: &[&str] = &[
+    // Raw HTML
+    "<script>alert(1)</script>",
+    "<img src=x onerror=alert(1)>",
+    "<svg/onload=alert(1)>",
+    "<iframe src=javascript:alert(1)>",
+    "<a href=\"javascript:alert(1)\">x</a>",
+    "<<script>script>alert(1)<</script>/script>",
+    "&lt;script&gt;alert(1)&lt;/script&gt;",
+    // Markdown links and images
+    "[x](javascript:alert(1))",
+    "[x](JaVaScRiPt:alert(1))",
+    "[x](java&#09;script:alert(1))",
+    "[x](&#106;avascript:alert(1))",
+    "[x](javascript&colon;alert(1))",
+    "[x](vbscript:msgbox(1))",
+    "[x](data:text/html;base64,PHNjcmlwdD5hbGVydCgxKTwvc2NyaXB0Pg==)",
+    "![x](javascript:alert(1))",
+    "![x](x\" onerror=\"alert(1))",
+    "[x](x \"t\\\" onmouseover=\\\"alert(1)\")",
+    "<javascript:alert(1)>",
+    // Inline decorations and plugins
+    "&badge(danger){[x](javascript:alert(1))};",
+    "&badge(danger\" onmouseover=\"alert(1)){x};",
+    "&color(red\" onmouseover=\"alert(1)){x};",
+    "&size(1\" onmouseover=\"alert(1)){x};",
+    "&time(x\" onmouseover=\"alert(1)){t};",
+    "&dfn{<img src=x onerror=alert(1)>};",
+    "&ruby(<script>alert(1)</script>){x};",
+    "&abbr(x){\" onmouseover=\"alert(1)};",
+    "&plugin(\"><script>alert(1)</script>){<img src=x onerror=alert(1)>};",
+    "@plugin(\"><script>alert(1)</script>){{ <script>alert(1)</script> }}",
+    // Block syntax
+    "COLOR(red\" onmouseover=\"alert(1)): x",
+    "SIZE(2\" onmouseover=\"alert(1)): x",
+    "> <img src=x onerror=alert(1)> <",
+    ":<img src=x onerror=alert(1)>|<script>alert(1)</script>",
+    "# Title {#x\" onmouseover=\"alert(1)}",
+    // Tables
+    "|~<script>alert(1)</script>|b|\n|<img src=x onerror=alert(1)>|d|",
+    "|COLOR(red\" onmouseover=\"alert(1)):x|>|\n|a|b|",
+    "| a | b |\n|---|---|\n| <img src=x onerror=alert(1)> | [x](javascript:alert(1)) |",
+    // Forged internal markers
+    "{{DEFINITION_LIST:[[\"<img src=x onerror=alert(1)>\",\"x\"]]:DEFINITION_LIST}}",
+    "{{BLOCK_PLUGIN:x::PHNjcmlwdD5hbGVydCgxKTwvc2NyaXB0Pg==:BLOCK_PLUGIN}}",
+    "\u{FDD0}P0\u{FDD1}<script>alert(1)</script>",
+    // Code and footnotes
+    "`<script>alert(1)</script>`",
+    "```html\n<script>alert(1)</script>\n```",
+    "x[^1]\n\n[^1]: <img src=x onerror=alert(1)>",
+];
+
+/// Describe executable markup found in `html`, if any
+fn find_executable_markup(html: &str) -> Option<String> {
+    for tag in TAG.captures_iter(html) {
+        let name = tag[1].to_ascii_lowercase();
+        if DANGEROUS_TAGS.contains(&name.as_str()) {
+            return Some(format!("dangerous tag <{}>", name));
+        }
+
+        for attr in ATTRIBUTE.captures_iter(&tag[2]) {
+            let attr_name = attr[1].to_ascii_lowercase();
+            if attr_name.starts_with("on") {
+                return Some(format!("event handler {} on <{}>", attr_name, name));
+            }
+
+            let value = attr.get(2).map_or("", |m| m.as_str());
+            let url: String = html_escape::decode_html_entities(value.trim_matches(['"', '\'']))
+                .chars()
+                .filter(|c| !c.is_ascii_whitespace() && !c.is_control())
+                .collect::<String>()
+                .to_ascii_lowercase();
+            if matches!(attr_name.as_str(), "href" | "src")
+                && (url.starts_with("javascript:")
+                    || url.starts_with("vbscript:")
+                    || url.starts_with("data:text"))
+            {
+                return Some(format!("script URL in {} on <{}>", attr_name, name));
+            }
+        }
+    }
+    None
+}
+
+fn check_corpus(options: &ParserOptions) {
+    for payload in PAYLOADS {
+        let result = parse_with_options(payload, options);
+        let html = format!("{}{}", result.html, result.footnotes.unwrap_or_default());
+        if let Some(problem) = find_executable_markup(&html) {
+            panic!("{} for payload {:?}:\n{}", problem, payload, html);
+        }
+    }
+}
+
+#[test]
+fn test_corpus_default_pipeline() {
+    check_corpus(&ParserOptions::default());
+}
+
+#[test]
+fn test_corpus_sanitized_output() {
+    check_corpus(&ParserOptions {
+        sanitize_output: true,
+        ..Default::default()
+    });
+}
+
+#[test]
+fn test_checker_detects_payloads() {
+    assert!(find_executable_markup("<img src=x onerror=alert(1)>").is_some());
+    assert!(find_executable_markup("<a href=\"java&#09;script:x\">").is_some());
+    assert!(find_executable_markup("<p title=\"onclick=x\">").is_none());
+}